@@ -0,0 +1,373 @@
+#![deny(missing_docs)]
+
+//! Derive macro for the `Editor` trait of `piston-editor`.
+//!
+//! ```ignore
+//! #[derive(Editor)]
+//! struct Scene {
+//!     #[editor(type = "point", hit_2d = "hit_point")]
+//!     points: Vec<Point>,
+//!     #[editor(type = "line")]
+//!     lines: Vec<Line>,
+//!     #[editor(state)]
+//!     state: EditorState,
+//! }
+//! ```
+//!
+//! Every `Vec<T>` field with `#[editor(type = "...")]` stores the objects of a type.
+//! `T` must implement `Clone`.
+//! Selection, visibility and cursors are stored in the field with `#[editor(state)]`,
+//! which must be an `editor::EditorState`.
+//! Every object is visible until hidden.
+//!
+//! Hit testing is optional per type:
+//!
+//! - `hit_2d = "f"` where `f: fn(&T, [f64; 2]) -> bool`
+//! - `hit_3d = "f"` where `f: fn(&T, [f64; 3]) -> bool`
+//!
+//! Generic structs are not supported.
+
+extern crate proc_macro;
+
+use proc_macro::{Delimiter, Spacing, TokenStream, TokenTree};
+
+/// Implements `Editor` for a struct of `Vec` fields.
+#[proc_macro_derive(Editor, attributes(editor))]
+pub fn derive_editor(input: TokenStream) -> TokenStream {
+    let code = match parse(input) {
+        Ok(input) => generate(&input),
+        Err(msg) => format!("compile_error!({:?});", msg),
+    };
+    code.parse().unwrap()
+}
+
+/// A field storing the objects of a type.
+struct Field {
+    name: String,
+    item: String,
+    ty: String,
+    hit_2d: Option<String>,
+    hit_3d: Option<String>,
+}
+
+/// The parsed struct.
+struct Input {
+    name: String,
+    fields: Vec<Field>,
+    state: String,
+}
+
+/// The arguments of an `#[editor(...)]` attribute.
+#[derive(Default)]
+struct Args {
+    state: bool,
+    ty: Option<String>,
+    hit_2d: Option<String>,
+    hit_3d: Option<String>,
+}
+
+fn is_punct(tt: &TokenTree, ch: char) -> bool {
+    match *tt {
+        TokenTree::Punct(ref p) => p.as_char() == ch,
+        _ => false,
+    }
+}
+
+fn is_ident(tt: &TokenTree, name: &str) -> bool {
+    match *tt {
+        TokenTree::Ident(ref id) => id.to_string() == name,
+        _ => false,
+    }
+}
+
+/// Returns `true` for the `-` of `->`.
+fn is_arrow_start(tt: &TokenTree) -> bool {
+    match *tt {
+        TokenTree::Punct(ref p) => p.as_char() == '-' && p.spacing() == Spacing::Joint,
+        _ => false,
+    }
+}
+
+/// Splits tokens by commas outside angle brackets.
+fn split_commas(tokens: Vec<TokenTree>) -> Vec<Vec<TokenTree>> {
+    let mut res = vec![];
+    let mut cur: Vec<TokenTree> = vec![];
+    let mut depth = 0;
+    for tt in tokens {
+        let arrow = cur.last().map(is_arrow_start).unwrap_or(false);
+        if is_punct(&tt, '<') { depth += 1; }
+        if is_punct(&tt, '>') && !arrow { depth -= 1; }
+        if depth == 0 && is_punct(&tt, ',') {
+            res.push(cur);
+            cur = vec![];
+        } else {
+            cur.push(tt);
+        }
+    }
+    if !cur.is_empty() { res.push(cur); }
+    res
+}
+
+fn parse(input: TokenStream) -> Result<Input, String> {
+    let tokens: Vec<TokenTree> = input.into_iter().collect();
+    let pos = tokens.iter().position(|tt| is_ident(tt, "struct"))
+        .ok_or("`#[derive(Editor)]` only supports structs")?;
+    let name = match tokens.get(pos + 1) {
+        Some(TokenTree::Ident(id)) => id.to_string(),
+        _ => return Err("expected struct name".into()),
+    };
+    let body = match tokens.get(pos + 2) {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Brace => g.stream(),
+        Some(tt) if is_punct(tt, '<') =>
+            return Err("`#[derive(Editor)]` does not support generic structs".into()),
+        _ => return Err("`#[derive(Editor)]` requires a struct with named fields".into()),
+    };
+
+    let mut fields = vec![];
+    let mut state = None;
+    for field in split_commas(body.into_iter().collect()) {
+        let mut args = Args::default();
+        let mut i = 0;
+        // Attributes.
+        while i < field.len() && is_punct(&field[i], '#') {
+            if let Some(TokenTree::Group(g)) = field.get(i + 1) {
+                parse_attribute(g.stream(), &mut args)?;
+            }
+            i += 2;
+        }
+        // Visibility.
+        if i < field.len() && is_ident(&field[i], "pub") {
+            i += 1;
+            if let Some(TokenTree::Group(g)) = field.get(i) {
+                if g.delimiter() == Delimiter::Parenthesis { i += 1; }
+            }
+        }
+        let field_name = match field.get(i) {
+            Some(TokenTree::Ident(id)) => id.to_string(),
+            _ => return Err("expected field name".into()),
+        };
+        let field_ty = &field[(i + 2).min(field.len())..];
+
+        if args.state {
+            if state.is_some() {
+                return Err("only one field can have `#[editor(state)]`".into());
+            }
+            state = Some(field_name);
+        } else if let Some(ty) = args.ty {
+            if fields.iter().any(|f: &Field| f.ty == ty) {
+                return Err(format!("type `{}` is used by more than one field", ty));
+            }
+            let item = vec_item(field_ty).ok_or_else(|| format!(
+                "field `{}` with `#[editor(type = ...)]` must have type `Vec<T>`", field_name
+            ))?;
+            fields.push(Field {
+                name: field_name,
+                item,
+                ty,
+                hit_2d: args.hit_2d,
+                hit_3d: args.hit_3d,
+            });
+        }
+    }
+    if fields.is_empty() {
+        return Err("`#[derive(Editor)]` requires a field with `#[editor(type = ...)]`".into());
+    }
+    let state = state.ok_or(
+        "`#[derive(Editor)]` requires an `EditorState` field with `#[editor(state)]`"
+    )?;
+    Ok(Input { name, fields, state })
+}
+
+/// Parses the inside of `#[...]`, ignoring attributes other than `editor`.
+fn parse_attribute(attr: TokenStream, args: &mut Args) -> Result<(), String> {
+    let tokens: Vec<TokenTree> = attr.into_iter().collect();
+    if tokens.is_empty() || !is_ident(&tokens[0], "editor") { return Ok(()); }
+    let inner = match tokens.get(1) {
+        Some(TokenTree::Group(g)) if g.delimiter() == Delimiter::Parenthesis => g.stream(),
+        _ => return Err("expected `#[editor(...)]`".into()),
+    };
+    for arg in split_commas(inner.into_iter().collect()) {
+        let key = match arg.first() {
+            Some(TokenTree::Ident(id)) => id.to_string(),
+            _ => return Err("expected `state` or `key = \"value\"`".into()),
+        };
+        if key == "state" && arg.len() == 1 {
+            args.state = true;
+            continue;
+        }
+        let val = match (arg.get(1), arg.get(2), arg.len()) {
+            (Some(eq), Some(TokenTree::Literal(lit)), 3) if is_punct(eq, '=') => {
+                let lit = lit.to_string();
+                if lit.len() < 2 || !lit.starts_with('"') || !lit.ends_with('"') {
+                    return Err(format!("expected a string for `{}`", key));
+                }
+                lit[1..lit.len() - 1].to_string()
+            }
+            _ => return Err(format!("expected `{} = \"...\"`", key)),
+        };
+        match &key[..] {
+            "type" => args.ty = Some(val),
+            "hit_2d" => args.hit_2d = Some(val),
+            "hit_3d" => args.hit_3d = Some(val),
+            _ => return Err(format!("unknown editor attribute `{}`", key)),
+        }
+    }
+    Ok(())
+}
+
+/// Gets `T` from `Vec<T>`.
+fn vec_item(ty: &[TokenTree]) -> Option<String> {
+    let open = ty.iter().position(|tt| is_punct(tt, '<'))?;
+    if open == 0 || !is_ident(&ty[open - 1], "Vec") || !is_punct(ty.last()?, '>') {
+        return None;
+    }
+    let item: TokenStream = ty[open + 1..ty.len() - 1].iter().cloned().collect();
+    Some(item.to_string())
+}
+
+fn generate(input: &Input) -> String {
+    let state = &input.state;
+    // Matches the type of each field, returning from the method on unknown types.
+    let dispatch = |arm: &dyn Fn(&Field) -> String, unknown: &str| -> String {
+        let mut s = String::from("match ty {");
+        for f in &input.fields {
+            s.push_str(&format!("::editor::Type({:?}) => {{ {} }}", f.ty, arm(f)));
+        }
+        s.push_str(&format!("_ => {} }}", unknown));
+        s
+    };
+    let unknown = "return Err(::editor::EditorError::UnknownType(ty))";
+    let len = dispatch(&|f| format!("self.{}.len()", f.name), unknown);
+    let hits = |which: &str, arg: &str| -> String {
+        let fs: Vec<(&Field, &String)> = input.fields.iter().filter_map(|f| {
+            let hit = if which == "2d" { &f.hit_2d } else { &f.hit_3d };
+            hit.as_ref().map(|hit| (f, hit))
+        }).collect();
+        let mut s = format!(
+            "fn hit_{}(&self, {}pos: {}) -> Vec<(::editor::Type, ::editor::Object)> {{
+                let mut res = vec![];",
+            which, if fs.is_empty() { "_" } else { "" }, arg
+        );
+        for (f, hit) in fs {
+            s.push_str(&format!(
+                "{{
+                    let ty = ::editor::Type({ty:?});
+                    let hit: fn(&{item}, {arg}) -> bool = {hit};
+                    for obj in self.{state}.visible(ty, self.{name}.len()) {{
+                        if hit(&self.{name}[obj.0], pos) {{ res.push((ty, obj)); }}
+                    }}
+                }}",
+                ty = f.ty, item = f.item, arg = arg, hit = hit, state = state, name = f.name
+            ));
+        }
+        s.push_str("res }");
+        s
+    };
+
+    format!("
+impl ::editor::Editor for {name} {{
+    fn cursor_2d(&self) -> Option<[f64; 2]> {{
+        self.{state}.cursor_2d
+    }}
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {{
+        self.{state}.cursor_3d
+    }}
+
+    {hit_2d}
+
+    {hit_3d}
+
+    fn select(&mut self, ty: ::editor::Type, obj: ::editor::Object)
+    -> Result<(), ::editor::EditorError> {{
+        let len = {len};
+        self.{state}.select(ty, obj, len)
+    }}
+
+    fn select_multiple(&mut self, ty: ::editor::Type, objs: &[::editor::Object])
+    -> Result<(), ::editor::EditorError> {{
+        let len = {len};
+        self.{state}.select_multiple(ty, objs, len)
+    }}
+
+    fn deselect_multiple(&mut self, ty: ::editor::Type, objs: &[::editor::Object])
+    -> Result<(), ::editor::EditorError> {{
+        {known}
+        self.{state}.deselect_multiple(ty, objs);
+        Ok(())
+    }}
+
+    fn select_none(&mut self, ty: ::editor::Type) -> Result<(), ::editor::EditorError> {{
+        {known}
+        self.{state}.select_none(ty);
+        Ok(())
+    }}
+
+    fn insert(&mut self, ty: ::editor::Type, args: &dyn std::any::Any)
+    -> Result<::editor::Object, ::editor::EditorError> {{
+        {insert}
+    }}
+
+    fn delete(&mut self, ty: ::editor::Type, obj: ::editor::Object)
+    -> Result<Option<::editor::Object>, ::editor::EditorError> {{
+        let moved = {delete};
+        self.{state}.deleted(ty, obj, moved);
+        Ok(moved)
+    }}
+
+    fn update(&mut self, ty: ::editor::Type, obj: ::editor::Object, args: &dyn std::any::Any)
+    -> Result<(), ::editor::EditorError> {{
+        {update}
+    }}
+
+    fn replace(&mut self, ty: ::editor::Type, from: ::editor::Object, to: ::editor::Object)
+    -> Result<(), ::editor::EditorError> {{
+        {replace}
+    }}
+
+    fn get(&self, ty: ::editor::Type, obj: ::editor::Object)
+    -> Result<&dyn std::any::Any, ::editor::EditorError> {{
+        {get}
+    }}
+
+    fn visible(&self, ty: ::editor::Type) -> Vec<::editor::Object> {{
+        {visible}
+    }}
+
+    fn selected(&self, ty: ::editor::Type) -> Option<::editor::Object> {{
+        self.{state}.selected(ty)
+    }}
+
+    fn multiple_selected(&self, ty: ::editor::Type) -> Vec<::editor::Object> {{
+        self.{state}.multiple_selected(ty)
+    }}
+
+    fn all(&self, ty: ::editor::Type) -> Vec<::editor::Object> {{
+        {all}
+    }}
+
+    fn navigate_to(&mut self, ty: ::editor::Type, obj: ::editor::Object)
+    -> Result<(), ::editor::EditorError> {{
+        let len = {len};
+        self.{state}.set_visible(ty, obj, true, len)
+    }}
+}}
+",
+        name = input.name,
+        state = state,
+        hit_2d = hits("2d", "[f64; 2]"),
+        hit_3d = hits("3d", "[f64; 3]"),
+        len = len,
+        known = dispatch(&|_| String::new(), unknown),
+        insert = dispatch(&|f| format!("::editor::insert(&mut self.{}, args)", f.name), unknown),
+        delete = dispatch(&|f| format!("::editor::delete(ty, &mut self.{}, obj)?", f.name), unknown),
+        update = dispatch(&|f| format!("::editor::update(ty, &mut self.{}, obj, args)", f.name),
+            unknown),
+        replace = dispatch(&|f| format!("::editor::replace(ty, &mut self.{}, from, to)", f.name),
+            unknown),
+        get = dispatch(&|f| format!("::editor::get(ty, &self.{}, obj)", f.name), unknown),
+        visible = dispatch(&|f| format!("self.{}.visible(ty, self.{}.len())", state, f.name),
+            "vec![]"),
+        all = dispatch(&|f| format!("::editor::all(&self.{})", f.name), "vec![]"),
+    )
+}
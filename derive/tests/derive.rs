@@ -0,0 +1,78 @@
+extern crate editor;
+
+use std::collections::HashMap;
+
+use editor::{Conformance, Editor, EditorError, EditorState, Object, Type};
+
+const POINT: Type = Type("point");
+const TAGS: Type = Type("tags");
+
+fn hit_point(p: &[f64; 2], pos: [f64; 2]) -> bool {
+    (p[0] - pos[0]).abs() < 0.5 && (p[1] - pos[1]).abs() < 0.5
+}
+
+fn double(x: i32) -> i32 {
+    2 * x
+}
+
+#[derive(Editor)]
+struct Scene {
+    // Fields after a function pointer type must still be found.
+    hook: fn(i32) -> i32,
+    #[editor(type = "point", hit_2d = "hit_point")]
+    points: Vec<[f64; 2]>,
+    #[editor(type = "tags")]
+    tags: Vec<HashMap<String, Vec<u32>>>,
+    #[editor(state)]
+    state: EditorState,
+}
+
+fn scene() -> Scene {
+    Scene {
+        hook: double,
+        points: vec![],
+        tags: vec![],
+        state: EditorState::new(),
+    }
+}
+
+#[test]
+fn function_pointer_field() {
+    let mut scene = scene();
+    assert_eq!((scene.hook)(2), 4);
+    for i in 0..3 { scene.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+    assert_eq!(scene.hit_2d([1.1, 0.0]), vec![(POINT, Object(1))]);
+    scene.select_multiple(POINT, &[Object(0), Object(2)]).unwrap();
+    assert_eq!(scene.delete(POINT, Object(0)).unwrap(), Some(Object(2)));
+    assert_eq!(scene.points, vec![[2.0, 0.0], [1.0, 0.0]]);
+    assert_eq!(scene.multiple_selected(POINT), vec![Object(0)]);
+    assert_eq!(scene.selected(POINT), Some(Object(0)));
+}
+
+#[test]
+fn generic_item_type() {
+    let mut scene = scene();
+    let mut tags = HashMap::new();
+    tags.insert("a".to_string(), vec![1u32, 2]);
+    let obj = scene.insert(TAGS, &tags).unwrap();
+    assert_eq!(scene.get(TAGS, obj).unwrap().downcast_ref(), Some(&tags));
+    match scene.insert(TAGS, &0u32) {
+        Err(EditorError::DowncastMismatch { .. }) => {}
+        _ => panic!("expected a downcast mismatch"),
+    }
+    match scene.select(Type("unknown"), obj) {
+        Err(EditorError::UnknownType(_)) => {}
+        _ => panic!("expected an unknown type error"),
+    }
+    assert_eq!(scene.all(TAGS), vec![Object(0)]);
+}
+
+#[test]
+fn conformance() {
+    let mut tags = HashMap::new();
+    tags.insert("b".to_string(), vec![3u32]);
+    Conformance::new()
+        .sample(POINT, vec![[0.0, 0.0], [1.0, 2.0]])
+        .sample(TAGS, vec![HashMap::new(), tags])
+        .run(|| Box::new(scene()));
+}
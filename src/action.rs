@@ -0,0 +1,155 @@
+use {Editor, EditorError, Type};
+
+/// A reusable generic action.
+///
+/// Actions are executed through the `Editor` trait only,
+/// so the same action works with any editor.
+/// Run the action with `Transaction::run` to roll back on failure.
+pub trait Action {
+    /// The name of the action, used to look it up in a registry.
+    fn name(&self) -> &str;
+    /// A short description, for example used in menus and tooltips.
+    fn description(&self) -> &str;
+    /// The types the action applies to.
+    ///
+    /// When empty, the action does not depend on the selection.
+    fn types(&self) -> &[Type];
+    /// Returns `true` if the action can be executed in the current state.
+    fn can_execute(&self, _editor: &dyn Editor) -> bool { true }
+    /// Executes the action.
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError>;
+}
+
+/// Keeps a list of actions, for example to generate menus and command palettes.
+#[derive(Default)]
+pub struct ActionRegistry {
+    actions: Vec<Box<dyn Action>>,
+}
+
+impl ActionRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> ActionRegistry {
+        ActionRegistry::default()
+    }
+
+    /// Adds an action.
+    /// Replaces any action with the same name.
+    pub fn register<A: Action + 'static>(&mut self, action: A) {
+        self.actions.retain(|a| a.name() != action.name());
+        self.actions.push(Box::new(action));
+    }
+
+    /// Removes an action by name, returning `true` if it was found.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        let n = self.actions.len();
+        self.actions.retain(|a| a.name() != name);
+        self.actions.len() != n
+    }
+
+    /// Gets an action by name.
+    pub fn get(&self, name: &str) -> Option<&dyn Action> {
+        self.actions.iter().find(|a| a.name() == name).map(|a| &**a)
+    }
+
+    /// Gets all actions in the order they were registered.
+    pub fn actions(&self) -> Vec<&dyn Action> {
+        self.actions.iter().map(|a| &**a).collect()
+    }
+
+    /// Gets the actions that apply to a type.
+    pub fn for_type(&self, ty: Type) -> Vec<&dyn Action> {
+        self.actions.iter()
+            .filter(|a| a.types().contains(&ty))
+            .map(|a| &**a)
+            .collect()
+    }
+
+    /// Gets the actions valid for the current selection.
+    ///
+    /// An action is available when at least one of its types has
+    /// selected objects, or when it has no types,
+    /// and `Action::can_execute` returns `true`.
+    pub fn available(&self, editor: &dyn Editor) -> Vec<&dyn Action> {
+        self.actions.iter()
+            .filter(|a| {
+                let types = a.types();
+                types.is_empty() || types.iter().any(|&ty| has_selection(editor, ty))
+            })
+            .filter(|a| a.can_execute(editor))
+            .map(|a| &**a)
+            .collect()
+    }
+}
+
+fn has_selection(editor: &dyn Editor, ty: Type) -> bool {
+    editor.selected(ty).is_some() || !editor.multiple_selected(ty).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Object, VecEditor};
+
+    const POINT: Type = Type("point");
+
+    struct Clear {
+        types: Vec<Type>,
+    }
+
+    impl Action for Clear {
+        fn name(&self) -> &str { "clear" }
+        fn description(&self) -> &str { "Deletes the selected objects" }
+        fn types(&self) -> &[Type] { &self.types }
+        fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+            for &ty in &self.types {
+                while let Some(obj) = editor.multiple_selected(ty).pop() {
+                    editor.delete(ty, obj)?;
+                }
+            }
+            Ok(())
+        }
+    }
+
+    struct Reset;
+
+    impl Action for Reset {
+        fn name(&self) -> &str { "reset" }
+        fn description(&self) -> &str { "Does nothing" }
+        fn types(&self) -> &[Type] { &[] }
+        fn can_execute(&self, editor: &dyn Editor) -> bool { editor.cursor_2d().is_some() }
+        fn execute(&self, _editor: &mut dyn Editor) -> Result<(), EditorError> { Ok(()) }
+    }
+
+    #[test]
+    fn registry() {
+        let mut registry = ActionRegistry::new();
+        registry.register(Clear { types: vec![] });
+        registry.register(Reset);
+        registry.register(Clear { types: vec![POINT] });
+        let names: Vec<&str> = registry.actions().iter().map(|a| a.name()).collect();
+        assert_eq!(names, vec!["reset", "clear"]);
+        assert_eq!(registry.for_type(POINT).len(), 1);
+        assert!(registry.unregister("reset"));
+        assert!(!registry.unregister("reset"));
+        assert!(registry.get("reset").is_none());
+    }
+
+    #[test]
+    fn available() {
+        let mut registry = ActionRegistry::new();
+        registry.register(Clear { types: vec![POINT] });
+        registry.register(Reset);
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        editor.insert(POINT, &0u32).unwrap();
+        assert!(registry.available(&editor).is_empty());
+
+        editor.set_cursor_2d(Some([0.0, 0.0]));
+        editor.select(POINT, Object(0)).unwrap();
+        let names: Vec<&str> = registry.available(&editor).iter().map(|a| a.name()).collect();
+        assert_eq!(names, vec!["clear", "reset"]);
+
+        registry.get("clear").unwrap().execute(&mut editor).unwrap();
+        assert!(editor.all(POINT).is_empty());
+    }
+}
@@ -0,0 +1,574 @@
+use std::any::Any;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use binary::Writer;
+use journal::{read_binary, write_header, write_record};
+use {
+    Bounds2, Document, Editor, EditorError, Hit, Journal, Object, Operation, Plane, Recording,
+    Type, TypeRegistry, View, ViewId,
+};
+
+const MARKER: &str = "session.lock";
+const SNAPSHOT: &str = "snapshot";
+const SNAPSHOT_EXT: &str = "ped";
+const JOURNAL: &str = "journal";
+const JOURNAL_EXT: &str = "pedj";
+
+/// Wraps an editor and keeps an autosave, for recovering edits after a crash.
+///
+/// The autosave is a directory with a snapshot of the editor and an append-only journal
+/// of the operations made since, see `Recording`.
+/// Every operation is written to the journal and synced to disk before returning.
+/// A new snapshot is written periodically, which starts a new journal.
+/// Snapshots are saved with `Document::save`, so every edited type needs value conversions.
+/// Snapshots do not include the selection, so each journal starts with the selection
+/// at the time of its snapshot.
+///
+/// A marker file is kept in the directory until `close` is called.
+/// On restart, use `Recovery::detect` before creating a new autosave,
+/// to find out whether the last session ended without `close`.
+///
+/// IO errors are returned as `EditorError::Custom` holding an `io::Error`.
+/// When an operation can not be written to the journal,
+/// a new snapshot is written instead, such that no operation is lost.
+/// If that fails too, the error is returned even though the operation was made,
+/// and every following operation tries to write a snapshot until one succeeds.
+pub struct Autosave<E: Editor> {
+    recording: Recording<E>,
+    types: Rc<TypeRegistry>,
+    dir: PathBuf,
+    generation: u64,
+    journal: File,
+    operations: usize,
+    last_snapshot: Instant,
+    interval: Duration,
+    max_operations: usize,
+    failed: bool,
+}
+
+impl<E: Editor> Autosave<E> {
+    /// Creates a new autosave in a directory, writing a snapshot of the editor.
+    ///
+    /// The directory is created if needed.
+    /// Any earlier autosave in the directory is removed.
+    /// Snapshots are written every 60 seconds or 1000 operations.
+    pub fn new<P: AsRef<Path>>(editor: E, types: Rc<TypeRegistry>, dir: P)
+    -> Result<Autosave<E>, EditorError> {
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir).map_err(io_error)?;
+        remove_files(&dir)?;
+        File::create(dir.join(MARKER)).map_err(io_error)?;
+        let generation = 0;
+        write_snapshot(Document::save(&editor, &types)?, &dir, generation)?;
+        let journal = create_journal(&dir, generation, &selection(&editor, &types))?;
+        Ok(Autosave {
+            recording: Recording::new(editor, types.clone()),
+            types,
+            dir,
+            generation,
+            journal,
+            operations: 0,
+            last_snapshot: Instant::now(),
+            interval: Duration::from_secs(60),
+            max_operations: 1000,
+            failed: false,
+        })
+    }
+
+    /// Sets the time between snapshots.
+    ///
+    /// The time is checked when operations are made.
+    pub fn set_interval(&mut self, interval: Duration) {
+        self.interval = interval;
+    }
+
+    /// Sets the number of operations between snapshots.
+    pub fn set_max_operations(&mut self, max_operations: usize) {
+        self.max_operations = max_operations;
+    }
+
+    /// Gets a reference to the wrapped editor.
+    pub fn get_ref(&self) -> &E {
+        self.recording.get_ref()
+    }
+
+    /// Gets the directory of the autosave.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Writes a snapshot now and starts a new journal.
+    ///
+    /// When the new journal can not be created, the new snapshot is removed,
+    /// such that recovery uses the old snapshot and journal,
+    /// and every following operation tries to write a snapshot until one succeeds.
+    pub fn snapshot(&mut self) -> Result<(), EditorError> {
+        let generation = self.generation + 1;
+        let document = Document::save(self.recording.get_ref(), &self.types)?;
+        write_snapshot(document, &self.dir, generation)?;
+        let ops = selection(self.recording.get_ref(), &self.types);
+        self.journal = match create_journal(&self.dir, generation, &ops) {
+            Ok(journal) => journal,
+            Err(err) => {
+                // Recovery picks the newest snapshot, which would have no journal.
+                let _ = fs::remove_file(file(&self.dir, SNAPSHOT, generation, SNAPSHOT_EXT));
+                self.failed = true;
+                return Err(err);
+            }
+        };
+        // The new snapshot is complete, so the old files are not needed for recovery.
+        let _ = fs::remove_file(file(&self.dir, SNAPSHOT, self.generation, SNAPSHOT_EXT));
+        let _ = fs::remove_file(file(&self.dir, JOURNAL, self.generation, JOURNAL_EXT));
+        self.generation = generation;
+        self.operations = 0;
+        self.last_snapshot = Instant::now();
+        self.failed = false;
+        Ok(())
+    }
+
+    /// Ends the session cleanly, removing the autosave.
+    ///
+    /// Returns the wrapped editor.
+    pub fn close(self) -> Result<E, EditorError> {
+        remove_files(&self.dir)?;
+        Ok(self.recording.into_inner())
+    }
+
+    /// Writes the operations recorded since the last call to the journal.
+    fn flush(&mut self) -> Result<(), EditorError> {
+        let operations = self.recording.take_journal().operations;
+        if operations.is_empty() { return Ok(()); }
+        self.operations += operations.len();
+        if !self.failed {
+            let mut w = Writer::new();
+            for op in &operations { write_record(op, &mut w)?; }
+            let res = self.journal.write_all(&w.bytes).and_then(|_| self.journal.sync_data());
+            // The journal might end with a partial record, so it can not be appended to.
+            if res.is_err() { self.failed = true; }
+        }
+        if self.failed ||
+           self.operations >= self.max_operations ||
+           self.last_snapshot.elapsed() >= self.interval {
+            self.snapshot()?;
+        }
+        Ok(())
+    }
+
+    fn with<T, F>(&mut self, f: F) -> Result<T, EditorError>
+        where F: FnOnce(&mut Recording<E>) -> Result<T, EditorError>
+    {
+        let res = f(&mut self.recording)?;
+        self.flush()?;
+        Ok(res)
+    }
+}
+
+impl<E: Editor> Editor for Autosave<E> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.recording.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.recording.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.recording.hit_2d(pos)
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.recording.hit_rect_2d(rect)
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.recording.hit_polygon_2d(points)
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.recording.hit_3d(pos)
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.recording.hit_frustum(planes)
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.recording.hit_ray(origin, direction)
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.with(|e| e.select(ty, obj))
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.with(|e| e.select_multiple(ty, objs))
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.with(|e| e.deselect_multiple(ty, objs))
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.with(|e| e.select_none(ty))
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        self.with(|e| e.insert(ty, args))
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        self.with(|e| e.delete(ty, obj))
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        self.with(|e| e.update(ty, obj, args))
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        self.with(|e| e.replace(ty, from, to))
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.recording.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.recording.visible(ty)
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.recording.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.recording.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.recording.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.with(|e| e.navigate_to(ty, obj))
+    }
+
+    fn refresh_views(&mut self) {
+        self.recording.refresh_views()
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.recording.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.recording.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.recording.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.recording.set_active_view(id)
+    }
+}
+
+/// An autosave left by a session that did not end with `Autosave::close`,
+/// for example because the program crashed.
+///
+/// Recovering loads the last snapshot and replays the journal written after it.
+/// Object ids in the journal refer to the objects as they were in the editor,
+/// so recover into an empty editor that gives loaded objects the ids they were saved with,
+/// such as `VecEditor` or an editor with `#[derive(Editor)]`.
+/// An operation that was being written during the crash is ignored.
+#[derive(Clone, Debug)]
+pub struct Recovery {
+    dir: PathBuf,
+    generation: u64,
+}
+
+impl Recovery {
+    /// Checks a directory for an autosave left by an unclean shutdown.
+    pub fn detect<P: AsRef<Path>>(dir: P) -> Result<Option<Recovery>, EditorError> {
+        let dir = dir.as_ref();
+        if !dir.join(MARKER).exists() { return Ok(None); }
+        let generation = match fs::read_dir(dir) {
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => None,
+            Err(err) => return Err(io_error(err)),
+            Ok(entries) => {
+                let mut generation = None;
+                for entry in entries {
+                    let path = entry.map_err(io_error)?.path();
+                    if path.extension().and_then(|ext| ext.to_str()) != Some(SNAPSHOT_EXT) {
+                        continue;
+                    }
+                    let g = path.file_stem()
+                        .and_then(|stem| stem.to_str())
+                        .and_then(|stem| stem.strip_prefix(SNAPSHOT))
+                        .and_then(|g| g.strip_prefix('-'))
+                        .and_then(|g| g.parse::<u64>().ok());
+                    if let Some(g) = g { generation = generation.max(Some(g)); }
+                }
+                generation
+            }
+        };
+        Ok(generation.map(|generation| Recovery { dir: dir.to_path_buf(), generation }))
+    }
+
+    /// Reads the snapshot and the journal of operations made after it.
+    pub fn load(&self, types: &TypeRegistry) -> Result<(Document, Journal), EditorError> {
+        let snapshot = fs::read(file(&self.dir, SNAPSHOT, self.generation, SNAPSHOT_EXT))
+            .map_err(io_error)?;
+        let document = Document::from_binary(&snapshot, types)?;
+        let journal = match fs::read(file(&self.dir, JOURNAL, self.generation, JOURNAL_EXT)) {
+            // The crash happened before the journal was created.
+            Err(ref err) if err.kind() == io::ErrorKind::NotFound => Journal::new(),
+            Err(err) => return Err(io_error(err)),
+            Ok(bytes) => read_binary(&bytes, types, true)?,
+        };
+        Ok((document, journal))
+    }
+
+    /// Loads the snapshot into an empty editor and replays the journal.
+    ///
+    /// Use `Transaction::run` to roll back on failure.
+    pub fn recover(&self, editor: &mut dyn Editor, types: &TypeRegistry)
+    -> Result<(), EditorError> {
+        let (document, journal) = self.load(types)?;
+        document.load(editor, types)?;
+        journal.replay(editor, types)
+    }
+
+    /// Removes the autosave.
+    pub fn discard(self) -> Result<(), EditorError> {
+        remove_files(&self.dir)
+    }
+}
+
+fn io_error(err: io::Error) -> EditorError {
+    EditorError::Custom(Box::new(err))
+}
+
+fn file(dir: &Path, name: &str, generation: u64, ext: &str) -> PathBuf {
+    dir.join(format!("{}-{}.{}", name, generation, ext))
+}
+
+/// Writes a snapshot to a temporary file and renames it,
+/// such that a snapshot is either complete or missing.
+fn write_snapshot(document: Document, dir: &Path, generation: u64) -> Result<(), EditorError> {
+    let bytes = document.to_binary()?;
+    let tmp = dir.join(format!("{}.tmp", SNAPSHOT));
+    let write = || -> io::Result<()> {
+        let mut f = File::create(&tmp)?;
+        f.write_all(&bytes)?;
+        f.sync_all()?;
+        fs::rename(&tmp, file(dir, SNAPSHOT, generation, SNAPSHOT_EXT))?;
+        sync_dir(dir);
+        Ok(())
+    };
+    write().map_err(io_error)
+}
+
+/// Gets the operations restoring the selection of every type with value conversions.
+fn selection(editor: &dyn Editor, types: &TypeRegistry) -> Vec<Operation> {
+    let mut ops = vec![];
+    for ty in types.types() {
+        if !types.schema(ty).map(|schema| schema.has_values()).unwrap_or(false) { continue; }
+        let multiple = editor.multiple_selected(ty);
+        ops.push(Operation::SelectNone(ty));
+        if !multiple.is_empty() { ops.push(Operation::SelectMultiple(ty, multiple.clone())); }
+        // Selecting the first object is done by `SelectMultiple`.
+        match editor.selected(ty) {
+            Some(obj) if multiple.first() != Some(&obj) && multiple.contains(&obj) =>
+                ops.push(Operation::Select(ty, obj)),
+            _ => {}
+        }
+    }
+    ops
+}
+
+/// Creates a journal starting with some operations.
+fn create_journal(dir: &Path, generation: u64, ops: &[Operation]) -> Result<File, EditorError> {
+    let mut w = Writer::new();
+    write_header(&mut w);
+    for op in ops { write_record(op, &mut w)?; }
+    let create = || -> io::Result<File> {
+        let mut f = OpenOptions::new().create(true).write(true).truncate(true)
+            .open(file(dir, JOURNAL, generation, JOURNAL_EXT))?;
+        f.write_all(&w.bytes)?;
+        f.sync_all()?;
+        sync_dir(dir);
+        Ok(f)
+    };
+    create().map_err(io_error)
+}
+
+/// Syncs a directory, such that renamed and created files are kept after a crash.
+///
+/// Not supported on every platform, so errors are ignored.
+fn sync_dir(dir: &Path) {
+    let _ = File::open(dir).and_then(|dir| dir.sync_all());
+}
+
+/// Removes the files of an autosave, keeping other files in the directory.
+fn remove_files(dir: &Path) -> Result<(), EditorError> {
+    let entries = match fs::read_dir(dir) {
+        Err(ref err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+        entries => entries.map_err(io_error)?,
+    };
+    for entry in entries {
+        let path = entry.map_err(io_error)?.path();
+        let name = match path.file_name().and_then(|name| name.to_str()) {
+            None => continue,
+            Some(name) => name.to_string(),
+        };
+        let ours = name == MARKER ||
+            name.starts_with(&format!("{}-", SNAPSHOT)) ||
+            name.starts_with(&format!("{}-", JOURNAL)) ||
+            name == format!("{}.tmp", SNAPSHOT);
+        if ours { fs::remove_file(&path).map_err(io_error)?; }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    fn types() -> Rc<TypeRegistry> {
+        let mut types = TypeRegistry::new();
+        types.register::<[f64; 2]>(POINT).values();
+        Rc::new(types)
+    }
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor
+    }
+
+    fn points(editor: &VecEditor) -> Vec<[f64; 2]> {
+        editor.items::<[f64; 2]>(POINT).unwrap().to_vec()
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = ::std::env::temp_dir()
+            .join(format!("piston-editor-autosave-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    fn edit(autosave: &mut Autosave<VecEditor>) {
+        for i in 0..3 { autosave.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        autosave.update(POINT, Object(1), &[5.0, 5.0]).unwrap();
+        autosave.delete(POINT, Object(0)).unwrap();
+        autosave.select(POINT, Object(1)).unwrap();
+    }
+
+    #[test]
+    fn recover_after_crash() {
+        let dir = temp_dir("crash");
+        let types = types();
+        let mut autosave = Autosave::new(editor(), types.clone(), &dir).unwrap();
+        edit(&mut autosave);
+        let expected = points(autosave.get_ref());
+        // Dropping without `close` leaves the autosave behind, like a crash.
+        drop(autosave);
+
+        let recovery = Recovery::detect(&dir).unwrap().expect("expected an autosave");
+        let mut recovered = editor();
+        recovery.recover(&mut recovered, &types).unwrap();
+        assert_eq!(points(&recovered), expected);
+        assert_eq!(recovered.selected(POINT), Some(Object(1)));
+
+        recovery.discard().unwrap();
+        assert!(Recovery::detect(&dir).unwrap().is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn recover_after_snapshot() {
+        let dir = temp_dir("snapshot");
+        let types = types();
+        let mut autosave = Autosave::new(editor(), types.clone(), &dir).unwrap();
+        autosave.set_max_operations(2);
+        edit(&mut autosave);
+        autosave.insert(POINT, &[9.0, 9.0]).unwrap();
+        autosave.select_multiple(POINT, &[Object(0), Object(2)]).unwrap();
+        // The selection is kept by the snapshot taken after the last selection change.
+        autosave.insert(POINT, &[10.0, 10.0]).unwrap();
+        let expected = points(autosave.get_ref());
+        drop(autosave);
+
+        let recovery = Recovery::detect(&dir).unwrap().expect("expected an autosave");
+        let mut recovered = editor();
+        recovery.recover(&mut recovered, &types).unwrap();
+        assert_eq!(points(&recovered), expected);
+        assert_eq!(recovered.multiple_selected(POINT), vec![Object(1), Object(0), Object(2)]);
+        assert_eq!(recovered.selected(POINT), Some(Object(1)));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn failed_journal() {
+        let dir = temp_dir("journal");
+        let types = types();
+        let mut autosave = Autosave::new(editor(), types.clone(), &dir).unwrap();
+        autosave.insert(POINT, &[1.0, 0.0]).unwrap();
+        // A directory in the place of the next journal makes creating it fail.
+        let blocker = file(&dir, JOURNAL, 1, JOURNAL_EXT);
+        fs::create_dir(&blocker).unwrap();
+        assert!(autosave.snapshot().is_err());
+        let recovery = Recovery::detect(&dir).unwrap().expect("expected an autosave");
+        let mut recovered = editor();
+        recovery.recover(&mut recovered, &types).unwrap();
+        assert_eq!(points(&recovered), vec![[1.0, 0.0]]);
+
+        // The next operation writes a snapshot instead of appending to the old journal.
+        fs::remove_dir(&blocker).unwrap();
+        autosave.insert(POINT, &[2.0, 0.0]).unwrap();
+        drop(autosave);
+        let recovery = Recovery::detect(&dir).unwrap().expect("expected an autosave");
+        let mut recovered = editor();
+        recovery.recover(&mut recovered, &types).unwrap();
+        assert_eq!(points(&recovered), vec![[1.0, 0.0], [2.0, 0.0]]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn close() {
+        let dir = temp_dir("close");
+        let mut autosave = Autosave::new(editor(), types(), &dir).unwrap();
+        edit(&mut autosave);
+        let editor = autosave.close().unwrap();
+        assert_eq!(points(&editor), vec![[2.0, 0.0], [5.0, 5.0]]);
+        assert!(Recovery::detect(&dir).unwrap().is_none());
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn new_removes_earlier_autosave() {
+        let dir = temp_dir("new");
+        let mut autosave = Autosave::new(editor(), types(), &dir).unwrap();
+        edit(&mut autosave);
+        drop(autosave);
+
+        let autosave = Autosave::new(editor(), types(), &dir).unwrap();
+        drop(autosave);
+        let recovery = Recovery::detect(&dir).unwrap().expect("expected an autosave");
+        let (document, journal) = recovery.load(&types()).unwrap();
+        let mut recovered = editor();
+        document.load(&mut recovered, &types()).unwrap();
+        assert!(points(&recovered).is_empty());
+        assert_eq!(journal.operations, vec![Operation::SelectNone(POINT)]);
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
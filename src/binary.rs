@@ -0,0 +1,289 @@
+//! A compact binary encoding of values used by documents.
+//!
+//! Integers and lengths are written as LEB128 varints,
+//! with signed integers zigzag encoded.
+//! Floats are written as little endian.
+
+use value::MAX_DEPTH;
+use {EditorError, Object, Type, Value};
+
+const BOOL: u8 = 0;
+const INT: u8 = 1;
+const FLOAT: u8 = 2;
+const STRING: u8 = 3;
+const VEC2: u8 = 4;
+const VEC3: u8 = 5;
+const COLOR: u8 = 6;
+const REFERENCE: u8 = 7;
+const LIST: u8 = 8;
+const MAP: u8 = 9;
+const UINT: u8 = 10;
+
+/// Writes binary data.
+pub(crate) struct Writer {
+    pub bytes: Vec<u8>,
+}
+
+impl Writer {
+    pub fn new() -> Writer {
+        Writer { bytes: vec![] }
+    }
+
+    pub fn u8(&mut self, x: u8) {
+        self.bytes.push(x);
+    }
+
+    pub fn varint(&mut self, mut x: u64) {
+        loop {
+            let byte = (x & 0x7f) as u8;
+            x >>= 7;
+            if x == 0 {
+                self.bytes.push(byte);
+                return;
+            }
+            self.bytes.push(byte | 0x80);
+        }
+    }
+
+    pub fn f64(&mut self, x: f64) {
+        self.bytes.extend_from_slice(&x.to_le_bytes());
+    }
+
+    pub fn f32(&mut self, x: f32) {
+        self.bytes.extend_from_slice(&x.to_le_bytes());
+    }
+
+    pub fn str(&mut self, x: &str) {
+        self.varint(x.len() as u64);
+        self.bytes.extend_from_slice(x.as_bytes());
+    }
+
+    /// Writes a value, using the index of the type in `types` for references.
+    pub fn value(&mut self, val: &Value, types: &[Type]) -> Result<(), EditorError> {
+        match *val {
+            Value::Bool(x) => {
+                self.u8(BOOL);
+                self.u8(x as u8);
+            }
+            Value::Int(x) => {
+                self.u8(INT);
+                self.varint(((x << 1) ^ (x >> 63)) as u64);
+            }
+            Value::UInt(x) => {
+                self.u8(UINT);
+                self.varint(x);
+            }
+            Value::Float(x) => {
+                self.u8(FLOAT);
+                self.f64(x);
+            }
+            Value::String(ref x) => {
+                self.u8(STRING);
+                self.str(x);
+            }
+            Value::Vec2(x) => {
+                self.u8(VEC2);
+                for &x in &x { self.f64(x); }
+            }
+            Value::Vec3(x) => {
+                self.u8(VEC3);
+                for &x in &x { self.f64(x); }
+            }
+            Value::Color(x) => {
+                self.u8(COLOR);
+                for &x in &x { self.f32(x); }
+            }
+            Value::Reference(ty, obj) => {
+                let index = match types.iter().position(|&t| t == ty) {
+                    None => return Err(EditorError::UnknownType(ty)),
+                    Some(index) => index,
+                };
+                self.u8(REFERENCE);
+                self.varint(index as u64);
+                self.varint(obj.0 as u64);
+            }
+            Value::List(ref items) => {
+                self.u8(LIST);
+                self.varint(items.len() as u64);
+                for item in items { self.value(item, types)?; }
+            }
+            Value::Map(ref map) => {
+                self.u8(MAP);
+                self.varint(map.len() as u64);
+                for (key, item) in map {
+                    self.str(key);
+                    self.value(item, types)?;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Reads binary data.
+pub(crate) struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(bytes: &'a [u8]) -> Reader<'a> {
+        Reader { bytes, pos: 0, depth: 0 }
+    }
+
+    pub fn is_at_end(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    pub fn error(&self, msg: &str) -> EditorError {
+        EditorError::InvalidData(format!("invalid binary data at byte {}: {}", self.pos, msg))
+    }
+
+    pub fn take(&mut self, n: usize) -> Result<&'a [u8], EditorError> {
+        if self.bytes.len() - self.pos < n { return Err(self.error("unexpected end of data")); }
+        let res = &self.bytes[self.pos..self.pos + n];
+        self.pos += n;
+        Ok(res)
+    }
+
+    pub fn u8(&mut self) -> Result<u8, EditorError> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn varint(&mut self) -> Result<u64, EditorError> {
+        let mut res = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.u8()?;
+            if shift >= 64 { return Err(self.error("varint too long")); }
+            res |= ((byte & 0x7f) as u64) << shift;
+            if byte & 0x80 == 0 { return Ok(res); }
+            shift += 7;
+        }
+    }
+
+    /// Reads a length, checking that it is not larger than the remaining data.
+    pub fn len(&mut self) -> Result<usize, EditorError> {
+        let n = self.varint()?;
+        if n > (self.bytes.len() - self.pos) as u64 { return Err(self.error("invalid length")); }
+        Ok(n as usize)
+    }
+
+    pub fn f64(&mut self) -> Result<f64, EditorError> {
+        let mut buf = [0; 8];
+        buf.copy_from_slice(self.take(8)?);
+        Ok(f64::from_le_bytes(buf))
+    }
+
+    pub fn f32(&mut self) -> Result<f32, EditorError> {
+        let mut buf = [0; 4];
+        buf.copy_from_slice(self.take(4)?);
+        Ok(f32::from_le_bytes(buf))
+    }
+
+    pub fn str(&mut self) -> Result<String, EditorError> {
+        let n = self.len()?;
+        let bytes = self.take(n)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.error("invalid UTF-8"))
+    }
+
+    /// Reads a value, looking up the types of references by index in `types`.
+    pub fn value(&mut self, types: &[Type]) -> Result<Value, EditorError> {
+        if self.depth >= MAX_DEPTH { return Err(self.error("nested too deeply")); }
+        self.depth += 1;
+        let res = self.nested_value(types);
+        self.depth -= 1;
+        res
+    }
+
+    fn nested_value(&mut self, types: &[Type]) -> Result<Value, EditorError> {
+        Ok(match self.u8()? {
+            BOOL => Value::Bool(self.u8()? != 0),
+            INT => {
+                let x = self.varint()?;
+                Value::Int(((x >> 1) as i64) ^ -((x & 1) as i64))
+            }
+            UINT => Value::UInt(self.varint()?),
+            FLOAT => Value::Float(self.f64()?),
+            STRING => Value::String(self.str()?),
+            VEC2 => Value::Vec2([self.f64()?, self.f64()?]),
+            VEC3 => Value::Vec3([self.f64()?, self.f64()?, self.f64()?]),
+            COLOR => Value::Color([self.f32()?, self.f32()?, self.f32()?, self.f32()?]),
+            REFERENCE => {
+                let ty = match types.get(self.varint()? as usize) {
+                    None => return Err(self.error("invalid type index")),
+                    Some(&ty) => ty,
+                };
+                Value::Reference(ty, Object(self.varint()? as usize))
+            }
+            LIST => {
+                let n = self.len()?;
+                let mut items = Vec::with_capacity(n);
+                for _ in 0..n { items.push(self.value(types)?); }
+                Value::List(items)
+            }
+            MAP => {
+                let n = self.len()?;
+                let mut map = ::std::collections::BTreeMap::new();
+                for _ in 0..n {
+                    let key = self.str()?;
+                    map.insert(key, self.value(types)?);
+                }
+                Value::Map(map)
+            }
+            _ => return Err(self.error("invalid value tag")),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    #[test]
+    fn round_trip() {
+        let val = Value::List(vec![
+            Value::Int(-300),
+            Value::Int(i64::MIN),
+            Value::UInt(u64::MAX),
+            Value::Float(0.5),
+            Value::String("é".into()),
+            Value::Color([0.0, 0.25, 0.5, 1.0]),
+            Value::Reference(POINT, Object(7)),
+            Value::map(vec![("v", Value::Vec3([1.0, 2.0, 3.0])), ("b", Value::Bool(true))]),
+        ]);
+        let mut w = Writer::new();
+        w.value(&val, &[POINT]).unwrap();
+        let mut r = Reader::new(&w.bytes);
+        assert_eq!(r.value(&[POINT]).unwrap(), val);
+        assert!(r.is_at_end());
+        assert!(Reader::new(&w.bytes).value(&[]).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let mut w = Writer::new();
+        w.value(&Value::String("hello".into()), &[]).unwrap();
+        let bytes = &w.bytes[..w.bytes.len() - 1];
+        assert!(Reader::new(bytes).value(&[]).is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        let nested = |n: usize| {
+            let mut bytes = vec![];
+            for _ in 0..n { bytes.extend_from_slice(&[LIST, 1]); }
+            bytes.extend_from_slice(&[BOOL, 1]);
+            bytes
+        };
+        assert!(Reader::new(&nested(MAX_DEPTH - 1)).value(&[]).is_ok());
+        assert!(Reader::new(&nested(MAX_DEPTH)).value(&[]).is_err());
+        match Reader::new(&nested(1_000_000)).value(&[]) {
+            Err(EditorError::InvalidData(msg)) => assert!(msg.contains("nested too deeply")),
+            _ => panic!("expected invalid data"),
+        }
+    }
+}
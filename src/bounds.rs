@@ -0,0 +1,346 @@
+use {EditorError, Object, Type};
+
+/// An axis aligned bounding box in 2D.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds2 {
+    /// The minimum corner.
+    pub min: [f64; 2],
+    /// The maximum corner.
+    pub max: [f64; 2],
+}
+
+impl Bounds2 {
+    /// Creates bounds from two corners in any order.
+    pub fn new(a: [f64; 2], b: [f64; 2]) -> Bounds2 {
+        Bounds2 {
+            min: [a[0].min(b[0]), a[1].min(b[1])],
+            max: [a[0].max(b[0]), a[1].max(b[1])],
+        }
+    }
+
+    /// Creates bounds of a point.
+    pub fn point(pos: [f64; 2]) -> Bounds2 {
+        Bounds2 { min: pos, max: pos }
+    }
+
+    /// Returns `true` if every coordinate is finite.
+    pub fn is_finite(&self) -> bool {
+        self.min.iter().chain(&self.max).all(|x| x.is_finite())
+    }
+
+    /// Returns `true` if the point is inside, including the border.
+    pub fn contains(&self, pos: [f64; 2]) -> bool {
+        (0..2).all(|i| self.min[i] <= pos[i] && pos[i] <= self.max[i])
+    }
+
+    /// Returns `true` if the bounds overlap, including touching borders.
+    pub fn intersects(&self, other: &Bounds2) -> bool {
+        (0..2).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    /// Returns `true` if the other bounds are completely inside.
+    pub fn encloses(&self, other: &Bounds2) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// Gets the distance along a ray where it enters the bounds.
+    ///
+    /// Returns zero when the ray starts inside.
+    pub fn ray(&self, origin: [f64; 2], direction: [f64; 2]) -> Option<f64> {
+        slab(&origin, &direction, &self.min, &self.max)
+    }
+
+    /// Gets the bounds of a polygon, or `None` if it has no points.
+    pub fn polygon(points: &[[f64; 2]]) -> Option<Bounds2> {
+        let first = Bounds2::point(*points.first()?);
+        Some(points.iter().fold(first, |b, &p| Bounds2 {
+            min: [b.min[0].min(p[0]), b.min[1].min(p[1])],
+            max: [b.max[0].max(p[0]), b.max[1].max(p[1])],
+        }))
+    }
+
+    /// Returns `true` if the bounds overlap a closed polygon,
+    /// for example a lasso.
+    pub fn intersects_polygon(&self, points: &[[f64; 2]]) -> bool {
+        if points.is_empty() { return false; }
+        let center = [(self.min[0] + self.max[0]) / 2.0, (self.min[1] + self.max[1]) / 2.0];
+        if polygon_contains(points, center) { return true; }
+        if points.iter().any(|&p| self.contains(p)) { return true; }
+        // Check the edges of the polygon against the edges of the bounds.
+        let corners = [
+            self.min, [self.max[0], self.min[1]], self.max, [self.min[0], self.max[1]]
+        ];
+        (0..points.len()).any(|i| {
+            let (a, b) = (points[i], points[(i + 1) % points.len()]);
+            (0..4).any(|j| segments_intersect(a, b, corners[j], corners[(j + 1) % 4]))
+        })
+    }
+}
+
+/// Returns `true` if a point is inside a closed polygon, using the even-odd rule.
+pub fn polygon_contains(points: &[[f64; 2]], pos: [f64; 2]) -> bool {
+    let mut inside = false;
+    let n = points.len();
+    for i in 0..n {
+        let (a, b) = (points[i], points[(i + n - 1) % n]);
+        if (a[1] > pos[1]) != (b[1] > pos[1]) &&
+            pos[0] < (b[0] - a[0]) * (pos[1] - a[1]) / (b[1] - a[1]) + a[0] {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+fn segments_intersect(a: [f64; 2], b: [f64; 2], c: [f64; 2], d: [f64; 2]) -> bool {
+    fn cross(o: [f64; 2], a: [f64; 2], b: [f64; 2]) -> f64 {
+        (a[0] - o[0]) * (b[1] - o[1]) - (a[1] - o[1]) * (b[0] - o[0])
+    }
+    let (d1, d2) = (cross(c, d, a), cross(c, d, b));
+    let (d3, d4) = (cross(a, b, c), cross(a, b, d));
+    ((d1 > 0.0) != (d2 > 0.0) || d1 == 0.0 || d2 == 0.0) &&
+        ((d3 > 0.0) != (d4 > 0.0) || d3 == 0.0 || d4 == 0.0) &&
+        // Reject collinear segments which do not overlap.
+        Bounds2::new(a, b).intersects(&Bounds2::new(c, d))
+}
+
+/// An axis aligned bounding box in 3D.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Bounds3 {
+    /// The minimum corner.
+    pub min: [f64; 3],
+    /// The maximum corner.
+    pub max: [f64; 3],
+}
+
+impl Bounds3 {
+    /// Creates bounds from two corners in any order.
+    pub fn new(a: [f64; 3], b: [f64; 3]) -> Bounds3 {
+        Bounds3 {
+            min: [a[0].min(b[0]), a[1].min(b[1]), a[2].min(b[2])],
+            max: [a[0].max(b[0]), a[1].max(b[1]), a[2].max(b[2])],
+        }
+    }
+
+    /// Creates bounds of a point.
+    pub fn point(pos: [f64; 3]) -> Bounds3 {
+        Bounds3 { min: pos, max: pos }
+    }
+
+    /// Creates bounds of a sphere.
+    pub fn sphere(center: [f64; 3], radius: f64) -> Bounds3 {
+        let r = radius.abs();
+        Bounds3 {
+            min: [center[0] - r, center[1] - r, center[2] - r],
+            max: [center[0] + r, center[1] + r, center[2] + r],
+        }
+    }
+
+    /// Returns `true` if every coordinate is finite.
+    pub fn is_finite(&self) -> bool {
+        self.min.iter().chain(&self.max).all(|x| x.is_finite())
+    }
+
+    /// Returns `true` if the point is inside, including the border.
+    pub fn contains(&self, pos: [f64; 3]) -> bool {
+        (0..3).all(|i| self.min[i] <= pos[i] && pos[i] <= self.max[i])
+    }
+
+    /// Returns `true` if the bounds overlap, including touching borders.
+    pub fn intersects(&self, other: &Bounds3) -> bool {
+        (0..3).all(|i| self.min[i] <= other.max[i] && other.min[i] <= self.max[i])
+    }
+
+    /// Returns `true` if the other bounds are completely inside.
+    pub fn encloses(&self, other: &Bounds3) -> bool {
+        self.contains(other.min) && self.contains(other.max)
+    }
+
+    /// Gets the smallest bounds enclosing both.
+    pub fn union(&self, other: &Bounds3) -> Bounds3 {
+        Bounds3 {
+            min: [
+                self.min[0].min(other.min[0]),
+                self.min[1].min(other.min[1]),
+                self.min[2].min(other.min[2]),
+            ],
+            max: [
+                self.max[0].max(other.max[0]),
+                self.max[1].max(other.max[1]),
+                self.max[2].max(other.max[2]),
+            ],
+        }
+    }
+
+    /// Gets the surface area.
+    pub fn area(&self) -> f64 {
+        let d = [self.max[0] - self.min[0], self.max[1] - self.min[1], self.max[2] - self.min[2]];
+        2.0 * (d[0] * d[1] + d[1] * d[2] + d[2] * d[0])
+    }
+
+    /// Gets the distance along a ray where it enters the bounds.
+    ///
+    /// Returns zero when the ray starts inside.
+    pub fn ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Option<f64> {
+        slab(&origin, &direction, &self.min, &self.max)
+    }
+
+    /// Returns `true` if the bounds are at least partly inside all planes,
+    /// for example those of a frustum.
+    ///
+    /// This is conservative, so bounds near the corners of a frustum
+    /// can pass without overlapping it.
+    pub fn intersects_planes(&self, planes: &[Plane]) -> bool {
+        planes.iter().all(|plane| {
+            // The corner furthest along the normal.
+            let mut p = self.min;
+            for (i, x) in p.iter_mut().enumerate() {
+                if plane.normal[i] >= 0.0 { *x = self.max[i]; }
+            }
+            plane.distance_to(p) >= 0.0
+        })
+    }
+}
+
+/// A plane in 3D, with the normal pointing to the inside.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Plane {
+    /// The normal, of unit length.
+    pub normal: [f64; 3],
+    /// The signed distance from the plane to the origin along the normal.
+    pub distance: f64,
+}
+
+impl Plane {
+    /// Creates a plane from the coefficients of `ax + by + cz + d = 0`,
+    /// normalizing them.
+    ///
+    /// Returns `None` if `a`, `b` and `c` are all zero.
+    pub fn from_coefficients(a: f64, b: f64, c: f64, d: f64) -> Option<Plane> {
+        let len = (a * a + b * b + c * c).sqrt();
+        if len == 0.0 || !len.is_finite() { return None; }
+        Some(Plane {
+            normal: [a / len, b / len, c / len],
+            distance: d / len,
+        })
+    }
+
+    /// Gets the signed distance to a point, positive on the inside.
+    pub fn distance_to(&self, pos: [f64; 3]) -> f64 {
+        let n = self.normal;
+        n[0] * pos[0] + n[1] * pos[1] + n[2] * pos[2] + self.distance
+    }
+}
+
+/// The error returned for bounds that are not finite.
+pub(crate) fn not_finite(ty: Type, obj: Object) -> EditorError {
+    EditorError::ConstraintViolation(format!(
+        "bounds of object {} of type `{}` are not finite", obj.0, ty.0))
+}
+
+/// Intersects a ray with a box using the slab method.
+///
+/// Returns the entry distance, or zero when starting inside.
+pub(crate) fn slab(origin: &[f64], direction: &[f64], min: &[f64], max: &[f64]) -> Option<f64> {
+    slab_range(origin, direction, min, max).map(|(enter, _)| enter)
+}
+
+/// Intersects a ray with a box, returning the distances where it enters and exits.
+pub(crate) fn slab_range(origin: &[f64], direction: &[f64], min: &[f64], max: &[f64])
+-> Option<(f64, f64)> {
+    let mut enter: f64 = 0.0;
+    let mut exit = f64::INFINITY;
+    for i in 0..origin.len() {
+        if direction[i] == 0.0 {
+            if origin[i] < min[i] || origin[i] > max[i] { return None; }
+            continue;
+        }
+        let inv = 1.0 / direction[i];
+        let (mut t0, mut t1) = ((min[i] - origin[i]) * inv, (max[i] - origin[i]) * inv);
+        if t0 > t1 { ::std::mem::swap(&mut t0, &mut t1); }
+        enter = enter.max(t0);
+        exit = exit.min(t1);
+        if enter > exit { return None; }
+    }
+    Some((enter, exit))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bounds_2d() {
+        let b = Bounds2::new([2.0, 0.0], [0.0, 2.0]);
+        assert_eq!(b, Bounds2 { min: [0.0, 0.0], max: [2.0, 2.0] });
+        assert!(b.contains([2.0, 1.0]));
+        assert!(!b.contains([2.1, 1.0]));
+        assert!(b.intersects(&Bounds2::new([2.0, 2.0], [3.0, 3.0])));
+        assert!(!b.intersects(&Bounds2::new([2.5, 0.0], [3.0, 3.0])));
+        assert!(b.encloses(&Bounds2::new([0.5, 0.5], [1.0, 1.0])));
+        assert!(!b.encloses(&Bounds2::new([0.5, 0.5], [3.0, 1.0])));
+        assert!(b.is_finite());
+        assert!(!Bounds2::new([0.0, 0.0], [f64::INFINITY, 0.0]).is_finite());
+        assert!(!Bounds2::point([f64::NAN, 0.0]).is_finite());
+    }
+
+    #[test]
+    fn bounds_3d() {
+        let a = Bounds3::new([0.0; 3], [1.0; 3]);
+        let b = Bounds3::point([2.0, 0.0, 0.0]);
+        let u = a.union(&b);
+        assert_eq!(u, Bounds3 { min: [0.0; 3], max: [2.0, 1.0, 1.0] });
+        assert!(u.encloses(&a) && u.encloses(&b));
+        assert!(!a.intersects(&b));
+        assert_eq!(a.area(), 6.0);
+        assert!(!Bounds3::point([0.0, f64::NAN, 0.0]).is_finite());
+    }
+
+    #[test]
+    fn ray() {
+        let b = Bounds2::new([1.0, -1.0], [2.0, 1.0]);
+        assert_eq!(b.ray([0.0, 0.0], [1.0, 0.0]), Some(1.0));
+        assert_eq!(b.ray([1.5, 0.0], [1.0, 0.0]), Some(0.0));
+        assert_eq!(b.ray([0.0, 0.0], [-1.0, 0.0]), None);
+        assert_eq!(b.ray([0.0, 2.0], [1.0, 0.0]), None);
+        let b = Bounds3::new([0.0; 3], [1.0; 3]);
+        assert_eq!(b.ray([0.5, 0.5, -2.0], [0.0, 0.0, 2.0]), Some(1.0));
+        assert_eq!(slab_range(&[0.5, 0.5, -2.0], &[0.0, 0.0, 1.0], &b.min, &b.max),
+            Some((2.0, 3.0)));
+    }
+
+    #[test]
+    fn polygon() {
+        // An L shape, which has a concave corner at [1, 1].
+        let l = [[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [1.0, 1.0], [1.0, 2.0], [0.0, 2.0]];
+        assert_eq!(Bounds2::polygon(&l), Some(Bounds2::new([0.0, 0.0], [2.0, 2.0])));
+        assert_eq!(Bounds2::polygon(&[]), None);
+        assert!(polygon_contains(&l, [0.5, 1.5]));
+        assert!(!polygon_contains(&l, [1.5, 1.5]));
+
+        // Inside the notch of the L.
+        assert!(!Bounds2::new([1.2, 1.2], [1.8, 1.8]).intersects_polygon(&l));
+        // Enclosing the polygon.
+        assert!(Bounds2::new([-1.0, -1.0], [3.0, 3.0]).intersects_polygon(&l));
+        // Crossing edges without containing a corner or the center of the other.
+        let bar = Bounds2::new([0.9, -1.0], [1.1, 3.0]);
+        assert!(bar.intersects_polygon(&[[-1.0, 0.4], [3.0, 0.4], [3.0, 0.6], [-1.0, 0.6]]));
+        assert!(!bar.intersects_polygon(&[]));
+    }
+
+    #[test]
+    fn planes() {
+        assert_eq!(Plane::from_coefficients(0.0, 0.0, 0.0, 1.0), None);
+        // The inside of x >= 1.
+        let plane = Plane::from_coefficients(2.0, 0.0, 0.0, -2.0).unwrap();
+        assert_eq!(plane.normal, [1.0, 0.0, 0.0]);
+        assert_eq!(plane.distance_to([3.0, 5.0, 5.0]), 2.0);
+
+        // The inside of 1 <= x <= 2.
+        let planes = [plane, Plane::from_coefficients(-1.0, 0.0, 0.0, 2.0).unwrap()];
+        assert!(Bounds3::sphere([0.0; 3], 1.5).intersects_planes(&planes));
+        assert!(Bounds3::sphere([1.5, 0.0, 0.0], 0.1).intersects_planes(&planes));
+        assert!(!Bounds3::sphere([0.0; 3], 0.5).intersects_planes(&planes));
+        assert!(!Bounds3::sphere([3.0, 0.0, 0.0], -0.5).intersects_planes(&planes));
+        assert_eq!(Bounds3::sphere([0.0; 3], -1.0), Bounds3::new([-1.0; 3], [1.0; 3]));
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::HashMap;
+
+use bounds::not_finite;
+use {Bounds3, EditorError, Object, Type};
+
+#[derive(Clone, Debug)]
+enum Kind {
+    Leaf(Type, Object),
+    Branch(usize, usize),
+}
+
+#[derive(Clone, Debug)]
+struct Node {
+    bounds: Bounds3,
+    parent: Option<usize>,
+    // The longest distance to a leaf, used to keep the tree balanced.
+    height: usize,
+    kind: Kind,
+}
+
+/// A bounding volume hierarchy for fast 3D queries.
+///
+/// This is a dynamic tree of bounding boxes, where objects can be inserted
+/// and removed without rebuilding the tree.
+/// The tree is rebalanced with rotations like an AVL tree,
+/// such that inserting sorted objects does not build a chain.
+/// The tree must be kept in sync with the editor,
+/// by calling `insert` on insert and update, and `deleted` on delete.
+#[derive(Clone, Debug, Default)]
+pub struct Bvh {
+    nodes: Vec<Node>,
+    free: Vec<usize>,
+    root: Option<usize>,
+    leaves: HashMap<(Type, Object), usize>,
+}
+
+impl Bvh {
+    /// Creates a new empty hierarchy.
+    pub fn new() -> Bvh {
+        Bvh::default()
+    }
+
+    /// Gets the number of objects.
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    /// Returns `true` if there are no objects.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Gets the bounds of an object.
+    pub fn bounds(&self, ty: Type, obj: Object) -> Option<Bounds3> {
+        self.leaves.get(&(ty, obj)).map(|&i| self.nodes[i].bounds)
+    }
+
+    /// Inserts an object, or moves it if it is already in the hierarchy.
+    ///
+    /// Bounds that are not finite are refused and the object is removed from the hierarchy.
+    pub fn insert(&mut self, ty: Type, obj: Object, bounds: Bounds3) -> Result<(), EditorError> {
+        self.remove(ty, obj);
+        if !bounds.is_finite() { return Err(not_finite(ty, obj)); }
+        let leaf = self.alloc(Node { bounds, parent: None, height: 0, kind: Kind::Leaf(ty, obj) });
+        self.leaves.insert((ty, obj), leaf);
+        let mut sibling = match self.root {
+            None => {
+                self.root = Some(leaf);
+                return Ok(());
+            }
+            Some(root) => root,
+        };
+
+        // Descend to the sibling which grows the least in surface area.
+        while let Kind::Branch(a, b) = self.nodes[sibling].kind {
+            let cost = |node: &Node| node.bounds.union(&bounds).area() - node.bounds.area();
+            sibling = if cost(&self.nodes[a]) <= cost(&self.nodes[b]) { a } else { b };
+        }
+
+        let old_parent = self.nodes[sibling].parent;
+        let parent = self.alloc(Node {
+            bounds: self.nodes[sibling].bounds.union(&bounds),
+            parent: old_parent,
+            height: 1,
+            kind: Kind::Branch(sibling, leaf),
+        });
+        self.nodes[sibling].parent = Some(parent);
+        self.nodes[leaf].parent = Some(parent);
+        match old_parent {
+            None => self.root = Some(parent),
+            Some(p) => self.replace_child(p, sibling, parent),
+        }
+        self.refit(old_parent);
+        Ok(())
+    }
+
+    /// Removes an object, returning `true` if it was found.
+    pub fn remove(&mut self, ty: Type, obj: Object) -> bool {
+        let leaf = match self.leaves.remove(&(ty, obj)) {
+            None => return false,
+            Some(leaf) => leaf,
+        };
+        self.free.push(leaf);
+        let parent = match self.nodes[leaf].parent {
+            None => {
+                self.root = None;
+                return true;
+            }
+            Some(parent) => parent,
+        };
+        // The sibling takes the place of the parent.
+        let sibling = match self.nodes[parent].kind {
+            Kind::Branch(a, b) => if a == leaf { b } else { a },
+            Kind::Leaf(..) => unreachable!("parent is a branch"),
+        };
+        let grand_parent = self.nodes[parent].parent;
+        self.nodes[sibling].parent = grand_parent;
+        self.free.push(parent);
+        match grand_parent {
+            None => self.root = Some(sibling),
+            Some(g) => self.replace_child(g, parent, sibling),
+        }
+        self.refit(grand_parent);
+        true
+    }
+
+    /// Removes all objects of a type.
+    pub fn remove_type(&mut self, ty: Type) {
+        let objs: Vec<Object> = self.leaves.keys().filter(|k| k.0 == ty).map(|k| k.1).collect();
+        for obj in objs { self.remove(ty, obj); }
+    }
+
+    /// Updates the hierarchy after deleting an object with swap-remove,
+    /// see `Editor::delete`.
+    pub fn deleted(&mut self, ty: Type, obj: Object, moved: Option<Object>) {
+        self.remove(ty, obj);
+        if let Some(last) = moved {
+            if let Some(leaf) = self.leaves.remove(&(ty, last)) {
+                // Rename the leaf, the bounds are unchanged.
+                self.nodes[leaf].kind = Kind::Leaf(ty, obj);
+                self.leaves.insert((ty, obj), leaf);
+            }
+        }
+    }
+
+    /// Finds objects which bounds contain a point.
+    pub fn query_point(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.query(|bounds| bounds.contains(pos))
+    }
+
+    /// Finds objects which bounds intersect a box.
+    pub fn query_box(&self, bounds: &Bounds3) -> Vec<(Type, Object)> {
+        self.query(|b| b.intersects(bounds))
+    }
+
+    /// Finds objects which bounds pass a test,
+    /// where the test must pass for bounds enclosing passing bounds.
+    ///
+    /// This can be used for custom queries, for example frustum culling.
+    pub fn query<F: Fn(&Bounds3) -> bool>(&self, f: F) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !f(&node.bounds) { continue; }
+            match node.kind {
+                Kind::Leaf(ty, obj) => res.push((ty, obj)),
+                Kind::Branch(a, b) => {
+                    stack.push(a);
+                    stack.push(b);
+                }
+            }
+        }
+        res
+    }
+
+    /// Finds objects which bounds are hit by a ray, nearest first.
+    pub fn query_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<(Type, Object)> {
+        let mut hits = vec![];
+        let mut stack: Vec<usize> = self.root.into_iter().collect();
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            let t = match node.bounds.ray(origin, direction) {
+                None => continue,
+                Some(t) => t,
+            };
+            match node.kind {
+                Kind::Leaf(ty, obj) => hits.push((t, (ty, obj))),
+                Kind::Branch(a, b) => {
+                    stack.push(a);
+                    stack.push(b);
+                }
+            }
+        }
+        hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        hits.into_iter().map(|(_, item)| item).collect()
+    }
+
+    fn alloc(&mut self, node: Node) -> usize {
+        match self.free.pop() {
+            Some(i) => {
+                self.nodes[i] = node;
+                i
+            }
+            None => {
+                self.nodes.push(node);
+                self.nodes.len() - 1
+            }
+        }
+    }
+
+    fn replace_child(&mut self, parent: usize, old: usize, new: usize) {
+        if let Kind::Branch(ref mut a, ref mut b) = self.nodes[parent].kind {
+            if *a == old { *a = new; } else { *b = new; }
+        }
+    }
+
+    /// Recomputes bounds and heights from a node up to the root,
+    /// rebalancing on the way.
+    fn refit(&mut self, mut node: Option<usize>) {
+        while let Some(i) = node {
+            let i = self.balance(i);
+            self.update(i);
+            node = self.nodes[i].parent;
+        }
+    }
+
+    /// Recomputes the bounds and height of a branch from its children.
+    fn update(&mut self, i: usize) {
+        if let Kind::Branch(a, b) = self.nodes[i].kind {
+            self.nodes[i].bounds = self.nodes[a].bounds.union(&self.nodes[b].bounds);
+            self.nodes[i].height = 1 + self.nodes[a].height.max(self.nodes[b].height);
+        }
+    }
+
+    /// Rotates the higher child of a node up when the heights of its children
+    /// differ by more than one.
+    ///
+    /// Returns the node now in its place.
+    fn balance(&mut self, i: usize) -> usize {
+        let (a, b) = match self.nodes[i].kind {
+            Kind::Branch(a, b) => (a, b),
+            Kind::Leaf(..) => return i,
+        };
+        let (ha, hb) = (self.nodes[a].height, self.nodes[b].height);
+        if ha > hb + 1 { self.rotate(i, a, b) }
+        else if hb > ha + 1 { self.rotate(i, b, a) }
+        else { i }
+    }
+
+    /// Moves the child `up` into the place of `i`, which becomes a child of `up`.
+    ///
+    /// The higher child of `up` stays, the lower one moves to `i` next to `other`.
+    fn rotate(&mut self, i: usize, up: usize, other: usize) -> usize {
+        let (c, d) = match self.nodes[up].kind {
+            Kind::Branch(c, d) => (c, d),
+            Kind::Leaf(..) => unreachable!("a higher child is a branch"),
+        };
+        let (keep, give) = if self.nodes[c].height > self.nodes[d].height { (c, d) }
+            else { (d, c) };
+        let parent = self.nodes[i].parent;
+        self.nodes[up].parent = parent;
+        match parent {
+            None => self.root = Some(up),
+            Some(p) => self.replace_child(p, i, up),
+        }
+        self.nodes[up].kind = Kind::Branch(i, keep);
+        self.nodes[i].parent = Some(up);
+        self.nodes[i].kind = Kind::Branch(other, give);
+        self.nodes[give].parent = Some(i);
+        self.update(i);
+        self.update(up);
+        up
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOX: Type = Type("box");
+
+    fn cube(x: f64) -> Bounds3 {
+        Bounds3::new([x, 0.0, 0.0], [x + 1.0, 1.0, 1.0])
+    }
+
+    #[test]
+    fn queries() {
+        let mut bvh = Bvh::new();
+        for i in 0..10 { bvh.insert(BOX, Object(i), cube(i as f64 * 2.0)).unwrap(); }
+        assert_eq!(bvh.query_point([4.5, 0.5, 0.5]), vec![(BOX, Object(2))]);
+        assert!(bvh.query_point([5.5, 0.5, 0.5]).is_empty());
+        let mut found = bvh.query_box(&Bounds3::new([3.5, 0.0, 0.0], [6.5, 1.0, 1.0]));
+        found.sort_by_key(|&(_, obj)| obj.0);
+        assert_eq!(found, vec![(BOX, Object(2)), (BOX, Object(3))]);
+        let hits = bvh.query_ray([100.0, 0.5, 0.5], [-1.0, 0.0, 0.0]);
+        assert_eq!(hits[..2], [(BOX, Object(9)), (BOX, Object(8))]);
+
+        bvh.deleted(BOX, Object(2), Some(Object(9)));
+        assert_eq!(bvh.len(), 9);
+        assert_eq!(bvh.query_point([18.5, 0.5, 0.5]), vec![(BOX, Object(2))]);
+        bvh.remove_type(BOX);
+        assert!(bvh.is_empty());
+    }
+
+    /// Checks parents, bounds and heights, returning the depth of the tree.
+    fn check(bvh: &Bvh, i: usize) -> usize {
+        let node = &bvh.nodes[i];
+        match node.kind {
+            Kind::Leaf(ty, obj) => {
+                assert_eq!(bvh.leaves[&(ty, obj)], i);
+                0
+            }
+            Kind::Branch(a, b) => {
+                for &child in &[a, b] {
+                    assert_eq!(bvh.nodes[child].parent, Some(i));
+                    assert!(node.bounds.encloses(&bvh.nodes[child].bounds));
+                }
+                let depth = 1 + check(bvh, a).max(check(bvh, b));
+                assert_eq!(node.height, depth);
+                depth
+            }
+        }
+    }
+
+    #[test]
+    fn balanced() {
+        let n = 10_000;
+        let mut bvh = Bvh::new();
+        for i in 0..n { bvh.insert(BOX, Object(i), cube(i as f64)).unwrap(); }
+        let max_depth = 2.0 * (n as f64).log2();
+        let root = bvh.root.unwrap();
+        assert!((bvh.nodes[root].height as f64) < max_depth);
+        check(&bvh, root);
+        assert_eq!(bvh.query_point([n as f64 - 0.5, 0.5, 0.5]), vec![(BOX, Object(n - 1))]);
+
+        // Removing every other object keeps the tree balanced too.
+        for i in (0..n).step_by(2) { bvh.remove(BOX, Object(i)); }
+        assert_eq!(bvh.len(), n / 2);
+        let root = bvh.root.unwrap();
+        assert!((bvh.nodes[root].height as f64) < max_depth);
+        check(&bvh, root);
+        assert!(bvh.query_point([0.5, 0.5, 0.5]).is_empty());
+        assert_eq!(bvh.query_point([1.5, 0.5, 0.5]), vec![(BOX, Object(1))]);
+    }
+
+    #[test]
+    fn refuse_non_finite() {
+        let mut bvh = Bvh::new();
+        bvh.insert(BOX, Object(0), cube(0.0)).unwrap();
+        assert!(bvh.insert(BOX, Object(0), cube(f64::NAN)).is_err());
+        assert!(bvh.is_empty());
+        assert!(bvh.query_point([0.5, 0.5, 0.5]).is_empty());
+    }
+}
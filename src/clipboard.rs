@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use selection::select_hits;
+use {
+    Cloner, Document, Editor, EditorError, EditorExt, Object, References, SelectMode, Transaction,
+    Type, TypeRegistry,
+};
+
+/// Copied objects, for moving objects between editors and documents.
+///
+/// Copying includes every object referred to by the selected objects,
+/// directly or indirectly, such that references within the clipboard
+/// can be remapped to the new objects when pasting.
+/// Objects are stored as a `Document`, which can be written to JSON or binary,
+/// for example to use the system clipboard.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Clipboard {
+    /// The copied objects.
+    pub document: Document,
+}
+
+impl Clipboard {
+    /// Copies the selected objects of some types with their dependencies.
+    ///
+    /// When `filter` is empty, the selection of every type with value conversions is copied.
+    pub fn copy(editor: &dyn Editor, types: &TypeRegistry, filter: &[Type])
+    -> Result<Clipboard, EditorError> {
+        let objects = collect(editor, types, filter)?;
+        Ok(Clipboard { document: Document::save_objects(editor, types, &objects)? })
+    }
+
+    /// Copies the selected objects of some types and deletes them.
+    ///
+    /// Dependencies are copied but not deleted.
+    /// Objects are deleted with `References::delete`,
+    /// which fails if a deleted object is still referred to by an object that is not cut.
+    /// The deletes run in a `Transaction`, so nothing is deleted on failure.
+    pub fn cut(
+        editor: &mut dyn Editor,
+        cloner: &Cloner,
+        types: &TypeRegistry,
+        references: &References,
+        filter: &[Type]
+    ) -> Result<Clipboard, EditorError> {
+        let clipboard = Clipboard::copy(editor, types, filter)?;
+        let mut pending: Vec<(Type, Object)> = selected_types(types, filter).into_iter()
+            .flat_map(|ty| editor.multiple_selected(ty).into_iter().map(move |obj| (ty, obj)))
+            .collect();
+        let order = delete_order(editor, references, &pending)?;
+        Transaction::run(editor, cloner, |editor| {
+            for i in order {
+                let (ty, obj) = pending[i];
+                if let Some(moved) = references.delete(editor, ty, obj)? {
+                    for item in &mut pending {
+                        if *item == (ty, moved) { item.1 = obj; }
+                    }
+                }
+            }
+            Ok(())
+        })?;
+        Ok(clipboard)
+    }
+
+    /// Inserts the copied objects and selects them.
+    ///
+    /// The changes run in a `Transaction`, so nothing is pasted on failure.
+    /// Returns the new objects of each type.
+    pub fn paste(&self, editor: &mut dyn Editor, cloner: &Cloner, types: &TypeRegistry)
+    -> Result<Vec<(Type, Vec<Object>)>, EditorError> {
+        Transaction::run(editor, cloner, |editor| {
+            let objects = self.document.load(editor, types)?;
+            let pasted_types: Vec<Type> = objects.iter().map(|&(ty, _)| ty).collect();
+            let hits: Vec<(Type, Object)> = objects.iter()
+                .flat_map(|&(ty, ref objs)| objs.iter().map(move |&obj| (ty, obj)))
+                .collect();
+            select_hits(editor, &hits, &pasted_types, SelectMode::Replace)?;
+            Ok(objects)
+        })
+    }
+
+    /// Returns `true` if nothing is copied.
+    pub fn is_empty(&self) -> bool {
+        self.document.tables.iter().all(|(_, values)| values.is_empty())
+    }
+
+    /// Writes the clipboard as JSON, see `Document::to_json`.
+    pub fn to_json(&self) -> Result<String, EditorError> {
+        self.document.to_json()
+    }
+
+    /// Reads the clipboard from JSON, see `Document::from_json`.
+    pub fn from_json(text: &str, types: &TypeRegistry) -> Result<Clipboard, EditorError> {
+        Ok(Clipboard { document: Document::from_json(text, types)? })
+    }
+
+    /// Writes the clipboard in binary, see `Document::to_binary`.
+    pub fn to_binary(&self) -> Result<Vec<u8>, EditorError> {
+        self.document.to_binary()
+    }
+
+    /// Reads the clipboard from binary, see `Document::from_binary`.
+    pub fn from_binary(bytes: &[u8], types: &TypeRegistry) -> Result<Clipboard, EditorError> {
+        Ok(Clipboard { document: Document::from_binary(bytes, types)? })
+    }
+}
+
+fn selected_types(types: &TypeRegistry, filter: &[Type]) -> Vec<Type> {
+    if !filter.is_empty() { return filter.to_vec(); }
+    types.types().into_iter()
+        .filter(|&ty| types.schema(ty).map(|schema| schema.has_values()).unwrap_or(false))
+        .collect()
+}
+
+/// Orders objects such that objects referring to others are deleted first,
+/// such that objects referring to each other can be deleted together.
+///
+/// Returns indices into `objs`.
+/// Objects in reference cycles are ordered as listed.
+fn delete_order(editor: &dyn Editor, references: &References, objs: &[(Type, Object)])
+-> Result<Vec<usize>, EditorError> {
+    // The number of other listed objects referring to each object.
+    let mut counts = vec![0usize; objs.len()];
+    // The listed objects each object refers to.
+    let mut targets: Vec<Vec<usize>> = vec![vec![]; objs.len()];
+    for (i, &(ty, obj)) in objs.iter().enumerate() {
+        for referrer in references.referencing(editor, ty, obj)? {
+            if referrer == (ty, obj) { continue; }
+            if let Some(j) = objs.iter().position(|&item| item == referrer) {
+                counts[i] += 1;
+                targets[j].push(i);
+            }
+        }
+    }
+    let mut done = vec![false; objs.len()];
+    let mut order = vec![];
+    while order.len() < objs.len() {
+        let next = (0..objs.len()).find(|&i| !done[i] && counts[i] == 0)
+            .or_else(|| (0..objs.len()).find(|&i| !done[i]))
+            .expect("an object is left");
+        done[next] = true;
+        order.push(next);
+        for &i in &targets[next] { counts[i] -= 1; }
+    }
+    Ok(order)
+}
+
+/// Collects the selected objects and their dependencies, grouped by type.
+fn collect(editor: &dyn Editor, types: &TypeRegistry, filter: &[Type])
+-> Result<Vec<(Type, Vec<Object>)>, EditorError> {
+    let mut objects: Vec<(Type, Vec<Object>)> = vec![];
+    let mut seen = HashSet::new();
+    let mut stack: Vec<(Type, Object)> = vec![];
+    for ty in selected_types(types, filter) {
+        for obj in editor.multiple_selected(ty) { stack.push((ty, obj)); }
+    }
+    // Reverse to keep the order of the selection.
+    stack.reverse();
+    while let Some((ty, obj)) = stack.pop() {
+        if !seen.insert((ty, obj)) { continue; }
+        let i = match objects.iter().position(|&(t, _)| t == ty) {
+            Some(i) => i,
+            None => {
+                objects.push((ty, vec![]));
+                objects.len() - 1
+            }
+        };
+        objects[i].1.push(obj);
+        let deps = editor.get_value(types, ty, obj)?.references();
+        stack.extend(deps.into_iter().rev());
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::{types, Line, LINE, POINT};
+    use {Bounds2, VecEditor};
+
+    fn references() -> References {
+        let mut references = References::new();
+        references.register::<Line>(LINE, POINT,
+            |line| vec![line.a, line.b],
+            |line| vec![&mut line.a, &mut line.b]);
+        references
+    }
+
+    /// Four points with lines from 0 to 1 and from 3 to 2.
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<Line>(LINE);
+        for i in 0..4 { editor.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        editor.insert(LINE, &Line { a: Object(0), b: Object(1) }).unwrap();
+        editor.insert(LINE, &Line { a: Object(3), b: Object(2) }).unwrap();
+        editor
+    }
+
+    #[test]
+    fn copy_and_paste() {
+        let types = types();
+        let mut editor = editor();
+        editor.select(LINE, Object(1)).unwrap();
+        let clipboard = Clipboard::copy(&editor, &types, &[]).unwrap();
+        assert!(!clipboard.is_empty());
+        let clipboard = Clipboard::from_json(&clipboard.to_json().unwrap(), &types).unwrap();
+
+        let cloner = editor.cloner().clone();
+        let pasted = clipboard.paste(&mut editor, &cloner, &types).unwrap();
+        assert_eq!(pasted, vec![
+            (LINE, vec![Object(2)]),
+            (POINT, vec![Object(4), Object(5)]),
+        ]);
+        assert_eq!(editor.items::<Line>(LINE).unwrap()[2], Line { a: Object(4), b: Object(5) });
+        assert_eq!(editor.items::<[f64; 2]>(POINT).unwrap()[4], [3.0, 0.0]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(2)]);
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(4), Object(5)]);
+    }
+
+    #[test]
+    fn cut_referenced_together() {
+        let (types, references) = (types(), references());
+        let mut editor = editor();
+        let cloner = editor.cloner().clone();
+        editor.select_multiple(POINT, &[Object(0), Object(1)]).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+        let clipboard = Clipboard::cut(&mut editor, &cloner, &types, &references, &[]).unwrap();
+        assert_eq!(editor.items::<[f64; 2]>(POINT).unwrap(), &[[3.0, 0.0], [2.0, 0.0]]);
+        assert_eq!(editor.items::<Line>(LINE).unwrap(), &[Line { a: Object(0), b: Object(1) }]);
+
+        clipboard.paste(&mut editor, &cloner, &types).unwrap();
+        assert_eq!(editor.items::<Line>(LINE).unwrap()[1], Line { a: Object(2), b: Object(3) });
+    }
+
+    #[test]
+    fn failed_cut_deletes_nothing() {
+        let (types, references) = (types(), references());
+        let mut editor = editor();
+        let cloner = editor.cloner().clone();
+        // The new point can be deleted, but the point 2 is still referred to by a line.
+        let extra = editor.insert(POINT, &[4.0, 0.0]).unwrap();
+        editor.select_multiple(POINT, &[extra, Object(2)]).unwrap();
+        match Clipboard::cut(&mut editor, &cloner, &types, &references, &[POINT]) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        assert_eq!(editor.all(POINT).len(), 5);
+        assert_eq!(editor.items::<Line>(LINE).unwrap()[1], Line { a: Object(3), b: Object(2) });
+    }
+
+    #[test]
+    fn failed_paste_inserts_nothing() {
+        let types = types();
+        let mut source = editor();
+        source.insert(POINT, &[f64::NAN, 0.0]).unwrap();
+        source.insert(LINE, &Line { a: Object(0), b: Object(4) }).unwrap();
+        source.select(LINE, Object(2)).unwrap();
+        let clipboard = Clipboard::copy(&source, &types, &[]).unwrap();
+
+        // Inserting the point which bounds are not finite fails after the line and first point.
+        let mut editor = editor();
+        editor.set_bounds_2d::<[f64; 2]>(POINT, |p| Bounds2::point(*p)).unwrap();
+        editor.select(POINT, Object(3)).unwrap();
+        let cloner = editor.cloner().clone();
+        match clipboard.paste(&mut editor, &cloner, &types) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        assert_eq!(editor.all(POINT).len(), 4);
+        assert_eq!(editor.all(LINE).len(), 2);
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(3)]);
+        assert!(editor.multiple_selected(LINE).is_empty());
+    }
+}
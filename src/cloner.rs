@@ -0,0 +1,71 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use error::downcast;
+use {EditorError, Type};
+
+type CloneFn = fn(&dyn Any) -> Result<Box<dyn Any>, EditorError>;
+
+/// Clones objects behind `&Any` for registered types.
+///
+/// The `Editor` trait only gives access to values by reference,
+/// so anything that needs to keep a copy of an object around,
+/// for example to restore it later, uses a cloner.
+#[derive(Clone, Default)]
+pub struct Cloner {
+    fns: HashMap<Type, CloneFn>,
+}
+
+impl Cloner {
+    /// Creates a new cloner without any registered types.
+    pub fn new() -> Cloner {
+        Cloner::default()
+    }
+
+    /// Registers the Rust type used to store objects of a type.
+    pub fn register<T: Any + Clone>(&mut self, ty: Type) {
+        self.fns.insert(ty, clone_any::<T>);
+    }
+
+    /// Returns `true` if the type is registered.
+    pub fn contains(&self, ty: Type) -> bool {
+        self.fns.contains_key(&ty)
+    }
+
+    /// Clones a value of a registered type.
+    pub fn clone_value(&self, ty: Type, val: &dyn Any) -> Result<Box<dyn Any>, EditorError> {
+        match self.fns.get(&ty) {
+            None => Err(EditorError::UnknownType(ty)),
+            Some(f) => f(val),
+        }
+    }
+}
+
+fn clone_any<T: Any + Clone>(val: &dyn Any) -> Result<Box<dyn Any>, EditorError> {
+    Ok(Box::new(downcast::<T>(val)?.clone()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    #[test]
+    fn clone_value() {
+        let mut cloner = Cloner::new();
+        assert!(!cloner.contains(POINT));
+        cloner.register::<[f64; 2]>(POINT);
+        assert!(cloner.contains(POINT));
+        let val = cloner.clone_value(POINT, &[1.0, 2.0]).unwrap();
+        assert_eq!(val.downcast_ref::<[f64; 2]>(), Some(&[1.0, 2.0]));
+        match cloner.clone_value(POINT, &0u32) {
+            Err(EditorError::DowncastMismatch { .. }) => {}
+            _ => panic!("expected a downcast mismatch"),
+        }
+        match cloner.clone_value(Type("unknown"), &0u32) {
+            Err(EditorError::UnknownType(_)) => {}
+            _ => panic!("expected an unknown type error"),
+        }
+    }
+}
@@ -0,0 +1,430 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use {Editor, EditorError, Object, Type};
+
+type EqFn = fn(&dyn Any, &dyn Any) -> bool;
+type CloneFn = fn(&dyn Any) -> Box<dyn Any>;
+
+/// Sample arguments of a type.
+struct Samples {
+    ty: Type,
+    values: Vec<Box<dyn Any>>,
+    eq: EqFn,
+    clone: CloneFn,
+}
+
+/// Arguments that no editor accepts.
+struct Wrong;
+
+/// An unknown type.
+const UNKNOWN: Type = Type("conformance unknown type");
+
+/// Checks that an editor keeps the promises of `Editor`,
+/// by running random operations and comparing with a model of the expected objects.
+///
+/// Checked after every operation:
+///
+/// - `all` contains the inserted objects, updated with swap-remove on `delete`
+/// - `get` returns the last inserted, updated or replaced value
+/// - `get` fails on deleted objects
+/// - selected and visible objects exist
+/// - `select` followed by `selected` round-trips
+/// - `select_multiple`, `deselect_multiple` and `select_none` change `multiple_selected`
+/// - `navigate_to` makes an object visible
+/// - operations on unknown types, deleted objects or with wrong arguments fail
+///   and leave the editor unchanged
+///
+/// ```
+/// use editor::{Conformance, Type, VecEditor};
+///
+/// Conformance::new()
+///     .sample(Type("point"), vec![[0.0, 0.0], [1.0, 2.0]])
+///     .run(|| {
+///         let mut editor = VecEditor::new();
+///         editor.register::<[f64; 2]>(Type("point"));
+///         Box::new(editor)
+///     });
+/// ```
+pub struct Conformance {
+    samples: Vec<Samples>,
+    seed: u64,
+    runs: usize,
+    steps: usize,
+}
+
+impl Default for Conformance {
+    fn default() -> Conformance {
+        Conformance::new()
+    }
+}
+
+impl Conformance {
+    /// Creates a new harness without types,
+    /// running 20 runs of 200 operations each.
+    pub fn new() -> Conformance {
+        Conformance {
+            samples: vec![],
+            seed: 0,
+            runs: 20,
+            steps: 200,
+        }
+    }
+
+    /// Adds sample arguments for a type stored as `T`.
+    ///
+    /// Inserts and updates use random samples.
+    pub fn sample<T: Any + Clone + PartialEq>(mut self, ty: Type, values: Vec<T>) -> Conformance {
+        fn eq<T: Any + PartialEq>(a: &dyn Any, b: &dyn Any) -> bool {
+            match (a.downcast_ref::<T>(), b.downcast_ref::<T>()) {
+                (Some(a), Some(b)) => a == b,
+                _ => false,
+            }
+        }
+        fn clone<T: Any + Clone>(a: &dyn Any) -> Box<dyn Any> {
+            Box::new(a.downcast_ref::<T>().unwrap().clone())
+        }
+
+        let values = values.into_iter().map(|val| Box::new(val) as Box<dyn Any>);
+        match self.samples.iter_mut().find(|samples| samples.ty == ty) {
+            Some(samples) => samples.values.extend(values),
+            None => self.samples.push(Samples {
+                ty,
+                values: values.collect(),
+                eq: eq::<T>,
+                clone: clone::<T>,
+            }),
+        }
+        self
+    }
+
+    /// Sets the seed of the random operations, such that failures can be reproduced.
+    pub fn seed(mut self, seed: u64) -> Conformance {
+        self.seed = seed;
+        self
+    }
+
+    /// Sets the number of runs, each on a new editor.
+    pub fn runs(mut self, runs: usize) -> Conformance {
+        self.runs = runs;
+        self
+    }
+
+    /// Sets the number of operations of each run.
+    pub fn steps(mut self, steps: usize) -> Conformance {
+        self.steps = steps;
+        self
+    }
+
+    /// Runs the checks, panicking on the first violation.
+    ///
+    /// Use this in tests.
+    pub fn run<F: Fn() -> Box<dyn Editor>>(&self, factory: F) {
+        if let Err(msg) = self.check(factory) { panic!("{}", msg); }
+    }
+
+    /// Runs the checks, returning a description of the first violation.
+    ///
+    /// The description includes the seed, run and step of the failed operation.
+    /// Fails if no samples were added with `sample`.
+    pub fn check<F: Fn() -> Box<dyn Editor>>(&self, factory: F) -> Result<(), String> {
+        if self.samples.is_empty() {
+            return Err("no samples were registered, see `Conformance::sample`".into());
+        }
+        if self.samples.iter().any(|samples| samples.values.is_empty()) {
+            return Err("every type needs at least one sample".into());
+        }
+        for run in 0..self.runs {
+            let mut rng = Rng(self.seed.wrapping_add(run as u64));
+            let mut editor = factory();
+            let mut model: Vec<HashMap<Object, Box<dyn Any>>> =
+                self.samples.iter().map(|_| HashMap::new()).collect();
+            for step in 0..self.steps {
+                let mut op = "";
+                self.step(&mut *editor, &mut model, &mut rng, &mut op)
+                    .and_then(|_| self.check_state(&*editor, &model))
+                    .map_err(|msg| format!(
+                        "seed {}, run {}, step {}, `{}`: {}",
+                        self.seed, run, step, op, msg
+                    ))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn step(
+        &self,
+        editor: &mut dyn Editor,
+        model: &mut [HashMap<Object, Box<dyn Any>>],
+        rng: &mut Rng,
+        op: &mut &'static str
+    ) -> Result<(), String> {
+        let i = rng.below(self.samples.len());
+        let samples = &self.samples[i];
+        let ty = samples.ty;
+        let model = &mut model[i];
+        let mut objs: Vec<Object> = model.keys().cloned().collect();
+        objs.sort_by_key(|obj| obj.0);
+        let stale = Object(objs.last().map(|obj| obj.0 + 1).unwrap_or(0) + rng.below(4));
+        let sample = &*samples.values[rng.below(samples.values.len())];
+        let choice = if objs.is_empty() { 0 } else { rng.below(12) };
+        let obj = if objs.is_empty() { stale } else { objs[rng.below(objs.len())] };
+        match choice {
+            0 => {
+                *op = "insert";
+                let obj = editor.insert(ty, sample).map_err(failed)?;
+                if model.contains_key(&obj) {
+                    return Err(format!("returned existing object {:?}", obj));
+                }
+                model.insert(obj, (samples.clone)(sample));
+            }
+            1 => {
+                *op = "delete";
+                let moved = editor.delete(ty, obj).map_err(failed)?;
+                model.remove(&obj);
+                if let Some(moved) = moved {
+                    let val = model.remove(&moved).ok_or_else(|| format!(
+                        "returned moved object {:?} which did not exist", moved
+                    ))?;
+                    model.insert(obj, val);
+                }
+            }
+            2 => {
+                *op = "update";
+                editor.update(ty, obj, sample).map_err(failed)?;
+                model.insert(obj, (samples.clone)(sample));
+            }
+            3 => {
+                *op = "replace";
+                let to = objs[rng.below(objs.len())];
+                editor.replace(ty, obj, to).map_err(failed)?;
+                let val = (samples.clone)(&*model[&to]);
+                model.insert(obj, val);
+            }
+            4 => {
+                *op = "select";
+                editor.select(ty, obj).map_err(failed)?;
+                if editor.selected(ty) != Some(obj) {
+                    return Err(format!("`selected` returned {:?}", editor.selected(ty)));
+                }
+            }
+            5 => {
+                *op = "select_multiple";
+                let sel = subset(rng, &objs);
+                editor.select_multiple(ty, &sel).map_err(failed)?;
+                let multiple = editor.multiple_selected(ty);
+                if let Some(obj) = sel.iter().find(|obj| !multiple.contains(obj)) {
+                    return Err(format!("{:?} is not in `multiple_selected`", obj));
+                }
+            }
+            6 => {
+                *op = "deselect_multiple";
+                let sel = subset(rng, &editor.multiple_selected(ty));
+                editor.deselect_multiple(ty, &sel).map_err(failed)?;
+                let multiple = editor.multiple_selected(ty);
+                if let Some(obj) = sel.iter().find(|obj| multiple.contains(obj)) {
+                    return Err(format!("{:?} is still in `multiple_selected`", obj));
+                }
+            }
+            7 => {
+                *op = "select_none";
+                editor.select_none(ty).map_err(failed)?;
+                if editor.selected(ty).is_some() || !editor.multiple_selected(ty).is_empty() {
+                    return Err("objects are still selected".into());
+                }
+            }
+            8 => {
+                *op = "navigate_to";
+                editor.navigate_to(ty, obj).map_err(failed)?;
+                if !editor.visible(ty).contains(&obj) {
+                    return Err(format!("{:?} is not visible", obj));
+                }
+            }
+            9 => {
+                *op = "operations on deleted objects";
+                let before = self.snapshot(editor);
+                refuse("delete", editor.delete(ty, stale))?;
+                refuse("update", editor.update(ty, stale, sample))?;
+                refuse("replace", editor.replace(ty, obj, stale))?;
+                refuse("select", editor.select(ty, stale))?;
+                refuse("select_multiple", editor.select_multiple(ty, &[obj, stale]))?;
+                refuse("navigate_to", editor.navigate_to(ty, stale))?;
+                unchanged(&before, &self.snapshot(editor))?;
+            }
+            10 => {
+                *op = "operations with wrong arguments";
+                let before = self.snapshot(editor);
+                refuse("insert", editor.insert(ty, &Wrong))?;
+                refuse("update", editor.update(ty, obj, &Wrong))?;
+                unchanged(&before, &self.snapshot(editor))?;
+            }
+            _ => {
+                *op = "operations on unknown type";
+                let before = self.snapshot(editor);
+                refuse("insert", editor.insert(UNKNOWN, sample))?;
+                refuse("delete", editor.delete(UNKNOWN, obj))?;
+                refuse("select", editor.select(UNKNOWN, obj))?;
+                unchanged(&before, &self.snapshot(editor))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn check_state(&self, editor: &dyn Editor, model: &[HashMap<Object, Box<dyn Any>>])
+    -> Result<(), String> {
+        for (samples, model) in self.samples.iter().zip(model) {
+            let ty = samples.ty;
+            let mut all = editor.all(ty);
+            all.sort_by_key(|obj| obj.0);
+            let mut expected: Vec<Object> = model.keys().cloned().collect();
+            expected.sort_by_key(|obj| obj.0);
+            if all != expected {
+                return Err(format!("`all` returned {:?}, expected {:?}", all, expected));
+            }
+            for (&obj, val) in model {
+                let actual = editor.get(ty, obj).map_err(|err| format!(
+                    "`get` of {:?} failed: {:?}", obj, err
+                ))?;
+                if !(samples.eq)(actual, &**val) {
+                    return Err(format!("`get` of {:?} returned a wrong value", obj));
+                }
+            }
+            let stale = Object(all.last().map(|obj| obj.0 + 1).unwrap_or(0));
+            if editor.get(ty, stale).is_ok() {
+                return Err(format!("`get` of deleted object {:?} succeeded", stale));
+            }
+            let selected = editor.selected(ty).into_iter().chain(editor.multiple_selected(ty));
+            for obj in selected {
+                if !model.contains_key(&obj) {
+                    return Err(format!("deleted object {:?} is selected", obj));
+                }
+            }
+            for obj in editor.visible(ty) {
+                if !model.contains_key(&obj) {
+                    return Err(format!("deleted object {:?} is visible", obj));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn snapshot(&self, editor: &dyn Editor) -> Vec<Snapshot> {
+        self.samples.iter().map(|samples| {
+            let ty = samples.ty;
+            Snapshot {
+                ty,
+                all: editor.all(ty),
+                selected: editor.selected(ty),
+                multiple: editor.multiple_selected(ty),
+                visible: editor.visible(ty),
+            }
+        }).collect()
+    }
+}
+
+/// The observable state of a type, except values which are checked against the model.
+#[derive(Debug, PartialEq)]
+struct Snapshot {
+    ty: Type,
+    all: Vec<Object>,
+    selected: Option<Object>,
+    multiple: Vec<Object>,
+    visible: Vec<Object>,
+}
+
+fn failed(err: EditorError) -> String {
+    format!("failed: {:?}", err)
+}
+
+fn refuse<T>(name: &str, res: Result<T, EditorError>) -> Result<(), String> {
+    match res {
+        Ok(_) => Err(format!("`{}` succeeded, but should fail", name)),
+        Err(_) => Ok(()),
+    }
+}
+
+fn unchanged(before: &[Snapshot], after: &[Snapshot]) -> Result<(), String> {
+    match before.iter().zip(after).find(|&(a, b)| a != b) {
+        None => Ok(()),
+        Some((a, b)) => Err(format!("failed operation changed {:?} to {:?}", a, b)),
+    }
+}
+
+fn subset(rng: &mut Rng, objs: &[Object]) -> Vec<Object> {
+    objs.iter().cloned().filter(|_| rng.below(2) == 0).collect()
+}
+
+/// A small random generator (SplitMix64), such that runs are reproducible.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Layers, UndoStack, VecEditor};
+
+    const POINT: Type = Type("point");
+    const NAME: Type = Type("name");
+
+    fn conformance() -> Conformance {
+        Conformance::new()
+            .sample(POINT, vec![[0.0, 0.0], [1.0, 2.0], [3.0, -1.0]])
+            .sample(NAME, vec![String::from("a"), String::from("b")])
+    }
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<String>(NAME);
+        editor
+    }
+
+    #[test]
+    fn no_samples() {
+        let res = Conformance::new().check(|| Box::new(editor()));
+        assert!(res.unwrap_err().contains("no samples"));
+        let res = Conformance::new().sample::<u32>(POINT, vec![]).check(|| Box::new(editor()));
+        assert!(res.unwrap_err().contains("at least one sample"));
+    }
+
+    #[test]
+    fn vec_editor() {
+        conformance().run(|| Box::new(editor()));
+    }
+
+    #[test]
+    fn undo_stack() {
+        conformance().run(|| {
+            let mut undo = UndoStack::new(editor());
+            undo.register::<[f64; 2]>(POINT);
+            undo.register::<String>(NAME);
+            Box::new(undo)
+        });
+    }
+
+    #[test]
+    fn layers() {
+        conformance().run(|| Box::new(Layers::new(editor())));
+    }
+
+    #[test]
+    fn reports_violations() {
+        // Samples of the wrong Rust type make every insert fail.
+        let res = Conformance::new().sample(POINT, vec![0u32]).check(|| Box::new(editor()));
+        let msg = res.unwrap_err();
+        assert!(msg.starts_with("seed 0, run 0, step 0, `insert`"), "{}", msg);
+    }
+}
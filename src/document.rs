@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+
+use binary::{Reader, Writer};
+use json::{self, Json};
+use {Editor, EditorError, EditorExt, Object, Type, TypeRegistry, Value};
+
+const FORMAT: &str = "piston-editor";
+const VERSION: i64 = 1;
+const MAGIC: &[u8] = b"PEDT";
+
+/// The objects of an editor, stored as values.
+///
+/// Object references are remapped to stable ids, which are the indices
+/// of objects within their table in the document.
+/// Every type is saved and loaded through the value conversions
+/// registered with `SchemaBuilder::values`.
+///
+/// In JSON, vectors, colors and references are written as objects
+/// with a single key starting with `$`, so such keys are reserved.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Document {
+    /// The objects of each type.
+    pub tables: Vec<(Type, Vec<Value>)>,
+}
+
+impl Document {
+    /// Creates a new empty document.
+    pub fn new() -> Document {
+        Document::default()
+    }
+
+    /// Saves all objects of every type with value conversions.
+    pub fn save(editor: &dyn Editor, types: &TypeRegistry) -> Result<Document, EditorError> {
+        let objects: Vec<(Type, Vec<Object>)> = types.types().into_iter()
+            .filter(|&ty| types.schema(ty).map(|schema| schema.has_values()).unwrap_or(false))
+            .map(|ty| (ty, editor.all(ty)))
+            .collect();
+        Document::save_objects(editor, types, &objects)
+    }
+
+    /// Saves some objects.
+    ///
+    /// Every referenced object must be saved too,
+    /// otherwise the reference is reported with `EditorError::ConstraintViolation`.
+    pub fn save_objects(
+        editor: &dyn Editor,
+        types: &TypeRegistry,
+        objects: &[(Type, Vec<Object>)]
+    ) -> Result<Document, EditorError> {
+        let mut ids: HashMap<(Type, Object), usize> = HashMap::new();
+        for &(ty, ref objs) in objects {
+            for (i, &obj) in objs.iter().enumerate() {
+                ids.insert((ty, obj), i);
+            }
+        }
+        let mut tables = vec![];
+        for &(ty, ref objs) in objects {
+            let mut values = vec![];
+            for &obj in objs {
+                let mut val = editor.get_value(types, ty, obj)?;
+                val.map_references(&mut |ref_ty, ref_obj| match ids.get(&(ref_ty, ref_obj)) {
+                    None => Err(EditorError::ConstraintViolation(format!(
+                        "`{}` {} refers to `{}` {} which is not saved",
+                        ty.0, obj.0, ref_ty.0, ref_obj.0
+                    ))),
+                    Some(&i) => Ok(Object(i)),
+                })?;
+                values.push(val);
+            }
+            tables.push((ty, values));
+        }
+        Ok(Document { tables })
+    }
+
+    /// Inserts the objects into an editor.
+    ///
+    /// Returns the new objects of each table.
+    /// References are updated to the new objects after all objects are inserted.
+    /// A reference to an object missing from the document is refused
+    /// with `EditorError::InvalidData` before the editor is changed.
+    ///
+    /// When the editor refuses to insert or update an object, the objects inserted so far
+    /// are kept and may still refer to objects by their ids in the document.
+    /// Use `Transaction::run` to roll back on failure.
+    pub fn load(&self, editor: &mut dyn Editor, types: &TypeRegistry)
+    -> Result<Vec<(Type, Vec<Object>)>, EditorError> {
+        let lens: HashMap<Type, usize> = self.tables.iter()
+            .map(|&(ty, ref values)| (ty, values.len()))
+            .collect();
+        for &(ty, ref values) in &self.tables {
+            for val in values {
+                for (ref_ty, ref_obj) in val.references() {
+                    if ref_obj.0 >= lens.get(&ref_ty).cloned().unwrap_or(0) {
+                        return Err(EditorError::InvalidData(format!(
+                            "`{}` refers to missing `{}` {}", ty.0, ref_ty.0, ref_obj.0
+                        )));
+                    }
+                }
+            }
+        }
+
+        let mut res = vec![];
+        for &(ty, ref values) in &self.tables {
+            let mut objs = vec![];
+            for val in values {
+                objs.push(editor.insert_value(types, ty, val)?);
+            }
+            res.push((ty, objs));
+        }
+        let ids: HashMap<Type, &[Object]> = res.iter()
+            .map(|&(ty, ref objs)| (ty, &objs[..]))
+            .collect();
+        for (&(ty, ref values), (_, objs)) in self.tables.iter().zip(res.iter()) {
+            for (val, &obj) in values.iter().zip(objs.iter()) {
+                if val.references().is_empty() { continue; }
+
+                let mut val = val.clone();
+                // Every reference was checked above.
+                val.map_references(&mut |ref_ty, ref_obj| Ok(ids[&ref_ty][ref_obj.0]))?;
+                editor.update_value(types, ty, obj, &val)?;
+            }
+        }
+        Ok(res)
+    }
+
+    /// Writes the document as JSON, for example for diffing.
+    pub fn to_json(&self) -> Result<String, EditorError> {
+        let tables = self.tables.iter()
+            .map(|&(ty, ref values)| {
+                (ty.0.to_string(), Json::Array(values.iter().map(to_json).collect()))
+            })
+            .collect();
+        let json = Json::Object(vec![
+            ("format".into(), Json::String(FORMAT.into())),
+            ("version".into(), Json::Int(VERSION)),
+            ("types".into(), Json::Object(tables)),
+        ]);
+        let mut res = String::new();
+        json::write(&json, 0, &mut res)?;
+        res.push('\n');
+        Ok(res)
+    }
+
+    /// Reads a document from JSON.
+    ///
+    /// Type names are looked up in the type registry.
+    pub fn from_json(text: &str, types: &TypeRegistry) -> Result<Document, EditorError> {
+        let fields = match json::parse(text)? {
+            Json::Object(fields) => fields,
+            _ => return Err(invalid("expected object")),
+        };
+        let field = |name: &str| fields.iter().find(|f| f.0 == name).map(|f| &f.1);
+        if field("format") != Some(&Json::String(FORMAT.into())) {
+            return Err(invalid("unknown format"));
+        }
+        match field("version") {
+            Some(&Json::Int(version)) if version <= VERSION => {}
+            _ => return Err(invalid("unsupported version")),
+        }
+        let tables = match field("types") {
+            Some(Json::Object(tables)) => tables,
+            _ => return Err(invalid("expected `types` object")),
+        };
+        let mut doc = Document::new();
+        for (name, values) in tables {
+            let ty = find_type(types, name)?;
+            let values = match *values {
+                Json::Array(ref values) => values,
+                _ => return Err(invalid("expected array of objects")),
+            };
+            let values = values.iter()
+                .map(|json| from_json(json, types))
+                .collect::<Result<Vec<Value>, EditorError>>()?;
+            doc.tables.push((ty, values));
+        }
+        Ok(doc)
+    }
+
+    /// Writes the document in a compact binary format, for large scenes.
+    pub fn to_binary(&self) -> Result<Vec<u8>, EditorError> {
+        let types: Vec<Type> = self.tables.iter().map(|&(ty, _)| ty).collect();
+        let mut w = Writer::new();
+        w.bytes.extend_from_slice(MAGIC);
+        w.varint(VERSION as u64);
+        // Type names come first, since references use indices into the tables.
+        w.varint(self.tables.len() as u64);
+        for &ty in &types {
+            w.str(ty.0);
+        }
+        for (_, values) in &self.tables {
+            w.varint(values.len() as u64);
+            for val in values {
+                w.value(val, &types)?;
+            }
+        }
+        Ok(w.bytes)
+    }
+
+    /// Reads a document from the binary format.
+    ///
+    /// Type names are looked up in the type registry.
+    pub fn from_binary(bytes: &[u8], types: &TypeRegistry) -> Result<Document, EditorError> {
+        let mut r = Reader::new(bytes);
+        if r.take(MAGIC.len()).ok() != Some(MAGIC) { return Err(r.error("unknown format")); }
+        if r.varint()? > VERSION as u64 { return Err(r.error("unsupported version")); }
+        let n = r.len()?;
+        let mut doc_types = Vec::with_capacity(n);
+        for _ in 0..n {
+            let name = r.str()?;
+            doc_types.push(find_type(types, &name)?);
+        }
+        let mut doc = Document::new();
+        for &ty in &doc_types {
+            let count = r.len()?;
+            let mut values = Vec::with_capacity(count);
+            for _ in 0..count { values.push(r.value(&doc_types)?); }
+            doc.tables.push((ty, values));
+        }
+        if !r.is_at_end() { return Err(r.error("expected end of data")); }
+        Ok(doc)
+    }
+}
+
+pub(crate) fn invalid(msg: &str) -> EditorError {
+    EditorError::InvalidData(format!("invalid document: {}", msg))
+}
+
+pub(crate) fn find_type(types: &TypeRegistry, name: &str) -> Result<Type, EditorError> {
+    types.find(name).ok_or_else(|| invalid(&format!("unknown type `{}`", name)))
+}
+
+/// Converts a value to JSON.
+///
+/// Values that have no JSON counterpart are written as an object
+/// with a single key starting with `$`.
+pub(crate) fn to_json(val: &Value) -> Json {
+    let tagged = |tag: &str, json: Json| Json::Object(vec![(tag.into(), json)]);
+    match *val {
+        Value::Bool(x) => Json::Bool(x),
+        Value::Int(x) => Json::Int(x),
+        Value::UInt(x) => Json::UInt(x),
+        Value::Float(x) => Json::Float(x),
+        Value::String(ref x) => Json::String(x.clone()),
+        Value::Vec2(x) => tagged("$vec2", Json::Array(x.iter().map(|&x| Json::Float(x)).collect())),
+        Value::Vec3(x) => tagged("$vec3", Json::Array(x.iter().map(|&x| Json::Float(x)).collect())),
+        Value::Color(x) =>
+            tagged("$color", Json::Array(x.iter().map(|&x| Json::Float32(x)).collect())),
+        Value::Reference(ty, obj) => tagged("$ref", Json::Array(vec![
+            Json::String(ty.0.into()),
+            Json::Int(obj.0 as i64),
+        ])),
+        Value::List(ref items) => Json::Array(items.iter().map(to_json).collect()),
+        Value::Map(ref map) => Json::Object(map.iter()
+            .map(|(key, x)| (key.clone(), to_json(x)))
+            .collect()),
+    }
+}
+
+pub(crate) fn from_json(json: &Json, types: &TypeRegistry) -> Result<Value, EditorError> {
+    fn floats(json: &Json, n: usize) -> Result<Vec<f64>, EditorError> {
+        match *json {
+            Json::Array(ref items) if items.len() == n => items.iter().map(|item| match *item {
+                Json::Float(x) => Ok(x),
+                Json::Int(x) => Ok(x as f64),
+                Json::UInt(x) => Ok(x as f64),
+                _ => Err(invalid("expected number")),
+            }).collect(),
+            _ => Err(invalid(&format!("expected array of {} numbers", n))),
+        }
+    }
+
+    Ok(match *json {
+        Json::Null => return Err(invalid("unexpected `null`")),
+        Json::Bool(x) => Value::Bool(x),
+        Json::Int(x) => Value::Int(x),
+        Json::UInt(x) => Value::UInt(x),
+        Json::Float(x) => Value::Float(x),
+        Json::Float32(x) => Value::Float(x as f64),
+        Json::String(ref x) => Value::String(x.clone()),
+        Json::Array(ref items) => Value::List(items.iter()
+            .map(|item| from_json(item, types))
+            .collect::<Result<_, _>>()?),
+        Json::Object(ref fields) => {
+            if let [(ref tag, ref x)] = fields[..] {
+                match &tag[..] {
+                    "$vec2" => {
+                        let x = floats(x, 2)?;
+                        return Ok(Value::Vec2([x[0], x[1]]));
+                    }
+                    "$vec3" => {
+                        let x = floats(x, 3)?;
+                        return Ok(Value::Vec3([x[0], x[1], x[2]]));
+                    }
+                    "$color" => {
+                        let x = floats(x, 4)?;
+                        return Ok(Value::Color([x[0] as f32, x[1] as f32, x[2] as f32, x[3] as f32]));
+                    }
+                    "$ref" => return match *x {
+                        Json::Array(ref items) => match items[..] {
+                            [Json::String(ref name), Json::Int(id)] if id >= 0 =>
+                                Ok(Value::Reference(find_type(types, name)?, Object(id as usize))),
+                            _ => Err(invalid("expected type name and id")),
+                        },
+                        _ => Err(invalid("expected type name and id")),
+                    },
+                    _ => {}
+                }
+            }
+            Value::Map(fields.iter()
+                .map(|(key, x)| Ok((key.clone(), from_json(x, types)?)))
+                .collect::<Result<_, EditorError>>()?)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::{types, Line, LINE, POINT};
+    use {ToValue, VecEditor};
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<Line>(LINE);
+        editor
+    }
+
+    fn scene() -> VecEditor {
+        let mut editor = editor();
+        for i in 0..3 { editor.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        editor.insert(LINE, &Line { a: Object(2), b: Object(0) }).unwrap();
+        editor
+    }
+
+    #[test]
+    fn load_remaps_references() {
+        let types = types();
+        let doc = Document::save(&scene(), &types).unwrap();
+        let mut editor = editor();
+        editor.insert(POINT, &[9.0, 9.0]).unwrap();
+        let objs = doc.load(&mut editor, &types).unwrap();
+        assert_eq!(objs[0], (POINT, vec![Object(1), Object(2), Object(3)]));
+        assert_eq!(editor.items::<Line>(LINE).unwrap()[0], Line { a: Object(3), b: Object(1) });
+    }
+
+    #[test]
+    fn json_and_binary() {
+        let types = types();
+        let doc = Document::save(&scene(), &types).unwrap();
+        let json = doc.to_json().unwrap();
+        assert_eq!(Document::from_json(&json, &types).unwrap(), doc);
+        let bytes = doc.to_binary().unwrap();
+        assert_eq!(Document::from_binary(&bytes, &types).unwrap(), doc);
+        assert!(Document::from_binary(&bytes[..bytes.len() - 1], &types).is_err());
+        assert!(Document::from_json(&json, &TypeRegistry::new()).is_err());
+    }
+
+    #[test]
+    fn save_objects_refuses_missing_references() {
+        let types = types();
+        let editor = scene();
+        let objects = vec![(POINT, vec![Object(0)]), (LINE, vec![Object(0)])];
+        match Document::save_objects(&editor, &types, &objects) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+    }
+
+    #[test]
+    fn load_refuses_missing_references() {
+        let types = types();
+        let mut doc = Document::save(&scene(), &types).unwrap();
+        doc.tables[1].1.push(Line { a: Object(0), b: Object(3) }.to_value());
+        let mut editor = editor();
+        match doc.load(&mut editor, &types) {
+            Err(EditorError::InvalidData(_)) => {}
+            _ => panic!("expected invalid data"),
+        }
+        assert!(editor.all(POINT).is_empty());
+        assert!(editor.all(LINE).is_empty());
+    }
+
+    #[test]
+    fn nested_too_deeply() {
+        let list = format!("{}1{}", "[".repeat(10_000), "]".repeat(10_000));
+        let text = format!(
+            "{{\"format\": \"piston-editor\", \"version\": 1, \"types\": {{\"point\": [{}]}}}}",
+            list);
+        match Document::from_json(&text, &types()) {
+            Err(EditorError::InvalidData(_)) => {}
+            _ => panic!("expected invalid data"),
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use error::downcast;
+use selection::select_hits;
+use {Action, Cloner, Editor, EditorError, Object, References, SelectMode, Type};
+
+type OffsetFn = Box<dyn Fn(&dyn Any) -> Result<Box<dyn Any>, EditorError>>;
+
+/// Duplicates the selected objects and selects the copies.
+///
+/// Objects are copied with `Editor::get` and `Editor::insert`.
+/// References between duplicated objects are changed to refer to the copies,
+/// while references to other objects are kept, see `References::remap`.
+/// Use `offset` to move the copies, such that they do not overlap the originals.
+pub struct Duplicate {
+    cloner: Rc<Cloner>,
+    references: Rc<References>,
+    filter: Vec<Type>,
+    offsets: Vec<(Type, OffsetFn)>,
+}
+
+impl Duplicate {
+    /// Creates a new action duplicating the selection of some types.
+    pub fn new(cloner: Rc<Cloner>, references: Rc<References>, filter: Vec<Type>) -> Duplicate {
+        Duplicate {
+            cloner,
+            references,
+            filter,
+            offsets: vec![],
+        }
+    }
+
+    /// Sets a function that changes the copies of a type stored as `T`,
+    /// for example moving them by an offset.
+    pub fn offset<T: Any + Clone>(mut self, ty: Type, f: fn(&mut T)) -> Duplicate {
+        let offset = move |val: &dyn Any| -> Result<Box<dyn Any>, EditorError> {
+            let mut val = downcast::<T>(val)?.clone();
+            f(&mut val);
+            Ok(Box::new(val))
+        };
+        self.offsets.retain(|&(t, _)| t != ty);
+        self.offsets.push((ty, Box::new(offset)));
+        self
+    }
+
+    /// Duplicates the selected objects, returning the copies of each type.
+    pub fn duplicate(&self, editor: &mut dyn Editor)
+    -> Result<Vec<(Type, Vec<Object>)>, EditorError> {
+        let mut map = HashMap::new();
+        let mut res = vec![];
+        for &ty in &self.filter {
+            let mut copies = vec![];
+            for obj in editor.multiple_selected(ty) {
+                let val = {
+                    let val = editor.get(ty, obj)?;
+                    match self.offsets.iter().find(|&&(t, _)| t == ty) {
+                        Some((_, f)) => f(val)?,
+                        None => self.cloner.clone_value(ty, val)?,
+                    }
+                };
+                let copy = editor.insert(ty, &*val)?;
+                map.insert((ty, obj), copy);
+                copies.push(copy);
+            }
+            res.push((ty, copies));
+        }
+        for &(ty, ref copies) in &res {
+            for &copy in copies {
+                self.references.remap(editor, ty, copy, &map)?;
+            }
+        }
+        let hits: Vec<(Type, Object)> = res.iter()
+            .flat_map(|&(ty, ref objs)| objs.iter().map(move |&obj| (ty, obj)))
+            .collect();
+        select_hits(editor, &hits, &self.filter, SelectMode::Replace)?;
+        Ok(res)
+    }
+}
+
+impl Action for Duplicate {
+    fn name(&self) -> &str {
+        "duplicate"
+    }
+
+    fn description(&self) -> &str {
+        "Duplicate the selected objects"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        self.duplicate(editor)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+    const LINE: Type = Type("line");
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<[Object; 2]>(LINE);
+        for i in 0..2 { editor.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        editor.insert(LINE, &[Object(0), Object(1)]).unwrap();
+        editor
+    }
+
+    fn duplicate(editor: &VecEditor) -> Duplicate {
+        let mut references = References::new();
+        references.register::<[Object; 2]>(LINE, POINT, |line| line.to_vec(), |line| {
+            line.iter_mut().collect()
+        });
+        let cloner = Rc::new(editor.cloner().clone());
+        Duplicate::new(cloner, Rc::new(references), vec![POINT, LINE])
+            .offset::<[f64; 2]>(POINT, |p| p[1] += 1.0)
+    }
+
+    #[test]
+    fn remap_copied_references() {
+        let mut editor = editor();
+        let action = duplicate(&editor);
+        // Only the first point is duplicated with the line.
+        editor.select(POINT, Object(0)).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+        let copies = action.duplicate(&mut editor).unwrap();
+        assert_eq!(copies, vec![(POINT, vec![Object(2)]), (LINE, vec![Object(1)])]);
+        assert_eq!(editor.items::<[f64; 2]>(POINT).unwrap()[2], [0.0, 1.0]);
+        assert_eq!(editor.items::<[Object; 2]>(LINE).unwrap(), &[
+            [Object(0), Object(1)],
+            [Object(2), Object(1)],
+        ]);
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(2)]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(1)]);
+    }
+
+    #[test]
+    fn nothing_selected() {
+        let mut editor = editor();
+        let action = duplicate(&editor);
+        action.execute(&mut editor).unwrap();
+        assert_eq!(editor.all(POINT).len(), 2);
+        assert_eq!(action.types(), &[POINT, LINE]);
+    }
+}
@@ -0,0 +1,146 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+
+use {Object, Type, Value};
+
+/// The reason an editor operation failed.
+///
+/// Returned by every fallible `Editor` method, such that generic actions
+/// can decide whether to report the error, recover or roll back.
+pub enum EditorError {
+    /// The type is not known by the editor.
+    UnknownType(Type),
+    /// The object does not exist, or has been deleted.
+    StaleObject(Type, Object),
+    /// The arguments could not be downcast to the type expected by the editor.
+    DowncastMismatch {
+        /// The name of the expected Rust type.
+        expected: &'static str,
+        /// The name of the actual Rust type, if known.
+        actual: Option<&'static str>,
+    },
+    /// The editor refused the change because it would break a constraint.
+    ConstraintViolation(String),
+    /// The object is on a locked layer and can not be changed, see `Layers`.
+    Locked(Type, Object),
+    /// Data could not be read, for example when loading a document.
+    InvalidData(String),
+    /// An editor specific error.
+    Custom(Box<dyn Any + Send + Sync>),
+}
+
+impl EditorError {
+    /// Creates a downcast mismatch error expecting type `T`.
+    pub fn downcast<T: ?Sized>(actual: Option<&'static str>) -> EditorError {
+        EditorError::DowncastMismatch {
+            expected: ::std::any::type_name::<T>(),
+            actual,
+        }
+    }
+}
+
+/// Downcasts a value, naming the actual type in the error when it is known.
+pub(crate) fn downcast<T: Any>(val: &dyn Any) -> Result<&T, EditorError> {
+    val.downcast_ref::<T>().ok_or_else(|| EditorError::downcast::<T>(type_name_of(val)))
+}
+
+/// Gets the name of the Rust type of a value, if it is a type commonly passed by mistake.
+///
+/// `&dyn Any` does not tell its type, so this checks values such as a `Value`
+/// instead of the stored type, or a `Box<dyn Any>` that was not dereferenced.
+pub(crate) fn type_name_of(val: &dyn Any) -> Option<&'static str> {
+    macro_rules! check {
+        ($($t:ty),*) => {
+            $(if val.is::<$t>() { return Some(::std::any::type_name::<$t>()); })*
+        }
+    }
+    check!(Box<dyn Any>, Value, bool, f64, f32, i64, i32, i16, i8, u64, u32, u16, u8, usize,
+        isize, String, &'static str, ());
+    None
+}
+
+impl fmt::Debug for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditorError::UnknownType(ty) =>
+                f.debug_tuple("UnknownType").field(&ty).finish(),
+            EditorError::StaleObject(ty, obj) =>
+                f.debug_tuple("StaleObject").field(&ty).field(&obj).finish(),
+            EditorError::DowncastMismatch { expected, actual } =>
+                f.debug_struct("DowncastMismatch")
+                    .field("expected", &expected)
+                    .field("actual", &actual)
+                    .finish(),
+            EditorError::ConstraintViolation(ref msg) =>
+                f.debug_tuple("ConstraintViolation").field(msg).finish(),
+            EditorError::Locked(ty, obj) =>
+                f.debug_tuple("Locked").field(&ty).field(&obj).finish(),
+            EditorError::InvalidData(ref msg) =>
+                f.debug_tuple("InvalidData").field(msg).finish(),
+            EditorError::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl fmt::Display for EditorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EditorError::UnknownType(ty) =>
+                write!(f, "unknown type `{}`", ty.0),
+            EditorError::StaleObject(ty, obj) =>
+                write!(f, "object {} of type `{}` does not exist", obj.0, ty.0),
+            EditorError::DowncastMismatch { expected, actual: Some(actual) } =>
+                write!(f, "expected `{}`, found `{}`", expected, actual),
+            EditorError::DowncastMismatch { expected, actual: None } =>
+                write!(f, "expected `{}`", expected),
+            EditorError::ConstraintViolation(ref msg) =>
+                write!(f, "constraint violation: {}", msg),
+            EditorError::Locked(ty, obj) =>
+                write!(f, "object {} of type `{}` is on a locked layer", obj.0, ty.0),
+            EditorError::InvalidData(ref msg) => f.write_str(msg),
+            EditorError::Custom(_) => f.write_str("editor specific error"),
+        }
+    }
+}
+
+impl Error for EditorError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn display() {
+        let err = EditorError::StaleObject(Type("point"), Object(3));
+        assert_eq!(err.to_string(), "object 3 of type `point` does not exist");
+        let err = EditorError::downcast::<u32>(Some("f64"));
+        assert_eq!(err.to_string(), "expected `u32`, found `f64`");
+        let err = EditorError::Locked(Type("point"), Object(1));
+        assert_eq!(format!("{:?}", err), "Locked(Type(\"point\"), Object(1))");
+    }
+
+    #[test]
+    fn send_sync() {
+        fn check<T: Send + Sync + 'static>() {}
+        check::<EditorError>();
+    }
+
+    #[test]
+    fn actual_type_name() {
+        let boxed: Box<dyn Any> = Box::new(1u32);
+        assert_eq!(type_name_of(&boxed), Some(::std::any::type_name::<Box<dyn Any>>()));
+        assert_eq!(type_name_of(&Value::Bool(true)), Some(::std::any::type_name::<Value>()));
+        assert_eq!(type_name_of(&1.0f64), Some("f64"));
+        assert_eq!(type_name_of(&[0u8; 2]), None);
+
+        let mut items: Vec<u32> = vec![];
+        match ::insert(&mut items, &boxed) {
+            Err(EditorError::DowncastMismatch { expected, actual }) => {
+                assert_eq!(expected, "u32");
+                assert_eq!(actual, Some(::std::any::type_name::<Box<dyn Any>>()));
+            }
+            _ => panic!("expected a downcast mismatch"),
+        }
+    }
+}
@@ -0,0 +1,338 @@
+use std::any::Any;
+
+use {Bounds2, Editor, EditorError, Hit, Object, Plane, Type, View, ViewId};
+
+/// A change made to an editor.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// An object was inserted.
+    Inserted(Type, Object),
+    /// An object was deleted.
+    Deleted {
+        /// The type of the object.
+        ty: Type,
+        /// The deleted object.
+        obj: Object,
+        /// The object moved into the place of the deleted one by swap-remove.
+        moved: Option<Object>,
+    },
+    /// An object was updated.
+    Updated(Type, Object),
+    /// An object was replaced with another.
+    Replaced {
+        /// The type of the objects.
+        ty: Type,
+        /// The replaced object.
+        from: Object,
+        /// The object it was replaced with.
+        to: Object,
+    },
+    /// The selection of a type changed.
+    SelectionChanged(Type),
+    /// The editor navigated to an object.
+    Navigated(Type, Object),
+}
+
+impl Event {
+    /// Gets the type the event is about.
+    pub fn ty(&self) -> Type {
+        match *self {
+            Event::Inserted(ty, _) |
+            Event::Deleted { ty, .. } |
+            Event::Updated(ty, _) |
+            Event::Replaced { ty, .. } |
+            Event::SelectionChanged(ty) |
+            Event::Navigated(ty, _) => ty,
+        }
+    }
+}
+
+/// Identifies a subscriber, used to unsubscribe.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Subscription(usize);
+
+type Subscriber = Box<dyn FnMut(&[Event])>;
+
+/// Wraps an editor and notifies subscribers about changes.
+///
+/// Events are queued as changes are made and delivered in one batch
+/// to every subscriber when `refresh_views` is called,
+/// such that views do not have to poll the editor.
+pub struct EventBus<E: Editor> {
+    editor: E,
+    pending: Vec<Event>,
+    subscribers: Vec<(Subscription, Subscriber)>,
+    next_id: usize,
+}
+
+impl<E: Editor> EventBus<E> {
+    /// Creates a new event bus wrapping an editor.
+    pub fn new(editor: E) -> EventBus<E> {
+        EventBus {
+            editor,
+            pending: vec![],
+            subscribers: vec![],
+            next_id: 0,
+        }
+    }
+
+    /// Gets a reference to the wrapped editor.
+    pub fn get_ref(&self) -> &E {
+        &self.editor
+    }
+
+    /// Returns the wrapped editor, dropping pending events.
+    pub fn into_inner(self) -> E {
+        self.editor
+    }
+
+    /// Adds a subscriber, which receives batches of events.
+    pub fn subscribe<F: FnMut(&[Event]) + 'static>(&mut self, f: F) -> Subscription {
+        let id = Subscription(self.next_id);
+        self.next_id += 1;
+        self.subscribers.push((id, Box::new(f)));
+        id
+    }
+
+    /// Removes a subscriber, returning `true` if it was found.
+    pub fn unsubscribe(&mut self, id: Subscription) -> bool {
+        let n = self.subscribers.len();
+        self.subscribers.retain(|&(sub, _)| sub != id);
+        self.subscribers.len() != n
+    }
+
+    /// Gets the events not yet delivered.
+    pub fn pending(&self) -> &[Event] {
+        &self.pending
+    }
+
+    fn push(&mut self, event: Event) {
+        // Only one selection event per type is needed in a batch.
+        if let Event::SelectionChanged(_) = event {
+            if self.pending.contains(&event) { return; }
+        }
+        self.pending.push(event);
+    }
+
+    fn select_with<F>(&mut self, ty: Type, f: F) -> Result<(), EditorError>
+        where F: FnOnce(&mut E) -> Result<(), EditorError>
+    {
+        let selected = self.editor.selected(ty);
+        let multiple = self.editor.multiple_selected(ty);
+        f(&mut self.editor)?;
+        if selected != self.editor.selected(ty) || multiple != self.editor.multiple_selected(ty) {
+            self.push(Event::SelectionChanged(ty));
+        }
+        Ok(())
+    }
+}
+
+impl<E: Editor> Editor for EventBus<E> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.editor.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.editor.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.editor.hit_2d(pos)
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.editor.hit_rect_2d(rect)
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.editor.hit_polygon_2d(points)
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.editor.hit_3d(pos)
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.editor.hit_frustum(planes)
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.editor.hit_ray(origin, direction)
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.select_with(ty, |e| e.select(ty, obj))
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.select_with(ty, |e| e.select_multiple(ty, objs))
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.select_with(ty, |e| e.deselect_multiple(ty, objs))
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.select_with(ty, |e| e.select_none(ty))
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let obj = self.editor.insert(ty, args)?;
+        self.push(Event::Inserted(ty, obj));
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        let selected = self.editor.selected(ty);
+        let multiple = self.editor.multiple_selected(ty);
+        let moved = self.editor.delete(ty, obj)?;
+        self.push(Event::Deleted { ty, obj, moved });
+        if selected != self.editor.selected(ty) || multiple != self.editor.multiple_selected(ty) {
+            self.push(Event::SelectionChanged(ty));
+        }
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        self.editor.update(ty, obj, args)?;
+        self.push(Event::Updated(ty, obj));
+        Ok(())
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        self.editor.replace(ty, from, to)?;
+        self.push(Event::Replaced { ty, from, to });
+        Ok(())
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.editor.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.editor.visible(ty)
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.editor.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.editor.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.editor.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.editor.navigate_to(ty, obj)?;
+        self.push(Event::Navigated(ty, obj));
+        Ok(())
+    }
+
+    /// Refreshes the views of the wrapped editor,
+    /// then delivers pending events to every subscriber.
+    fn refresh_views(&mut self) {
+        self.editor.refresh_views();
+        if self.pending.is_empty() { return; }
+
+        let events = ::std::mem::take(&mut self.pending);
+        for &mut (_, ref mut f) in &mut self.subscribers {
+            f(&events);
+        }
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.editor.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.editor.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.editor.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.editor.set_active_view(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    type Batches = Rc<RefCell<Vec<Vec<Event>>>>;
+
+    fn bus() -> (EventBus<VecEditor>, Batches) {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        let mut bus = EventBus::new(editor);
+        let batches = Rc::new(RefCell::new(vec![]));
+        let received = batches.clone();
+        bus.subscribe(move |events| received.borrow_mut().push(events.to_vec()));
+        (bus, batches)
+    }
+
+    #[test]
+    fn batches() {
+        let (mut bus, batches) = bus();
+        bus.refresh_views();
+        assert!(batches.borrow().is_empty());
+
+        for i in 0..3u32 { bus.insert(POINT, &i).unwrap(); }
+        bus.select(POINT, Object(0)).unwrap();
+        bus.select_multiple(POINT, &[Object(2)]).unwrap();
+        bus.update(POINT, Object(1), &5u32).unwrap();
+        bus.delete(POINT, Object(0)).unwrap();
+        assert!(bus.update(POINT, Object(9), &0u32).is_err());
+        bus.refresh_views();
+        assert_eq!(batches.borrow()[0], vec![
+            Event::Inserted(POINT, Object(0)),
+            Event::Inserted(POINT, Object(1)),
+            Event::Inserted(POINT, Object(2)),
+            Event::SelectionChanged(POINT),
+            Event::Updated(POINT, Object(1)),
+            Event::Deleted { ty: POINT, obj: Object(0), moved: Some(Object(2)) },
+        ]);
+        assert!(bus.pending().is_empty());
+    }
+
+    #[test]
+    fn unchanged_selection() {
+        let (mut bus, batches) = bus();
+        bus.insert(POINT, &0u32).unwrap();
+        bus.refresh_views();
+        bus.select_none(POINT).unwrap();
+        bus.deselect_multiple(POINT, &[Object(0)]).unwrap();
+        assert!(bus.pending().is_empty());
+        bus.select(POINT, Object(0)).unwrap();
+        bus.select(POINT, Object(0)).unwrap();
+        assert_eq!(bus.pending(), &[Event::SelectionChanged(POINT)]);
+        bus.refresh_views();
+        assert_eq!(batches.borrow().len(), 2);
+    }
+
+    #[test]
+    fn unsubscribe() {
+        let (mut bus, batches) = bus();
+        let count = Rc::new(RefCell::new(0));
+        let counted = count.clone();
+        let id = bus.subscribe(move |events| *counted.borrow_mut() += events.len());
+        bus.insert(POINT, &0u32).unwrap();
+        bus.refresh_views();
+        assert!(bus.unsubscribe(id));
+        assert!(!bus.unsubscribe(id));
+        bus.insert(POINT, &1u32).unwrap();
+        bus.refresh_views();
+        assert_eq!(*count.borrow(), 1);
+        assert_eq!(batches.borrow().len(), 2);
+    }
+}
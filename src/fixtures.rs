@@ -0,0 +1,40 @@
+//! Objects shared by the tests of several modules.
+
+use {EditorError, FromValue, Object, ToValue, Type, TypeRegistry, Value};
+
+pub const POINT: Type = Type("point");
+pub const LINE: Type = Type("line");
+
+/// A line between two points.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Line {
+    pub a: Object,
+    pub b: Object,
+}
+
+pub fn line(a: usize, b: usize) -> Line {
+    Line { a: Object(a), b: Object(b) }
+}
+
+impl ToValue for Line {
+    fn to_value(&self) -> Value {
+        Value::map(vec![
+            ("a", Value::Reference(POINT, self.a)),
+            ("b", Value::Reference(POINT, self.b)),
+        ])
+    }
+}
+
+impl FromValue for Line {
+    fn from_value(val: &Value) -> Result<Line, EditorError> {
+        Ok(Line { a: val.field("a")?, b: val.field("b")? })
+    }
+}
+
+/// Points stored as `[f64; 2]` and lines, with value conversions.
+pub fn types() -> TypeRegistry {
+    let mut types = TypeRegistry::new();
+    types.register::<[f64; 2]>(POINT).values();
+    types.register::<Line>(LINE).values();
+    types
+}
@@ -0,0 +1,344 @@
+use std::collections::{HashMap, HashSet};
+
+use bounds::{not_finite, slab_range};
+use {Bounds2, EditorError, Object, Type};
+
+type Cell = (i64, i64);
+
+/// The maximum number of cells an object is stored in.
+///
+/// Larger objects are kept in a list which every query tests.
+pub const MAX_CELLS: f64 = 1024.0;
+
+// Keeps cell coordinates far from overflowing.
+const MAX_COORD: i64 = 1 << 52;
+
+/// A uniform grid for fast 2D queries.
+///
+/// Objects are stored in every cell their bounds overlap,
+/// so the cell size should be about the size of typical objects.
+/// Objects overlapping more than `MAX_CELLS` cells are tested by every query instead.
+/// The grid must be kept in sync with the editor,
+/// by calling `insert` on insert and update, and `deleted` on delete.
+#[derive(Clone, Debug)]
+pub struct Grid {
+    cell_size: f64,
+    cells: HashMap<Cell, Vec<(Type, Object)>>,
+    entries: HashMap<(Type, Object), Bounds2>,
+    overflow: Vec<(Type, Object)>,
+    // Grows but never shrinks, which is fine for clipping rays.
+    extent: Option<(Cell, Cell)>,
+}
+
+impl Default for Grid {
+    fn default() -> Grid {
+        Grid::new(1.0)
+    }
+}
+
+impl Grid {
+    /// Creates a new empty grid.
+    pub fn new(cell_size: f64) -> Grid {
+        assert!(cell_size > 0.0, "cell size must be positive");
+        Grid {
+            cell_size,
+            cells: HashMap::new(),
+            entries: HashMap::new(),
+            overflow: vec![],
+            extent: None,
+        }
+    }
+
+    /// Gets the number of objects.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns `true` if there are no objects.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Gets the bounds of an object.
+    pub fn bounds(&self, ty: Type, obj: Object) -> Option<Bounds2> {
+        self.entries.get(&(ty, obj)).cloned()
+    }
+
+    /// Inserts an object, or moves it if it is already in the grid.
+    ///
+    /// Bounds that are not finite are refused and the object is removed from the grid.
+    pub fn insert(&mut self, ty: Type, obj: Object, bounds: Bounds2) -> Result<(), EditorError> {
+        self.remove(ty, obj);
+        if !bounds.is_finite() { return Err(not_finite(ty, obj)); }
+        self.entries.insert((ty, obj), bounds);
+        let (min, max) = match self.cells_of(&bounds) {
+            None => {
+                self.overflow.push((ty, obj));
+                return Ok(());
+            }
+            Some(range) => range,
+        };
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                self.cells.entry((x, y)).or_default().push((ty, obj));
+            }
+        }
+        self.extent = Some(match self.extent {
+            None => (min, max),
+            Some((a, b)) => ((a.0.min(min.0), a.1.min(min.1)), (b.0.max(max.0), b.1.max(max.1))),
+        });
+        Ok(())
+    }
+
+    /// Removes an object, returning `true` if it was found.
+    pub fn remove(&mut self, ty: Type, obj: Object) -> bool {
+        let bounds = match self.entries.remove(&(ty, obj)) {
+            None => return false,
+            Some(bounds) => bounds,
+        };
+        let (min, max) = match self.cells_of(&bounds) {
+            None => {
+                self.overflow.retain(|&item| item != (ty, obj));
+                return true;
+            }
+            Some(range) => range,
+        };
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                if let Some(items) = self.cells.get_mut(&(x, y)) {
+                    items.retain(|&item| item != (ty, obj));
+                    if items.is_empty() { self.cells.remove(&(x, y)); }
+                }
+            }
+        }
+        true
+    }
+
+    /// Removes all objects of a type.
+    pub fn remove_type(&mut self, ty: Type) {
+        let objs: Vec<Object> = self.entries.keys().filter(|k| k.0 == ty).map(|k| k.1).collect();
+        for obj in objs { self.remove(ty, obj); }
+    }
+
+    /// Updates the grid after deleting an object with swap-remove,
+    /// see `Editor::delete`.
+    pub fn deleted(&mut self, ty: Type, obj: Object, moved: Option<Object>) {
+        self.remove(ty, obj);
+        if let Some(last) = moved {
+            if let Some(bounds) = self.bounds(ty, last) {
+                self.remove(ty, last);
+                // The bounds were checked when inserted.
+                let _ = self.insert(ty, obj, bounds);
+            }
+        }
+    }
+
+    /// Finds objects which bounds contain a point.
+    pub fn query_point(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        let items = self.cells.get(&self.cell(pos)).map(|items| &items[..]).unwrap_or(&[]);
+        items.iter().chain(&self.overflow)
+            .filter(|&item| self.entries[item].contains(pos))
+            .cloned()
+            .collect()
+    }
+
+    /// Finds objects which bounds intersect a rectangle.
+    pub fn query_rect(&self, rect: &Bounds2) -> Vec<(Type, Object)> {
+        let (min, max) = self.cell_range(rect);
+        let mut seen = HashSet::new();
+        let mut res = vec![];
+        self.collect_rect(&self.overflow, rect, &mut seen, &mut res);
+        // Loop over the smaller of the cell range and the occupied cells.
+        if span(min, max) <= self.cells.len() as f64 {
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    if let Some(items) = self.cells.get(&(x, y)) {
+                        self.collect_rect(items, rect, &mut seen, &mut res);
+                    }
+                }
+            }
+        } else {
+            for (cell, items) in &self.cells {
+                if cell.0 >= min.0 && cell.0 <= max.0 && cell.1 >= min.1 && cell.1 <= max.1 {
+                    self.collect_rect(items, rect, &mut seen, &mut res);
+                }
+            }
+        }
+        res
+    }
+
+    /// Finds objects which bounds are hit by a ray, nearest first.
+    pub fn query_ray(&self, origin: [f64; 2], direction: [f64; 2]) -> Vec<(Type, Object)> {
+        let mut hits = vec![];
+        for &item in &self.overflow {
+            if let Some(t) = self.entries[&item].ray(origin, direction) { hits.push((t, item)); }
+        }
+        if let Some(extent) = self.extent { self.walk_ray(origin, direction, extent, &mut hits); }
+        hits.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(::std::cmp::Ordering::Equal));
+        hits.into_iter().map(|(_, item)| item).collect()
+    }
+
+    /// Collects hits of objects in the cells along a ray.
+    fn walk_ray(
+        &self,
+        origin: [f64; 2],
+        direction: [f64; 2],
+        (min, max): (Cell, Cell),
+        hits: &mut Vec<(f64, (Type, Object))>
+    ) {
+        let s = self.cell_size;
+        let lo = [min.0 as f64 * s, min.1 as f64 * s];
+        let hi = [(max.0 + 1) as f64 * s, (max.1 + 1) as f64 * s];
+        let (enter, exit) = match slab_range(&origin, &direction, &lo, &hi) {
+            None => return,
+            Some(range) => range,
+        };
+
+        // Test every object when the ray passes more cells than there are objects.
+        let steps = (exit - enter) * (direction[0].abs() + direction[1].abs()) / s;
+        if steps.is_nan() || steps > self.entries.len() as f64 {
+            for (&item, bounds) in &self.entries {
+                if self.overflow.contains(&item) { continue; }
+                if let Some(t) = bounds.ray(origin, direction) { hits.push((t, item)); }
+            }
+            return;
+        }
+
+        // Walk the cells along the ray.
+        let start = [origin[0] + direction[0] * enter, origin[1] + direction[1] * enter];
+        let cell = self.cell(start);
+        let mut cell = [cell.0.max(min.0).min(max.0), cell.1.max(min.1).min(max.1)];
+        let mut step = [0; 2];
+        let mut t_max = [f64::INFINITY; 2];
+        let mut t_delta = [f64::INFINITY; 2];
+        for i in 0..2 {
+            if direction[i] > 0.0 {
+                step[i] = 1;
+                t_max[i] = ((cell[i] + 1) as f64 * s - origin[i]) / direction[i];
+                t_delta[i] = s / direction[i];
+            } else if direction[i] < 0.0 {
+                step[i] = -1;
+                t_max[i] = (cell[i] as f64 * s - origin[i]) / direction[i];
+                t_delta[i] = -s / direction[i];
+            }
+        }
+        let mut seen = HashSet::new();
+        loop {
+            if let Some(items) = self.cells.get(&(cell[0], cell[1])) {
+                for &item in items {
+                    if !seen.insert(item) { continue; }
+                    if let Some(t) = self.entries[&item].ray(origin, direction) {
+                        hits.push((t, item));
+                    }
+                }
+            }
+            let i = if t_max[0] < t_max[1] { 0 } else { 1 };
+            if t_max[i] > exit || step[i] == 0 { break; }
+            cell[i] += step[i];
+            t_max[i] += t_delta[i];
+            if cell[0] < min.0 || cell[0] > max.0 || cell[1] < min.1 || cell[1] > max.1 { break; }
+        }
+    }
+
+    fn collect_rect(
+        &self,
+        items: &[(Type, Object)],
+        rect: &Bounds2,
+        seen: &mut HashSet<(Type, Object)>,
+        res: &mut Vec<(Type, Object)>
+    ) {
+        for &item in items {
+            if seen.insert(item) && self.entries[&item].intersects(rect) { res.push(item); }
+        }
+    }
+
+    fn cell(&self, pos: [f64; 2]) -> Cell {
+        ((pos[0] / self.cell_size).floor() as i64, (pos[1] / self.cell_size).floor() as i64)
+    }
+
+    fn cell_range(&self, bounds: &Bounds2) -> (Cell, Cell) {
+        (self.cell(bounds.min), self.cell(bounds.max))
+    }
+
+    /// Gets the cells to store an object in, or `None` if it goes in the overflow list.
+    fn cells_of(&self, bounds: &Bounds2) -> Option<(Cell, Cell)> {
+        let (min, max) = self.cell_range(bounds);
+        let coords = [min.0, min.1, max.0, max.1];
+        if span(min, max) > MAX_CELLS || coords.iter().any(|x| x.abs() > MAX_COORD) { None }
+        else { Some((min, max)) }
+    }
+}
+
+/// Gets the number of cells in a range, as a float to not overflow.
+fn span(min: Cell, max: Cell) -> f64 {
+    (max.0 as f64 - min.0 as f64 + 1.0) * (max.1 as f64 - min.1 as f64 + 1.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    fn square(x: f64, y: f64, size: f64) -> Bounds2 {
+        Bounds2::new([x, y], [x + size, y + size])
+    }
+
+    #[test]
+    fn queries() {
+        let mut grid = Grid::new(1.0);
+        grid.insert(POINT, Object(0), square(0.0, 0.0, 0.5)).unwrap();
+        grid.insert(POINT, Object(1), square(2.5, 0.0, 2.0)).unwrap();
+        assert_eq!(grid.query_point([0.25, 0.25]), vec![(POINT, Object(0))]);
+        assert!(grid.query_point([0.75, 0.75]).is_empty());
+        assert_eq!(grid.query_rect(&square(3.0, 1.0, 0.1)), vec![(POINT, Object(1))]);
+        assert_eq!(grid.query_ray([-1.0, 0.25], [1.0, 0.0]),
+                   vec![(POINT, Object(0)), (POINT, Object(1))]);
+        assert_eq!(grid.query_ray([9.0, 0.25], [-1.0, 0.0]),
+                   vec![(POINT, Object(1)), (POINT, Object(0))]);
+
+        grid.deleted(POINT, Object(0), Some(Object(1)));
+        assert_eq!(grid.len(), 1);
+        assert_eq!(grid.query_point([3.0, 1.0]), vec![(POINT, Object(0))]);
+    }
+
+    #[test]
+    fn refuse_non_finite() {
+        let mut grid = Grid::new(1.0);
+        grid.insert(POINT, Object(0), square(0.0, 0.0, 1.0)).unwrap();
+        for &x in &[f64::NAN, f64::INFINITY] {
+            match grid.insert(POINT, Object(0), square(x, 0.0, 1.0)) {
+                Err(EditorError::ConstraintViolation(_)) => {}
+                _ => panic!("expected a constraint violation"),
+            }
+        }
+        assert!(grid.is_empty());
+        assert!(grid.query_point([0.5, 0.5]).is_empty());
+    }
+
+    #[test]
+    fn overflow() {
+        let mut grid = Grid::new(1.0);
+        let huge = Bounds2::new([-1e300, -1e300], [1e300, 1e300]);
+        grid.insert(POINT, Object(0), huge).unwrap();
+        grid.insert(POINT, Object(1), square(1e18, 1e18, 1.0)).unwrap();
+        grid.insert(POINT, Object(2), square(0.0, 0.0, 1.0)).unwrap();
+        assert_eq!(grid.query_point([1e200, 0.0]), vec![(POINT, Object(0))]);
+        assert_eq!(grid.query_point([0.5, 0.5]).len(), 2);
+        assert_eq!(grid.query_rect(&square(1e18, 1e18, 0.5)).len(), 2);
+        assert_eq!(grid.query_ray([-1e10, 0.5], [1.0, 0.0]),
+                   vec![(POINT, Object(0)), (POINT, Object(2))]);
+        assert!(grid.remove(POINT, Object(0)));
+        assert!(grid.remove(POINT, Object(1)));
+        assert_eq!(grid.query_rect(&huge), vec![(POINT, Object(2))]);
+    }
+
+    #[test]
+    fn sparse_ray() {
+        let mut grid = Grid::new(1.0);
+        grid.insert(POINT, Object(0), square(0.0, 0.0, 1.0)).unwrap();
+        grid.insert(POINT, Object(1), square(1e15, 0.0, 1.0)).unwrap();
+        assert_eq!(grid.query_ray([-1.0, 0.5], [1.0, 0.0]),
+                   vec![(POINT, Object(0)), (POINT, Object(1))]);
+    }
+}
@@ -0,0 +1,577 @@
+use std::any::Any;
+use std::collections::HashSet;
+use std::rc::Rc;
+
+use error::downcast;
+use selection::select_hits;
+use {Action, Editor, EditorError, Object, SelectMode, Type};
+
+/// Parent-child relationships between objects, for scenes, UI trees and skeletons.
+///
+/// Implementations only need to read and write the parent of an object,
+/// see `Parents` for storing parents in fields.
+/// The provided methods find children by scanning the objects of `child_types`,
+/// which can be overridden when the editor keeps an index.
+///
+/// Deleting with swap-remove moves the last object of a table,
+/// so use `Hierarchy::delete` instead of `Editor::delete`
+/// to keep the children of the moved object.
+pub trait Hierarchy {
+    /// Gets the types which objects can have a parent of type `ty`.
+    fn child_types(&self, ty: Type) -> Vec<Type>;
+    /// Gets the parent of an object.
+    fn parent(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Option<(Type, Object)>, EditorError>;
+    /// Sets the parent of an object without checking for cycles, see `reparent`.
+    fn set_parent(
+        &self,
+        editor: &mut dyn Editor,
+        ty: Type,
+        obj: Object,
+        parent: Option<(Type, Object)>
+    ) -> Result<(), EditorError>;
+
+    /// Gets the children of an object.
+    fn children(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut res = vec![];
+        for child_ty in self.child_types(ty) {
+            for child in editor.all(child_ty) {
+                if self.parent(editor, child_ty, child)? == Some((ty, obj)) {
+                    res.push((child_ty, child));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Gets the parent, grandparent and so on of an object, nearest first.
+    ///
+    /// Returns `EditorError::ConstraintViolation` if the parents form a cycle.
+    fn ancestors(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut res: Vec<(Type, Object)> = vec![];
+        let mut seen = HashSet::new();
+        seen.insert((ty, obj));
+        let mut cur = self.parent(editor, ty, obj)?;
+        while let Some((ty, obj)) = cur {
+            if !seen.insert((ty, obj)) {
+                return Err(EditorError::ConstraintViolation(format!(
+                    "`{}` {} is its own ancestor", ty.0, obj.0
+                )));
+            }
+            res.push((ty, obj));
+            cur = self.parent(editor, ty, obj)?;
+        }
+        Ok(res)
+    }
+
+    /// Gets the children, grandchildren and so on of an object, parents before children.
+    fn descendants(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut res = vec![];
+        let mut seen = HashSet::new();
+        let mut stack = self.children(editor, ty, obj)?;
+        stack.reverse();
+        while let Some((ty, obj)) = stack.pop() {
+            if !seen.insert((ty, obj)) { continue; }
+            res.push((ty, obj));
+            let mut children = self.children(editor, ty, obj)?;
+            children.reverse();
+            stack.extend(children);
+        }
+        Ok(res)
+    }
+
+    /// Moves an object to a new parent, or makes it a root with `None`.
+    ///
+    /// Refuses to make an object its own ancestor.
+    fn reparent(
+        &self,
+        editor: &mut dyn Editor,
+        ty: Type,
+        obj: Object,
+        parent: Option<(Type, Object)>
+    ) -> Result<(), EditorError> {
+        if let Some(parent) = parent {
+            let cycle = parent == (ty, obj) ||
+                self.ancestors(editor, parent.0, parent.1)?.contains(&(ty, obj));
+            if cycle {
+                return Err(EditorError::ConstraintViolation(format!(
+                    "`{}` {} can not be a child of its descendant `{}` {}",
+                    ty.0, obj.0, (parent.0).0, (parent.1).0
+                )));
+            }
+        }
+        self.set_parent(editor, ty, obj, parent)
+    }
+
+    /// Deletes an object without children,
+    /// moving the children of the object moved by swap-remove.
+    ///
+    /// Refuses to delete an object with children, which would be left with a wrong parent.
+    /// See `delete_subtree` for deleting the children too.
+    fn delete(&self, editor: &mut dyn Editor, ty: Type, obj: Object)
+    -> Result<Option<Object>, EditorError> {
+        if !self.children(editor, ty, obj)?.is_empty() {
+            return Err(EditorError::ConstraintViolation(format!(
+                "`{}` {} has children", ty.0, obj.0
+            )));
+        }
+        let moved = editor.delete(ty, obj)?;
+        if let Some(last) = moved {
+            // The last object now takes the place of the deleted object.
+            for (child_ty, child) in self.children(editor, ty, last)? {
+                self.set_parent(editor, child_ty, child, Some((ty, obj)))?;
+            }
+        }
+        Ok(moved)
+    }
+
+    /// Deletes an object with its descendants.
+    fn delete_subtree(&self, editor: &mut dyn Editor, ty: Type, obj: Object)
+    -> Result<(), EditorError> {
+        delete_subtrees(self, editor, &[(ty, obj)])
+    }
+}
+
+/// Deletes objects with their descendants, children before parents.
+fn delete_subtrees<H: Hierarchy + ?Sized>(
+    hierarchy: &H,
+    editor: &mut dyn Editor,
+    roots: &[(Type, Object)]
+) -> Result<(), EditorError> {
+    let mut pending: Vec<(usize, Type, Object)> = vec![];
+    for &(ty, obj) in roots {
+        let depth = hierarchy.ancestors(editor, ty, obj)?.len();
+        let mut items = vec![(depth, ty, obj)];
+        for (ty, obj) in hierarchy.descendants(editor, ty, obj)? {
+            items.push((hierarchy.ancestors(editor, ty, obj)?.len(), ty, obj));
+        }
+        for item in items {
+            if !pending.iter().any(|&(_, ty, obj)| (ty, obj) == (item.1, item.2)) {
+                pending.push(item);
+            }
+        }
+    }
+    // Deleting the deepest objects first deletes children before their parents.
+    // Moving an object does not change its depth, so the order stays valid.
+    pending.sort_by_key(|&(depth, _, _)| depth);
+    while let Some((_, ty, obj)) = pending.pop() {
+        if let Some(moved) = hierarchy.delete(editor, ty, obj)? {
+            for item in &mut pending {
+                if (item.1, item.2) == (ty, moved) { item.2 = obj; }
+            }
+        }
+    }
+    Ok(())
+}
+
+type ParentFn = Box<dyn Fn(&dyn Any) -> Result<Option<Object>, EditorError>>;
+type SetParentFn = Box<dyn Fn(&dyn Any, Option<Object>) -> Result<Box<dyn Any>, EditorError>>;
+
+/// Declares the field holding the parent of objects of a type.
+struct Declaration {
+    ty: Type,
+    parent_ty: Type,
+    get: ParentFn,
+    set: SetParentFn,
+}
+
+/// A hierarchy where objects store their parent in a field.
+///
+/// Objects of types without a declared parent field are roots.
+#[derive(Default)]
+pub struct Parents {
+    declarations: Vec<Declaration>,
+}
+
+impl Parents {
+    /// Creates a new hierarchy without parent fields.
+    pub fn new() -> Parents {
+        Parents::default()
+    }
+
+    /// Declares the field of `T` that holds the parent of type `parent_ty`.
+    ///
+    /// `T` is the Rust type used to store objects of type `ty`.
+    /// `get` reads the parent and `set` gives access to the same field,
+    /// for example `|node: &Node| node.parent` and `|node: &mut Node| &mut node.parent`.
+    /// Declaring a type again replaces the field.
+    pub fn register<T: Any + Clone>(
+        &mut self,
+        ty: Type,
+        parent_ty: Type,
+        get: fn(&T) -> Option<Object>,
+        set: fn(&mut T) -> &mut Option<Object>
+    ) {
+        let get = move |val: &dyn Any| -> Result<Option<Object>, EditorError> {
+            Ok(get(downcast::<T>(val)?))
+        };
+        let set = move |val: &dyn Any, parent: Option<Object>|
+        -> Result<Box<dyn Any>, EditorError> {
+            let mut val = downcast::<T>(val)?.clone();
+            *set(&mut val) = parent;
+            Ok(Box::new(val))
+        };
+        self.declarations.retain(|decl| decl.ty != ty);
+        self.declarations.push(Declaration {
+            ty,
+            parent_ty,
+            get: Box::new(get),
+            set: Box::new(set),
+        });
+    }
+
+    fn declaration(&self, ty: Type) -> Option<&Declaration> {
+        self.declarations.iter().find(|decl| decl.ty == ty)
+    }
+}
+
+impl Hierarchy for Parents {
+    fn child_types(&self, ty: Type) -> Vec<Type> {
+        self.declarations.iter().filter(|decl| decl.parent_ty == ty).map(|decl| decl.ty).collect()
+    }
+
+    fn parent(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Option<(Type, Object)>, EditorError> {
+        let val = editor.get(ty, obj)?;
+        match self.declaration(ty) {
+            None => Ok(None),
+            Some(decl) => Ok((decl.get)(val)?.map(|parent| (decl.parent_ty, parent))),
+        }
+    }
+
+    fn set_parent(
+        &self,
+        editor: &mut dyn Editor,
+        ty: Type,
+        obj: Object,
+        parent: Option<(Type, Object)>
+    ) -> Result<(), EditorError> {
+        let decl = match self.declaration(ty) {
+            Some(decl) if parent.map(|(parent_ty, _)| parent_ty == decl.parent_ty)
+                .unwrap_or(true) => decl,
+            _ => return Err(EditorError::ConstraintViolation(format!(
+                "`{}` can not have a parent of type `{}`",
+                ty.0, parent.map(|(parent_ty, _)| parent_ty.0).unwrap_or("none")
+            ))),
+        };
+        let val = (decl.set)(editor.get(ty, obj)?, parent.map(|(_, parent)| parent))?;
+        editor.update(ty, obj, &*val)
+    }
+}
+
+fn selection(editor: &dyn Editor, types: &[Type]) -> Vec<(Type, Object)> {
+    types.iter()
+        .flat_map(|&ty| editor.multiple_selected(ty).into_iter().map(move |obj| (ty, obj)))
+        .collect()
+}
+
+/// Groups the selected objects under a new object and selects it.
+///
+/// Only the topmost selected objects are grouped,
+/// such that selected descendants keep their parent.
+/// The group gets the parent of the grouped objects when they share one.
+pub struct Group {
+    /// The hierarchy to change.
+    pub hierarchy: Rc<dyn Hierarchy>,
+    /// The type of the group object.
+    pub group: Type,
+    /// Creates the arguments for inserting the group object.
+    pub new_group: fn() -> Box<dyn Any>,
+    /// The types to group.
+    pub filter: Vec<Type>,
+}
+
+impl Action for Group {
+    fn name(&self) -> &str {
+        "group"
+    }
+
+    fn description(&self) -> &str {
+        "Group the selected objects"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let selected = selection(editor, &self.filter);
+        let mut objs = vec![];
+        for &(ty, obj) in &selected {
+            let ancestors = self.hierarchy.ancestors(editor, ty, obj)?;
+            if !ancestors.iter().any(|ancestor| selected.contains(ancestor)) {
+                objs.push((ty, obj));
+            }
+        }
+        if objs.is_empty() { return Ok(()); }
+        let mut parents = vec![];
+        for &(ty, obj) in &objs {
+            let parent = self.hierarchy.parent(editor, ty, obj)?;
+            if !parents.contains(&parent) { parents.push(parent); }
+        }
+        let parent = if parents.len() == 1 { parents[0] } else { None };
+        let group = editor.insert(self.group, &*(self.new_group)())?;
+        if parent.is_some() { self.hierarchy.reparent(editor, self.group, group, parent)?; }
+        for (ty, obj) in objs {
+            self.hierarchy.reparent(editor, ty, obj, Some((self.group, group)))?;
+        }
+        let mut types = self.filter.clone();
+        if !types.contains(&self.group) { types.push(self.group); }
+        select_hits(editor, &[(self.group, group)], &types, SelectMode::Replace)
+    }
+}
+
+/// Moves the children of the selected objects to their parent,
+/// deletes the selected objects and selects the children.
+///
+/// Selected objects without children are kept.
+pub struct Ungroup {
+    /// The hierarchy to change.
+    pub hierarchy: Rc<dyn Hierarchy>,
+    /// The types of the group objects.
+    pub filter: Vec<Type>,
+}
+
+impl Action for Ungroup {
+    fn name(&self) -> &str {
+        "ungroup"
+    }
+
+    fn description(&self) -> &str {
+        "Move the children of the selected objects out and delete them"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let mut groups = vec![];
+        let mut children = vec![];
+        for (ty, obj) in selection(editor, &self.filter) {
+            let group_children = self.hierarchy.children(editor, ty, obj)?;
+            if group_children.is_empty() { continue; }
+            let parent = self.hierarchy.parent(editor, ty, obj)?;
+            for &(child_ty, child) in &group_children {
+                self.hierarchy.reparent(editor, child_ty, child, parent)?;
+            }
+            groups.push((ty, obj));
+            // A child is found twice when its group was ungrouped before the outer group.
+            for child in group_children {
+                if !children.contains(&child) { children.push(child); }
+            }
+        }
+        // Selected groups inside other selected groups are deleted as well.
+        children.retain(|child| !groups.contains(child));
+        while let Some((ty, obj)) = groups.pop() {
+            if let Some(moved) = self.hierarchy.delete(editor, ty, obj)? {
+                for item in groups.iter_mut().chain(children.iter_mut()) {
+                    if *item == (ty, moved) { item.1 = obj; }
+                }
+            }
+        }
+        let mut types = self.filter.clone();
+        for &(ty, _) in &children {
+            if !types.contains(&ty) { types.push(ty); }
+        }
+        select_hits(editor, &children, &types, SelectMode::Replace)
+    }
+}
+
+/// Adds the children of the selected objects to the selection.
+pub struct SelectChildren {
+    /// The hierarchy to follow.
+    pub hierarchy: Rc<dyn Hierarchy>,
+    /// Selects every descendant instead of only children.
+    pub recursive: bool,
+    /// The types which children are selected.
+    pub filter: Vec<Type>,
+}
+
+impl Action for SelectChildren {
+    fn name(&self) -> &str {
+        "select_children"
+    }
+
+    fn description(&self) -> &str {
+        "Select the children of the selected objects"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let mut hits = vec![];
+        for (ty, obj) in selection(editor, &self.filter) {
+            if self.recursive {
+                hits.extend(self.hierarchy.descendants(editor, ty, obj)?);
+            } else {
+                hits.extend(self.hierarchy.children(editor, ty, obj)?);
+            }
+        }
+        select_hits(editor, &hits, &[], SelectMode::Add)
+    }
+}
+
+/// Deletes the selected objects with their descendants.
+pub struct DeleteSubtree {
+    /// The hierarchy to follow.
+    pub hierarchy: Rc<dyn Hierarchy>,
+    /// The types to delete.
+    pub filter: Vec<Type>,
+}
+
+impl Action for DeleteSubtree {
+    fn name(&self) -> &str {
+        "delete_subtree"
+    }
+
+    fn description(&self) -> &str {
+        "Delete the selected objects and their descendants"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let roots = selection(editor, &self.filter);
+        delete_subtrees(&*self.hierarchy, editor, &roots)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const NODE: Type = Type("node");
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Node {
+        name: u32,
+        parent: Option<Object>,
+    }
+
+    fn node(name: u32, parent: Option<usize>) -> Node {
+        Node { name, parent: parent.map(Object) }
+    }
+
+    fn new_group() -> Box<dyn Any> {
+        Box::new(node(99, None))
+    }
+
+    fn parents() -> Rc<Parents> {
+        let mut parents = Parents::new();
+        parents.register::<Node>(NODE, NODE, |n| n.parent, |n| &mut n.parent);
+        Rc::new(parents)
+    }
+
+    fn editor(nodes: &[Node]) -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<Node>(NODE);
+        for n in nodes { editor.insert(NODE, n).unwrap(); }
+        editor
+    }
+
+    fn nodes(editor: &VecEditor) -> Vec<Node> {
+        editor.items::<Node>(NODE).unwrap().to_vec()
+    }
+
+    fn objs(objs: &[usize]) -> Vec<(Type, Object)> {
+        objs.iter().map(|&obj| (NODE, Object(obj))).collect()
+    }
+
+    #[test]
+    fn tree() {
+        // 0 has the children 1 and 2, and 1 has the child 3.
+        let mut editor = editor(&[node(0, None), node(1, Some(0)), node(2, Some(0)),
+            node(3, Some(1))]);
+        let parents = parents();
+        assert_eq!(parents.children(&editor, NODE, Object(0)).unwrap(), objs(&[1, 2]));
+        assert_eq!(parents.ancestors(&editor, NODE, Object(3)).unwrap(), objs(&[1, 0]));
+        assert_eq!(parents.descendants(&editor, NODE, Object(0)).unwrap(), objs(&[1, 3, 2]));
+        assert_eq!(parents.parent(&editor, NODE, Object(0)).unwrap(), None);
+
+        match parents.reparent(&mut editor, NODE, Object(0), Some((NODE, Object(3)))) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        parents.reparent(&mut editor, NODE, Object(3), Some((NODE, Object(2)))).unwrap();
+        assert_eq!(parents.children(&editor, NODE, Object(2)).unwrap(), objs(&[3]));
+        parents.reparent(&mut editor, NODE, Object(3), None).unwrap();
+        assert_eq!(parents.parent(&editor, NODE, Object(3)).unwrap(), None);
+    }
+
+    #[test]
+    fn delete() {
+        let mut editor = editor(&[node(0, None), node(1, Some(0)), node(2, None),
+            node(3, None), node(4, Some(3))]);
+        let parents = parents();
+        match parents.delete(&mut editor, NODE, Object(0)) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        // Deleting 2 moves 4 into its place.
+        assert_eq!(parents.delete(&mut editor, NODE, Object(2)).unwrap(), Some(Object(4)));
+        // Deleting 1 moves 3 into its place, so the child of 3 follows.
+        assert_eq!(parents.delete(&mut editor, NODE, Object(1)).unwrap(), Some(Object(3)));
+        assert_eq!(nodes(&editor), vec![node(0, None), node(3, None), node(4, Some(1))]);
+
+        parents.delete_subtree(&mut editor, NODE, Object(1)).unwrap();
+        assert_eq!(nodes(&editor), vec![node(0, None)]);
+    }
+
+    #[test]
+    fn group_topmost() {
+        let mut editor = editor(&[node(0, None), node(1, Some(0)), node(2, Some(0)),
+            node(3, Some(1))]);
+        editor.select_multiple(NODE, &[Object(1), Object(2), Object(3)]).unwrap();
+        let group = Group {
+            hierarchy: parents(),
+            group: NODE,
+            new_group,
+            filter: vec![NODE],
+        };
+        group.execute(&mut editor).unwrap();
+        assert_eq!(nodes(&editor), vec![node(0, None), node(1, Some(4)), node(2, Some(4)),
+            node(3, Some(1)), node(99, Some(0))]);
+        assert_eq!(editor.multiple_selected(NODE), vec![Object(4)]);
+    }
+
+    #[test]
+    fn ungroup() {
+        let mut editor = editor(&[node(0, None), node(1, Some(0)), node(2, Some(0)),
+            node(3, None)]);
+        editor.select_multiple(NODE, &[Object(0), Object(3)]).unwrap();
+        let ungroup = Ungroup { hierarchy: parents(), filter: vec![NODE] };
+        ungroup.execute(&mut editor).unwrap();
+        // The node without children is kept.
+        assert_eq!(nodes(&editor), vec![node(3, None), node(1, None), node(2, None)]);
+        let mut selected = editor.multiple_selected(NODE);
+        selected.sort_by_key(|obj| obj.0);
+        assert_eq!(selected, vec![Object(1), Object(2)]);
+    }
+
+    #[test]
+    fn ungroup_nested() {
+        // The group 0 has the group 1, which has 2. The root 3 is unrelated.
+        let tree = [node(0, None), node(1, Some(0)), node(2, Some(1)), node(3, None)];
+        for order in &[[0, 1], [1, 0]] {
+            let mut editor = editor(&tree);
+            editor.select_multiple(NODE, &[Object(order[0]), Object(order[1])]).unwrap();
+            let ungroup = Ungroup { hierarchy: parents(), filter: vec![NODE] };
+            ungroup.execute(&mut editor).unwrap();
+            let nodes = nodes(&editor);
+            assert_eq!(nodes.len(), 2);
+            assert!(nodes.iter().all(|n| n.parent.is_none()));
+            // Only the child is selected, not the deleted inner group or the unrelated root.
+            let child = nodes.iter().position(|n| n.name == 2).unwrap();
+            assert_eq!(editor.multiple_selected(NODE), vec![Object(child)]);
+        }
+    }
+}
@@ -0,0 +1,803 @@
+use std::any::Any;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use binary::{Reader, Writer};
+use document::{find_type, from_json, invalid, to_json};
+use json::{self, Json};
+use type_registry::no_values;
+use {
+    Bounds2, Editor, EditorError, EditorExt, Hit, Object, Plane, Type, TypeRegistry, Value,
+    View, ViewId,
+};
+
+const FORMAT: &str = "piston-editor-journal";
+const VERSION: i64 = 1;
+const MAGIC: &[u8] = b"PEDJ";
+
+const SELECT: u8 = 0;
+const SELECT_MULTIPLE: u8 = 1;
+const DESELECT_MULTIPLE: u8 = 2;
+const SELECT_NONE: u8 = 3;
+const INSERT: u8 = 4;
+const DELETE: u8 = 5;
+const UPDATE: u8 = 6;
+const REPLACE: u8 = 7;
+const NAVIGATE_TO: u8 = 8;
+
+/// An editor operation, as recorded by `Recording`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Operation {
+    /// Selected a single object.
+    Select(Type, Object),
+    /// Added objects to the selection.
+    SelectMultiple(Type, Vec<Object>),
+    /// Removed objects from the selection.
+    DeselectMultiple(Type, Vec<Object>),
+    /// Deselected everything of a type.
+    SelectNone(Type),
+    /// Inserted an object.
+    Insert {
+        /// The type of the object.
+        ty: Type,
+        /// The inserted object.
+        obj: Object,
+        /// The arguments, converted with `TypeRegistry::to_value`.
+        value: Value,
+    },
+    /// Deleted an object.
+    Delete {
+        /// The type of the object.
+        ty: Type,
+        /// The deleted object.
+        obj: Object,
+        /// The object moved into the place of the deleted one by swap-remove.
+        moved: Option<Object>,
+    },
+    /// Updated an object.
+    Update {
+        /// The type of the object.
+        ty: Type,
+        /// The updated object.
+        obj: Object,
+        /// The arguments, converted with `TypeRegistry::to_value`.
+        value: Value,
+    },
+    /// Replaced an object with another.
+    Replace {
+        /// The type of the objects.
+        ty: Type,
+        /// The replaced object.
+        from: Object,
+        /// The object it was replaced with.
+        to: Object,
+    },
+    /// Navigated to an object.
+    NavigateTo(Type, Object),
+}
+
+impl Operation {
+    /// Gets the type the operation is about.
+    pub fn ty(&self) -> Type {
+        match *self {
+            Operation::Select(ty, _) |
+            Operation::SelectMultiple(ty, _) |
+            Operation::DeselectMultiple(ty, _) |
+            Operation::SelectNone(ty) |
+            Operation::Insert { ty, .. } |
+            Operation::Delete { ty, .. } |
+            Operation::Update { ty, .. } |
+            Operation::Replace { ty, .. } |
+            Operation::NavigateTo(ty, _) => ty,
+        }
+    }
+
+    /// Applies the operation, changing recorded objects to objects of the editor.
+    fn apply(&self, editor: &mut dyn Editor, types: &TypeRegistry, ids: &mut Ids)
+    -> Result<(), EditorError> {
+        let ty = self.ty();
+        let map = |ids: &Ids, objs: &[Object]| -> Vec<Object> {
+            objs.iter().map(|&obj| ids.get(ty, obj)).collect()
+        };
+        let value = |ids: &Ids, value: &Value| -> Result<Value, EditorError> {
+            let mut value = value.clone();
+            value.map_references(&mut |ty, obj| Ok(ids.get(ty, obj)))?;
+            Ok(value)
+        };
+        match *self {
+            Operation::Select(_, obj) => editor.select(ty, ids.get(ty, obj)),
+            Operation::SelectMultiple(_, ref objs) => editor.select_multiple(ty, &map(ids, objs)),
+            Operation::DeselectMultiple(_, ref objs) =>
+                editor.deselect_multiple(ty, &map(ids, objs)),
+            Operation::SelectNone(_) => editor.select_none(ty),
+            Operation::Insert { obj, value: ref val, .. } => {
+                let actual = editor.insert_value(types, ty, &value(ids, val)?)?;
+                ids.set(ty, obj, actual);
+                Ok(())
+            }
+            Operation::Delete { obj, moved, .. } => {
+                let actual = ids.get(ty, obj);
+                let actual_moved = editor.delete(ty, actual)?;
+                ids.deleted(ty, obj, moved, actual, actual_moved);
+                Ok(())
+            }
+            Operation::Update { obj, value: ref val, .. } =>
+                editor.update_value(types, ty, ids.get(ty, obj), &value(ids, val)?),
+            Operation::Replace { from, to, .. } =>
+                editor.replace(ty, ids.get(ty, from), ids.get(ty, to)),
+            Operation::NavigateTo(_, obj) => editor.navigate_to(ty, ids.get(ty, obj)),
+        }
+    }
+
+    fn to_json(&self) -> Json {
+        let objects = |objs: &[Object]| {
+            Json::Array(objs.iter().map(|obj| Json::Int(obj.0 as i64)).collect())
+        };
+        let (op, fields) = match *self {
+            Operation::Select(_, obj) => ("select", vec![("object", Json::Int(obj.0 as i64))]),
+            Operation::SelectMultiple(_, ref objs) =>
+                ("select_multiple", vec![("objects", objects(objs))]),
+            Operation::DeselectMultiple(_, ref objs) =>
+                ("deselect_multiple", vec![("objects", objects(objs))]),
+            Operation::SelectNone(_) => ("select_none", vec![]),
+            Operation::Insert { obj, ref value, .. } => ("insert", vec![
+                ("object", Json::Int(obj.0 as i64)),
+                ("value", to_json(value)),
+            ]),
+            Operation::Delete { obj, moved, .. } => ("delete", vec![
+                ("object", Json::Int(obj.0 as i64)),
+                ("moved", moved.map(|obj| Json::Int(obj.0 as i64)).unwrap_or(Json::Null)),
+            ]),
+            Operation::Update { obj, ref value, .. } => ("update", vec![
+                ("object", Json::Int(obj.0 as i64)),
+                ("value", to_json(value)),
+            ]),
+            Operation::Replace { from, to, .. } => ("replace", vec![
+                ("from", Json::Int(from.0 as i64)),
+                ("to", Json::Int(to.0 as i64)),
+            ]),
+            Operation::NavigateTo(_, obj) =>
+                ("navigate_to", vec![("object", Json::Int(obj.0 as i64))]),
+        };
+        let mut res = vec![
+            ("op".into(), Json::String(op.into())),
+            ("type".into(), Json::String(self.ty().0.into())),
+        ];
+        res.extend(fields.into_iter().map(|(key, json)| (key.into(), json)));
+        Json::Object(res)
+    }
+
+    fn from_json(json: &Json, types: &TypeRegistry) -> Result<Operation, EditorError> {
+        let fields = match *json {
+            Json::Object(ref fields) => fields,
+            _ => return Err(invalid("expected operation object")),
+        };
+        let field = |name: &str| {
+            fields.iter().find(|f| f.0 == name).map(|f| &f.1)
+                .ok_or_else(|| invalid(&format!("missing `{}`", name)))
+        };
+        let object = |name: &str| match *field(name)? {
+            Json::Int(id) if id >= 0 => Ok(Object(id as usize)),
+            _ => Err(invalid(&format!("expected object id for `{}`", name))),
+        };
+        let objects = || match *field("objects")? {
+            Json::Array(ref items) => items.iter().map(|item| match *item {
+                Json::Int(id) if id >= 0 => Ok(Object(id as usize)),
+                _ => Err(invalid("expected object id")),
+            }).collect::<Result<Vec<Object>, EditorError>>(),
+            _ => Err(invalid("expected array of object ids")),
+        };
+        let ty = match *field("type")? {
+            Json::String(ref name) => find_type(types, name)?,
+            _ => return Err(invalid("expected type name")),
+        };
+        let op = match *field("op")? {
+            Json::String(ref op) => op,
+            _ => return Err(invalid("expected operation name")),
+        };
+        Ok(match &op[..] {
+            "select" => Operation::Select(ty, object("object")?),
+            "select_multiple" => Operation::SelectMultiple(ty, objects()?),
+            "deselect_multiple" => Operation::DeselectMultiple(ty, objects()?),
+            "select_none" => Operation::SelectNone(ty),
+            "insert" => Operation::Insert {
+                ty,
+                obj: object("object")?,
+                value: from_json(field("value")?, types)?,
+            },
+            "delete" => Operation::Delete {
+                ty,
+                obj: object("object")?,
+                moved: match *field("moved")? {
+                    Json::Null => None,
+                    _ => Some(object("moved")?),
+                },
+            },
+            "update" => Operation::Update {
+                ty,
+                obj: object("object")?,
+                value: from_json(field("value")?, types)?,
+            },
+            "replace" => Operation::Replace { ty, from: object("from")?, to: object("to")? },
+            "navigate_to" => Operation::NavigateTo(ty, object("object")?),
+            _ => return Err(invalid(&format!("unknown operation `{}`", op))),
+        })
+    }
+
+    /// Writes the operation as a record of its own,
+    /// starting with the names of the types it refers to.
+    fn write(&self, w: &mut Writer) -> Result<(), EditorError> {
+        let mut types = vec![self.ty()];
+        if let Operation::Insert { ref value, .. } | Operation::Update { ref value, .. } = *self {
+            for (ty, _) in value.references() {
+                if !types.contains(&ty) { types.push(ty); }
+            }
+        }
+        w.varint(types.len() as u64);
+        for ty in &types { w.str(ty.0); }
+        let objects = |w: &mut Writer, objs: &[Object]| {
+            w.varint(objs.len() as u64);
+            for obj in objs { w.varint(obj.0 as u64); }
+        };
+        match *self {
+            Operation::Select(_, obj) => {
+                w.u8(SELECT);
+                w.varint(obj.0 as u64);
+            }
+            Operation::SelectMultiple(_, ref objs) => {
+                w.u8(SELECT_MULTIPLE);
+                objects(w, objs);
+            }
+            Operation::DeselectMultiple(_, ref objs) => {
+                w.u8(DESELECT_MULTIPLE);
+                objects(w, objs);
+            }
+            Operation::SelectNone(_) => w.u8(SELECT_NONE),
+            Operation::Insert { obj, ref value, .. } => {
+                w.u8(INSERT);
+                w.varint(obj.0 as u64);
+                w.value(value, &types)?;
+            }
+            Operation::Delete { obj, moved, .. } => {
+                w.u8(DELETE);
+                w.varint(obj.0 as u64);
+                // Zero means that no object was moved.
+                w.varint(moved.map(|obj| obj.0 as u64 + 1).unwrap_or(0));
+            }
+            Operation::Update { obj, ref value, .. } => {
+                w.u8(UPDATE);
+                w.varint(obj.0 as u64);
+                w.value(value, &types)?;
+            }
+            Operation::Replace { from, to, .. } => {
+                w.u8(REPLACE);
+                w.varint(from.0 as u64);
+                w.varint(to.0 as u64);
+            }
+            Operation::NavigateTo(_, obj) => {
+                w.u8(NAVIGATE_TO);
+                w.varint(obj.0 as u64);
+            }
+        }
+        Ok(())
+    }
+
+    fn read(r: &mut Reader, types: &TypeRegistry) -> Result<Operation, EditorError> {
+        let n = r.len()?;
+        let mut op_types = Vec::with_capacity(n);
+        for _ in 0..n {
+            let name = r.str()?;
+            op_types.push(find_type(types, &name)?);
+        }
+        let ty = match op_types.first() {
+            None => return Err(r.error("expected operation type")),
+            Some(&ty) => ty,
+        };
+        let object = |r: &mut Reader| -> Result<Object, EditorError> {
+            Ok(Object(r.varint()? as usize))
+        };
+        let objects = |r: &mut Reader| -> Result<Vec<Object>, EditorError> {
+            let n = r.len()?;
+            (0..n).map(|_| Ok(Object(r.varint()? as usize))).collect()
+        };
+        Ok(match r.u8()? {
+            SELECT => Operation::Select(ty, object(r)?),
+            SELECT_MULTIPLE => Operation::SelectMultiple(ty, objects(r)?),
+            DESELECT_MULTIPLE => Operation::DeselectMultiple(ty, objects(r)?),
+            SELECT_NONE => Operation::SelectNone(ty),
+            INSERT => Operation::Insert { ty, obj: object(r)?, value: r.value(&op_types)? },
+            DELETE => Operation::Delete {
+                ty,
+                obj: object(r)?,
+                moved: match r.varint()? {
+                    0 => None,
+                    x => Some(Object(x as usize - 1)),
+                },
+            },
+            UPDATE => Operation::Update { ty, obj: object(r)?, value: r.value(&op_types)? },
+            REPLACE => Operation::Replace { ty, from: object(r)?, to: object(r)? },
+            NAVIGATE_TO => Operation::NavigateTo(ty, object(r)?),
+            _ => return Err(r.error("unknown operation")),
+        })
+    }
+}
+
+/// Maps objects of a journal to objects of the editor it is replayed onto.
+///
+/// Objects are mapped to themselves until inserted or moved by swap-remove.
+#[derive(Default)]
+struct Ids {
+    forward: HashMap<(Type, Object), Object>,
+    backward: HashMap<(Type, Object), Object>,
+}
+
+impl Ids {
+    /// Gets the object of the editor.
+    fn get(&self, ty: Type, obj: Object) -> Object {
+        self.forward.get(&(ty, obj)).cloned().unwrap_or(obj)
+    }
+
+    /// Gets the object of the journal.
+    fn recorded(&self, ty: Type, obj: Object) -> Object {
+        self.backward.get(&(ty, obj)).cloned().unwrap_or(obj)
+    }
+
+    fn set(&mut self, ty: Type, recorded: Object, actual: Object) {
+        self.forward.insert((ty, recorded), actual);
+        self.backward.insert((ty, actual), recorded);
+    }
+
+    /// Updates the map after deleting an object both in the journal and the editor,
+    /// which might move different objects by swap-remove.
+    fn deleted(
+        &mut self,
+        ty: Type,
+        obj: Object,
+        moved: Option<Object>,
+        actual: Object,
+        actual_moved: Option<Object>
+    ) {
+        // The editor object of the object moved in the journal, and vice versa.
+        let moved_actual = moved.map(|obj| self.get(ty, obj));
+        let actual_moved_recorded = actual_moved.map(|obj| self.recorded(ty, obj));
+        for &obj in [Some(obj), moved, actual_moved_recorded].iter().flatten() {
+            self.forward.remove(&(ty, obj));
+        }
+        for &obj in [Some(actual), actual_moved, moved_actual].iter().flatten() {
+            self.backward.remove(&(ty, obj));
+        }
+        if let Some(a) = moved_actual {
+            let a = if Some(a) == actual_moved { actual } else { a };
+            self.set(ty, obj, a);
+        }
+        if let Some(r) = actual_moved_recorded {
+            let r = if Some(r) == moved { obj } else { r };
+            self.set(ty, r, actual);
+        }
+    }
+}
+
+/// A list of editor operations, recorded with `Recording`.
+///
+/// Replaying a journal gives macros and makes it possible to reproduce bugs.
+/// Objects inserted by the journal are mapped to the objects inserted when replaying,
+/// including references in values and objects moved by swap-remove.
+/// Other objects are referred to by their ids,
+/// so replay onto an editor in the state the recording started from,
+/// or one where the objects used have the same ids.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Journal {
+    /// The recorded operations, in order.
+    pub operations: Vec<Operation>,
+}
+
+impl Journal {
+    /// Creates a new empty journal.
+    pub fn new() -> Journal {
+        Journal::default()
+    }
+
+    /// Replays the operations onto an editor.
+    ///
+    /// Stops at the first failed operation.
+    /// Use `Transaction::run` to roll back on failure.
+    pub fn replay(&self, editor: &mut dyn Editor, types: &TypeRegistry)
+    -> Result<(), EditorError> {
+        let mut ids = Ids::default();
+        for op in &self.operations {
+            op.apply(editor, types, &mut ids)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the journal as JSON, with one object per operation.
+    pub fn to_json(&self) -> Result<String, EditorError> {
+        let json = Json::Object(vec![
+            ("format".into(), Json::String(FORMAT.into())),
+            ("version".into(), Json::Int(VERSION)),
+            ("operations".into(), Json::Array(self.operations.iter().map(|op| op.to_json())
+                .collect())),
+        ]);
+        let mut res = String::new();
+        json::write(&json, 0, &mut res)?;
+        res.push('\n');
+        Ok(res)
+    }
+
+    /// Reads a journal from JSON.
+    ///
+    /// Type names are looked up in the type registry.
+    pub fn from_json(text: &str, types: &TypeRegistry) -> Result<Journal, EditorError> {
+        let fields = match json::parse(text)? {
+            Json::Object(fields) => fields,
+            _ => return Err(invalid("expected object")),
+        };
+        let field = |name: &str| fields.iter().find(|f| f.0 == name).map(|f| &f.1);
+        if field("format") != Some(&Json::String(FORMAT.into())) {
+            return Err(invalid("unknown format"));
+        }
+        match field("version") {
+            Some(&Json::Int(version)) if version <= VERSION => {}
+            _ => return Err(invalid("unsupported version")),
+        }
+        let operations = match field("operations") {
+            Some(Json::Array(operations)) => operations,
+            _ => return Err(invalid("expected `operations` array")),
+        };
+        Ok(Journal {
+            operations: operations.iter()
+                .map(|json| Operation::from_json(json, types))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Writes the journal in a compact binary format.
+    ///
+    /// Every operation is a length-prefixed record that names its own types,
+    /// such that operations can be appended to existing data.
+    pub fn to_binary(&self) -> Result<Vec<u8>, EditorError> {
+        let mut w = Writer::new();
+        write_header(&mut w);
+        for op in &self.operations {
+            write_record(op, &mut w)?;
+        }
+        Ok(w.bytes)
+    }
+
+    /// Reads a journal from the binary format.
+    ///
+    /// Type names are looked up in the type registry.
+    pub fn from_binary(bytes: &[u8], types: &TypeRegistry) -> Result<Journal, EditorError> {
+        read_binary(bytes, types, false)
+    }
+}
+
+/// Reads a journal from the binary format.
+///
+/// When `truncated` is `true`, an incomplete last record is ignored,
+/// for example after a crash while appending.
+pub(crate) fn read_binary(bytes: &[u8], types: &TypeRegistry, truncated: bool)
+-> Result<Journal, EditorError> {
+    let mut r = Reader::new(bytes);
+    if r.take(MAGIC.len()).ok() != Some(MAGIC) { return Err(r.error("unknown format")); }
+    if r.varint()? > VERSION as u64 { return Err(r.error("unsupported version")); }
+    let mut journal = Journal::new();
+    while !r.is_at_end() {
+        let n = match r.len() {
+            Err(_) if truncated => break,
+            n => n?,
+        };
+        let mut record = Reader::new(r.take(n)?);
+        journal.operations.push(Operation::read(&mut record, types)?);
+        if !record.is_at_end() { return Err(record.error("expected end of operation")); }
+    }
+    Ok(journal)
+}
+
+/// Writes the start of a binary journal, before any records.
+pub(crate) fn write_header(w: &mut Writer) {
+    w.bytes.extend_from_slice(MAGIC);
+    w.varint(VERSION as u64);
+}
+
+/// Writes an operation as a length-prefixed record.
+pub(crate) fn write_record(op: &Operation, w: &mut Writer) -> Result<(), EditorError> {
+    let mut record = Writer::new();
+    op.write(&mut record)?;
+    w.varint(record.bytes.len() as u64);
+    w.bytes.extend_from_slice(&record.bytes);
+    Ok(())
+}
+
+/// Wraps an editor and records changes and selection to a journal.
+///
+/// Arguments of `insert` and `update` are stored as values,
+/// so every inserted or updated type needs value conversions,
+/// see `SchemaBuilder::values`.
+/// While recording, every operation on a type without value conversions is refused,
+/// including deletes and selection changes,
+/// such that the journal always replays to the same state.
+/// Failed operations are not recorded.
+pub struct Recording<E: Editor> {
+    editor: E,
+    types: Rc<TypeRegistry>,
+    journal: Journal,
+    recording: bool,
+}
+
+impl<E: Editor> Recording<E> {
+    /// Creates a new recording wrapping an editor, starting to record.
+    pub fn new(editor: E, types: Rc<TypeRegistry>) -> Recording<E> {
+        Recording {
+            editor,
+            types,
+            journal: Journal::new(),
+            recording: true,
+        }
+    }
+
+    /// Gets a reference to the wrapped editor.
+    pub fn get_ref(&self) -> &E {
+        &self.editor
+    }
+
+    /// Returns the wrapped editor, dropping the journal.
+    pub fn into_inner(self) -> E {
+        self.editor
+    }
+
+    /// Gets the recorded operations.
+    pub fn journal(&self) -> &Journal {
+        &self.journal
+    }
+
+    /// Takes the recorded operations, leaving an empty journal.
+    pub fn take_journal(&mut self) -> Journal {
+        ::std::mem::take(&mut self.journal)
+    }
+
+    /// Returns `true` if operations are recorded.
+    pub fn is_recording(&self) -> bool {
+        self.recording
+    }
+
+    /// Pauses or resumes recording.
+    ///
+    /// Operations made while paused are not recorded,
+    /// so later operations might not replay.
+    pub fn set_recording(&mut self, recording: bool) {
+        self.recording = recording;
+    }
+
+    fn push(&mut self, op: Operation) {
+        if self.recording { self.journal.operations.push(op); }
+    }
+
+    /// Refuses types without value conversions while recording.
+    fn check(&self, ty: Type) -> Result<(), EditorError> {
+        if !self.recording || self.types.schema(ty)?.has_values() { Ok(()) }
+        else { Err(no_values(ty)) }
+    }
+
+    fn to_value(&self, ty: Type, args: &dyn Any) -> Result<Option<Value>, EditorError> {
+        if self.recording { self.types.to_value(ty, args).map(Some) } else { Ok(None) }
+    }
+}
+
+impl<E: Editor> Editor for Recording<E> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.editor.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.editor.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.editor.hit_2d(pos)
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.editor.hit_rect_2d(rect)
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.editor.hit_polygon_2d(points)
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.editor.hit_3d(pos)
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.editor.hit_frustum(planes)
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.editor.hit_ray(origin, direction)
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.select(ty, obj)?;
+        self.push(Operation::Select(ty, obj));
+        Ok(())
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.select_multiple(ty, objs)?;
+        self.push(Operation::SelectMultiple(ty, objs.to_vec()));
+        Ok(())
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.deselect_multiple(ty, objs)?;
+        self.push(Operation::DeselectMultiple(ty, objs.to_vec()));
+        Ok(())
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.select_none(ty)?;
+        self.push(Operation::SelectNone(ty));
+        Ok(())
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let value = self.to_value(ty, args)?;
+        let obj = self.editor.insert(ty, args)?;
+        if let Some(value) = value { self.push(Operation::Insert { ty, obj, value }); }
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        self.check(ty)?;
+        let moved = self.editor.delete(ty, obj)?;
+        self.push(Operation::Delete { ty, obj, moved });
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        let value = self.to_value(ty, args)?;
+        self.editor.update(ty, obj, args)?;
+        if let Some(value) = value { self.push(Operation::Update { ty, obj, value }); }
+        Ok(())
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.replace(ty, from, to)?;
+        self.push(Operation::Replace { ty, from, to });
+        Ok(())
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.editor.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.editor.visible(ty)
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.editor.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.editor.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.editor.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.check(ty)?;
+        self.editor.navigate_to(ty, obj)?;
+        self.push(Operation::NavigateTo(ty, obj));
+        Ok(())
+    }
+
+    fn refresh_views(&mut self) {
+        self.editor.refresh_views()
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.editor.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.editor.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.editor.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.editor.set_active_view(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+    const TEMP: Type = Type("temp");
+
+    fn types() -> Rc<TypeRegistry> {
+        let mut types = TypeRegistry::new();
+        types.register::<[f64; 2]>(POINT).values();
+        types.register::<u32>(TEMP);
+        Rc::new(types)
+    }
+
+    fn editor(n: usize) -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<u32>(TEMP);
+        for i in 0..n { editor.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        editor.insert(TEMP, &0u32).unwrap();
+        editor
+    }
+
+    fn points(editor: &VecEditor) -> Vec<[f64; 2]> {
+        editor.items::<[f64; 2]>(POINT).unwrap().to_vec()
+    }
+
+    fn record(types: &Rc<TypeRegistry>) -> (Journal, VecEditor) {
+        let mut rec = Recording::new(editor(1), types.clone());
+        let a = rec.insert(POINT, &[5.0, 5.0]).unwrap();
+        let b = rec.insert(POINT, &[6.0, 6.0]).unwrap();
+        rec.update(POINT, a, &[7.0, 7.0]).unwrap();
+        rec.select_multiple(POINT, &[a, b]).unwrap();
+        rec.delete(POINT, Object(0)).unwrap();
+        assert!(rec.update(POINT, Object(9), &[0.0, 0.0]).is_err());
+        (rec.take_journal(), rec.into_inner())
+    }
+
+    #[test]
+    fn replay() {
+        let types = types();
+        let (journal, recorded) = record(&types);
+        assert_eq!(journal.operations.len(), 5);
+        let mut editor = editor(1);
+        journal.replay(&mut editor, &types).unwrap();
+        assert_eq!(points(&editor), points(&recorded));
+        assert_eq!(editor.multiple_selected(POINT), recorded.multiple_selected(POINT));
+    }
+
+    #[test]
+    fn json_and_binary() {
+        let types = types();
+        let (journal, _) = record(&types);
+        assert_eq!(Journal::from_json(&journal.to_json().unwrap(), &types).unwrap(), journal);
+        assert_eq!(Journal::from_binary(&journal.to_binary().unwrap(), &types).unwrap(), journal);
+    }
+
+    #[test]
+    fn refuse_types_without_values() {
+        let mut rec = Recording::new(editor(1), types());
+        match rec.select(TEMP, Object(0)) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        assert!(rec.insert(TEMP, &1u32).is_err());
+        assert!(rec.select_multiple(TEMP, &[Object(0)]).is_err());
+        assert!(rec.select_none(TEMP).is_err());
+        assert!(rec.replace(TEMP, Object(0), Object(0)).is_err());
+        assert!(rec.navigate_to(TEMP, Object(0)).is_err());
+        assert!(rec.delete(TEMP, Object(0)).is_err());
+        assert_eq!(rec.all(TEMP), vec![Object(0)]);
+        assert_eq!(rec.selected(TEMP), None);
+        assert!(rec.journal().operations.is_empty());
+
+        rec.set_recording(false);
+        rec.delete(TEMP, Object(0)).unwrap();
+        assert!(rec.journal().operations.is_empty());
+    }
+}
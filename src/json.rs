@@ -0,0 +1,377 @@
+//! A minimal JSON reader and writer used by documents.
+
+use value::MAX_DEPTH;
+use EditorError;
+
+/// A JSON value.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Json {
+    Null,
+    Bool(bool),
+    Int(i64),
+    /// An integer above `i64::MAX`.
+    UInt(u64),
+    Float(f64),
+    /// A float written with the shortest representation of `f32`.
+    Float32(f32),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    fn is_scalar(&self) -> bool {
+        !matches!(*self, Json::Array(_) | Json::Object(_))
+    }
+
+    /// Returns `true` if the value is short enough to write on a single line.
+    fn is_short(&self) -> bool {
+        match *self {
+            Json::Array(ref items) => items.iter().all(|item| item.is_scalar()),
+            Json::Object(ref fields) => fields.len() == 1 && fields[0].1.is_short(),
+            _ => true,
+        }
+    }
+}
+
+/// Writes JSON with one item per line, such that documents diff well.
+///
+/// Arrays of scalars and objects with a single short field
+/// are written on a single line.
+pub(crate) fn write(json: &Json, indent: usize, out: &mut String) -> Result<(), EditorError> {
+    match *json {
+        Json::Null => out.push_str("null"),
+        Json::Bool(x) => out.push_str(if x { "true" } else { "false" }),
+        Json::Int(x) => out.push_str(&x.to_string()),
+        Json::UInt(x) => out.push_str(&x.to_string()),
+        Json::Float(x) => {
+            if !x.is_finite() { return Err(not_finite(x)); }
+            out.push_str(&format!("{:?}", x));
+        }
+        Json::Float32(x) => {
+            if !x.is_finite() { return Err(not_finite(x as f64)); }
+            out.push_str(&format!("{:?}", x));
+        }
+        Json::String(ref x) => write_string(x, out),
+        Json::Array(ref items) => {
+            if json.is_short() {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { out.push_str(", "); }
+                    write(item, indent, out)?;
+                }
+                out.push(']');
+            } else {
+                out.push('[');
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { out.push(','); }
+                    newline(indent + 1, out);
+                    write(item, indent + 1, out)?;
+                }
+                newline(indent, out);
+                out.push(']');
+            }
+        }
+        Json::Object(ref fields) => {
+            if fields.is_empty() {
+                out.push_str("{}");
+                return Ok(());
+            }
+            if json.is_short() {
+                out.push('{');
+                write_string(&fields[0].0, out);
+                out.push_str(": ");
+                write(&fields[0].1, indent, out)?;
+                out.push('}');
+                return Ok(());
+            }
+            out.push('{');
+            for (i, (key, val)) in fields.iter().enumerate() {
+                if i > 0 { out.push(','); }
+                newline(indent + 1, out);
+                write_string(key, out);
+                out.push_str(": ");
+                write(val, indent + 1, out)?;
+            }
+            newline(indent, out);
+            out.push('}');
+        }
+    }
+    Ok(())
+}
+
+fn not_finite(x: f64) -> EditorError {
+    EditorError::InvalidData(format!("`{}` can not be written to JSON", x))
+}
+
+fn newline(indent: usize, out: &mut String) {
+    out.push('\n');
+    for _ in 0..indent { out.push_str("  "); }
+}
+
+fn write_string(x: &str, out: &mut String) {
+    out.push('"');
+    for c in x.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Reads JSON text.
+pub(crate) fn parse(text: &str) -> Result<Json, EditorError> {
+    let mut parser = Parser { chars: text.chars().collect(), pos: 0, depth: 0 };
+    let json = parser.value()?;
+    parser.whitespace();
+    if parser.pos < parser.chars.len() {
+        return Err(parser.error("expected end of text"));
+    }
+    Ok(json)
+}
+
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, msg: &str) -> EditorError {
+        let line = self.chars[..self.pos.min(self.chars.len())].iter()
+            .filter(|&&c| c == '\n').count() + 1;
+        EditorError::InvalidData(format!("invalid JSON at line {}: {}", line, msg))
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).cloned()
+    }
+
+    fn whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() { break; }
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), EditorError> {
+        self.whitespace();
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(&format!("expected `{}`", c)))
+        }
+    }
+
+    fn keyword(&mut self, word: &str, json: Json) -> Result<Json, EditorError> {
+        for c in word.chars() {
+            if self.peek() != Some(c) { return Err(self.error("unexpected character")); }
+            self.pos += 1;
+        }
+        Ok(json)
+    }
+
+    fn value(&mut self) -> Result<Json, EditorError> {
+        if self.depth >= MAX_DEPTH { return Err(self.error("nested too deeply")); }
+        self.depth += 1;
+        let res = self.nested_value();
+        self.depth -= 1;
+        res
+    }
+
+    fn nested_value(&mut self) -> Result<Json, EditorError> {
+        self.whitespace();
+        match self.peek() {
+            None => Err(self.error("unexpected end of text")),
+            Some('n') => self.keyword("null", Json::Null),
+            Some('t') => self.keyword("true", Json::Bool(true)),
+            Some('f') => self.keyword("false", Json::Bool(false)),
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('[') => {
+                self.pos += 1;
+                let mut items = vec![];
+                self.whitespace();
+                if self.peek() == Some(']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    self.whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some(']') => {
+                            self.pos += 1;
+                            return Ok(Json::Array(items));
+                        }
+                        _ => return Err(self.error("expected `,` or `]`")),
+                    }
+                }
+            }
+            Some('{') => {
+                self.pos += 1;
+                let mut fields = vec![];
+                self.whitespace();
+                if self.peek() == Some('}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.expect(':')?;
+                    fields.push((key, self.value()?));
+                    self.whitespace();
+                    match self.peek() {
+                        Some(',') => self.pos += 1,
+                        Some('}') => {
+                            self.pos += 1;
+                            return Ok(Json::Object(fields));
+                        }
+                        _ => return Err(self.error("expected `,` or `}`")),
+                    }
+                }
+            }
+            Some(c) if c == '-' || c.is_ascii_digit() => self.number(),
+            Some(_) => Err(self.error("unexpected character")),
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, EditorError> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_ascii_digit() || c == '-' || c == '+' || c == '.' || c == 'e' || c == 'E') {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text: String = self.chars[start..self.pos].iter().collect();
+        if text.contains(['.', 'e', 'E']) {
+            text.parse().map(Json::Float).map_err(|_| self.error("invalid number"))
+        } else {
+            text.parse().map(Json::Int)
+                .or_else(|_| text.parse().map(Json::UInt))
+                .map_err(|_| self.error("invalid integer"))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, EditorError> {
+        if self.peek() != Some('"') { return Err(self.error("expected string")); }
+        self.pos += 1;
+        let mut res = String::new();
+        loop {
+            let c = match self.peek() {
+                None => return Err(self.error("unterminated string")),
+                Some(c) => c,
+            };
+            self.pos += 1;
+            match c {
+                '"' => return Ok(res),
+                '\\' => {
+                    let c = match self.peek() {
+                        None => return Err(self.error("unterminated string")),
+                        Some(c) => c,
+                    };
+                    self.pos += 1;
+                    match c {
+                        '"' => res.push('"'),
+                        '\\' => res.push('\\'),
+                        '/' => res.push('/'),
+                        'b' => res.push('\u{8}'),
+                        'f' => res.push('\u{c}'),
+                        'n' => res.push('\n'),
+                        'r' => res.push('\r'),
+                        't' => res.push('\t'),
+                        'u' => {
+                            let mut code = self.hex4()?;
+                            if (0xd800..0xdc00).contains(&code) {
+                                // Surrogate pair.
+                                if self.peek() != Some('\\') { return Err(self.error("invalid surrogate")); }
+                                self.pos += 1;
+                                if self.peek() != Some('u') { return Err(self.error("invalid surrogate")); }
+                                self.pos += 1;
+                                let low = self.hex4()?;
+                                if !(0xdc00..0xe000).contains(&low) {
+                                    return Err(self.error("invalid surrogate"));
+                                }
+                                code = 0x10000 + ((code - 0xd800) << 10) + (low - 0xdc00);
+                            }
+                            match ::std::char::from_u32(code) {
+                                None => return Err(self.error("invalid unicode escape")),
+                                Some(c) => res.push(c),
+                            }
+                        }
+                        _ => return Err(self.error("invalid escape")),
+                    }
+                }
+                c => res.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> Result<u32, EditorError> {
+        if self.pos + 4 > self.chars.len() { return Err(self.error("invalid unicode escape")); }
+        let text: String = self.chars[self.pos..self.pos + 4].iter().collect();
+        self.pos += 4;
+        u32::from_str_radix(&text, 16).map_err(|_| self.error("invalid unicode escape"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn nested(n: usize) -> String {
+        format!("{}0{}", "[".repeat(n), "]".repeat(n))
+    }
+
+    #[test]
+    fn round_trip() {
+        let text = "{\"a\": [1, 2.5, \"x\\n\\u00e9\"], \"b\": {\"c\": null}, \"d\": true, \
+            \"e\": [-9223372036854775808, 18446744073709551615]}";
+        let json = parse(text).unwrap();
+        let mut out = String::new();
+        write(&json, 0, &mut out).unwrap();
+        assert_eq!(parse(&out).unwrap(), json);
+        match json {
+            Json::Object(ref fields) => {
+                assert_eq!(fields[0].1, Json::Array(vec![
+                    Json::Int(1), Json::Float(2.5), Json::String("x\né".into()),
+                ]));
+                let ints = Json::Array(vec![Json::Int(i64::MIN), Json::UInt(u64::MAX)]);
+                assert_eq!(fields[3].1, ints);
+            }
+            _ => panic!("expected object"),
+        }
+        assert!(parse("18446744073709551616").is_err());
+    }
+
+    #[test]
+    fn errors() {
+        match parse("{\n\"a\": [1,\n}") {
+            Err(EditorError::InvalidData(msg)) => assert!(msg.contains("line 3"), "{}", msg),
+            _ => panic!("expected invalid data"),
+        }
+        assert!(parse("[1] 2").is_err());
+        assert!(parse("\"open").is_err());
+        let mut out = String::new();
+        assert!(write(&Json::Float(f64::NAN), 0, &mut out).is_err());
+    }
+
+    #[test]
+    fn depth_limit() {
+        assert!(parse(&nested(MAX_DEPTH - 1)).is_ok());
+        assert!(parse(&nested(MAX_DEPTH)).is_err());
+        match parse(&"[".repeat(1_000_000)) {
+            Err(EditorError::InvalidData(msg)) => assert!(msg.contains("nested too deeply")),
+            _ => panic!("expected invalid data"),
+        }
+    }
+}
@@ -0,0 +1,458 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use {Bounds2, Editor, EditorError, Hit, Object, Plane, Type, View, ViewId};
+
+/// The name of the layer that always exists.
+pub const DEFAULT_LAYER: &str = "default";
+
+/// A named layer and its state.
+struct Layer {
+    name: String,
+    hidden: bool,
+    locked: bool,
+    solo: bool,
+}
+
+/// Wraps an editor and assigns objects to named layers.
+///
+/// Every object belongs to one layer, initially `DEFAULT_LAYER`.
+/// Inserted objects go to the active layer.
+///
+/// A layer can be hidden, locked or soloed:
+///
+/// - When any layer is soloed, only objects on soloed layers are shown,
+///   otherwise only objects on layers that are not hidden.
+///   `visible` leaves out objects that are not shown.
+/// - Objects on locked layers can not be updated, replaced or deleted,
+///   which fails with `EditorError::Locked`.
+///
+/// The `hit_*` methods only return objects that are shown and not locked,
+/// such that actions selecting by hits can not pick them.
+///
+/// Layer assignments follow swap-remove moves, but are not part of undo history.
+/// Undoing a delete re-inserts the object on the active layer.
+pub struct Layers<E: Editor> {
+    editor: E,
+    layers: Vec<Layer>,
+    active: usize,
+    // Objects not in the map are on the default layer, which has index 0.
+    assigned: HashMap<(Type, Object), usize>,
+}
+
+impl<E: Editor> Layers<E> {
+    /// Creates new layers wrapping an editor, with every object on the default layer.
+    pub fn new(editor: E) -> Layers<E> {
+        Layers {
+            editor,
+            layers: vec![Layer {
+                name: DEFAULT_LAYER.into(),
+                hidden: false,
+                locked: false,
+                solo: false,
+            }],
+            active: 0,
+            assigned: HashMap::new(),
+        }
+    }
+
+    /// Gets a reference to the wrapped editor.
+    pub fn get_ref(&self) -> &E {
+        &self.editor
+    }
+
+    /// Returns the wrapped editor, dropping layer assignments.
+    pub fn into_inner(self) -> E {
+        self.editor
+    }
+
+    /// Gets the names of the layers, in the order they were added.
+    pub fn layers(&self) -> Vec<&str> {
+        self.layers.iter().map(|layer| &*layer.name).collect()
+    }
+
+    /// Adds a new layer.
+    pub fn add_layer(&mut self, name: &str) -> Result<(), EditorError> {
+        if self.find(name).is_ok() {
+            return Err(EditorError::ConstraintViolation(format!(
+                "layer `{}` already exists", name)));
+        }
+        self.layers.push(Layer {
+            name: name.into(),
+            hidden: false,
+            locked: false,
+            solo: false,
+        });
+        Ok(())
+    }
+
+    /// Removes a layer, moving its objects to the default layer.
+    ///
+    /// If the layer was active, the default layer becomes active.
+    /// The default layer can not be removed.
+    pub fn remove_layer(&mut self, name: &str) -> Result<(), EditorError> {
+        let ind = self.find(name)?;
+        if ind == 0 {
+            return Err(EditorError::ConstraintViolation(
+                "the default layer can not be removed".into()));
+        }
+        self.layers.remove(ind);
+        self.assigned.retain(|_, layer| *layer != ind);
+        for layer in self.assigned.values_mut() {
+            if *layer > ind { *layer -= 1; }
+        }
+        if self.active == ind { self.active = 0; }
+        else if self.active > ind { self.active -= 1; }
+        Ok(())
+    }
+
+    /// Gets the layer inserted objects go to.
+    pub fn active_layer(&self) -> &str {
+        &self.layers[self.active].name
+    }
+
+    /// Sets the layer inserted objects go to.
+    pub fn set_active_layer(&mut self, name: &str) -> Result<(), EditorError> {
+        self.active = self.find(name)?;
+        Ok(())
+    }
+
+    /// Gets the layer of an object.
+    pub fn layer(&self, ty: Type, obj: Object) -> &str {
+        &self.layers[self.index(ty, obj)].name
+    }
+
+    /// Moves an object to a layer.
+    ///
+    /// Moving an object from or to a locked layer fails with `EditorError::Locked`.
+    pub fn set_layer(&mut self, ty: Type, obj: Object, name: &str) -> Result<(), EditorError> {
+        let ind = self.find(name)?;
+        if !self.editor.all(ty).contains(&obj) { return Err(EditorError::StaleObject(ty, obj)); }
+        if self.layers[ind].locked { return Err(EditorError::Locked(ty, obj)); }
+        self.check_unlocked(ty, obj)?;
+        if ind == 0 { self.assigned.remove(&(ty, obj)); }
+        else { self.assigned.insert((ty, obj), ind); }
+        Ok(())
+    }
+
+    /// Gets the objects of a type on a layer.
+    pub fn objects(&self, ty: Type, name: &str) -> Result<Vec<Object>, EditorError> {
+        let ind = self.find(name)?;
+        Ok(self.editor.all(ty).into_iter().filter(|&obj| self.index(ty, obj) == ind).collect())
+    }
+
+    /// Gets whether a layer is hidden.
+    pub fn is_hidden(&self, name: &str) -> Result<bool, EditorError> {
+        Ok(self.layers[self.find(name)?].hidden)
+    }
+
+    /// Hides or shows a layer.
+    pub fn set_hidden(&mut self, name: &str, hidden: bool) -> Result<(), EditorError> {
+        let ind = self.find(name)?;
+        self.layers[ind].hidden = hidden;
+        Ok(())
+    }
+
+    /// Gets whether a layer is locked.
+    pub fn is_locked(&self, name: &str) -> Result<bool, EditorError> {
+        Ok(self.layers[self.find(name)?].locked)
+    }
+
+    /// Locks or unlocks a layer.
+    pub fn set_locked(&mut self, name: &str, locked: bool) -> Result<(), EditorError> {
+        let ind = self.find(name)?;
+        self.layers[ind].locked = locked;
+        Ok(())
+    }
+
+    /// Gets whether a layer is soloed.
+    pub fn is_solo(&self, name: &str) -> Result<bool, EditorError> {
+        Ok(self.layers[self.find(name)?].solo)
+    }
+
+    /// Solos a layer or ends soloing it.
+    ///
+    /// Several layers can be soloed at the same time.
+    pub fn set_solo(&mut self, name: &str, solo: bool) -> Result<(), EditorError> {
+        let ind = self.find(name)?;
+        self.layers[ind].solo = solo;
+        Ok(())
+    }
+
+    /// Gets whether objects on a layer are shown,
+    /// taking hidden and soloed layers into account.
+    pub fn is_shown(&self, name: &str) -> Result<bool, EditorError> {
+        Ok(self.shown(self.find(name)?))
+    }
+
+    fn find(&self, name: &str) -> Result<usize, EditorError> {
+        self.layers.iter().position(|layer| layer.name == name).ok_or_else(||
+            EditorError::ConstraintViolation(format!("layer `{}` does not exist", name)))
+    }
+
+    fn index(&self, ty: Type, obj: Object) -> usize {
+        self.assigned.get(&(ty, obj)).cloned().unwrap_or(0)
+    }
+
+    fn shown(&self, ind: usize) -> bool {
+        if self.layers.iter().any(|layer| layer.solo) { self.layers[ind].solo }
+        else { !self.layers[ind].hidden }
+    }
+
+    fn pickable(&self, ty: Type, obj: Object) -> bool {
+        let ind = self.index(ty, obj);
+        self.shown(ind) && !self.layers[ind].locked
+    }
+
+    fn check_unlocked(&self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        if self.layers[self.index(ty, obj)].locked { Err(EditorError::Locked(ty, obj)) }
+        else { Ok(()) }
+    }
+
+    fn filter_hits(&self, hits: Vec<(Type, Object)>) -> Vec<(Type, Object)> {
+        hits.into_iter().filter(|&(ty, obj)| self.pickable(ty, obj)).collect()
+    }
+}
+
+impl<E: Editor> Editor for Layers<E> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.editor.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.editor.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.filter_hits(self.editor.hit_2d(pos))
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.filter_hits(self.editor.hit_rect_2d(rect))
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.filter_hits(self.editor.hit_polygon_2d(points))
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.filter_hits(self.editor.hit_3d(pos))
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.filter_hits(self.editor.hit_frustum(planes))
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.editor.hit_ray(origin, direction).into_iter()
+            .filter(|hit| self.pickable(hit.ty, hit.obj))
+            .collect()
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.editor.select(ty, obj)
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.editor.select_multiple(ty, objs)
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.editor.deselect_multiple(ty, objs)
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.editor.select_none(ty)
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let obj = self.editor.insert(ty, args)?;
+        // The object may reuse the id of a deleted one.
+        if self.active == 0 { self.assigned.remove(&(ty, obj)); }
+        else { self.assigned.insert((ty, obj), self.active); }
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        self.check_unlocked(ty, obj)?;
+        let moved = self.editor.delete(ty, obj)?;
+        self.assigned.remove(&(ty, obj));
+        if let Some(moved) = moved {
+            if let Some(layer) = self.assigned.remove(&(ty, moved)) {
+                self.assigned.insert((ty, obj), layer);
+            }
+        }
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        self.check_unlocked(ty, obj)?;
+        self.editor.update(ty, obj, args)
+    }
+
+    /// Replaces an object with another, keeping the layer of the replaced object.
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        self.check_unlocked(ty, from)?;
+        self.editor.replace(ty, from, to)
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.editor.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.editor.visible(ty).into_iter()
+            .filter(|&obj| self.shown(self.index(ty, obj)))
+            .collect()
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.editor.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.editor.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.editor.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.editor.navigate_to(ty, obj)
+    }
+
+    fn refresh_views(&mut self) {
+        self.editor.refresh_views()
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.editor.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.editor.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.editor.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.editor.set_active_view(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    fn layers() -> Layers<VecEditor> {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.set_bounds_2d::<[f64; 2]>(POINT, |p| Bounds2::point(*p)).unwrap();
+        let mut layers = Layers::new(editor);
+        layers.add_layer("a").unwrap();
+        layers.add_layer("b").unwrap();
+        layers.insert(POINT, &[0.0, 0.0]).unwrap();
+        layers.set_active_layer("a").unwrap();
+        layers.insert(POINT, &[1.0, 0.0]).unwrap();
+        layers.set_active_layer("b").unwrap();
+        layers.insert(POINT, &[2.0, 0.0]).unwrap();
+        layers
+    }
+
+    fn hits(layers: &Layers<VecEditor>) -> Vec<Object> {
+        let mut hits: Vec<Object> = layers.hit_rect_2d(Bounds2::new([-1.0, -1.0], [3.0, 1.0]))
+            .into_iter().map(|(_, obj)| obj).collect();
+        hits.sort_by_key(|obj| obj.0);
+        hits
+    }
+
+    fn expect_locked<T>(res: Result<T, EditorError>) {
+        match res {
+            Err(EditorError::Locked(..)) => {}
+            _ => panic!("expected a locked error"),
+        }
+    }
+
+    #[test]
+    fn add_and_remove() {
+        let mut layers = layers();
+        assert_eq!(layers.layers(), vec![DEFAULT_LAYER, "a", "b"]);
+        assert!(layers.add_layer("a").is_err());
+        assert!(layers.remove_layer(DEFAULT_LAYER).is_err());
+        assert!(layers.set_active_layer("c").is_err());
+        assert_eq!(layers.layer(POINT, Object(0)), DEFAULT_LAYER);
+        assert_eq!(layers.layer(POINT, Object(2)), "b");
+        assert_eq!(layers.objects(POINT, "a").unwrap(), vec![Object(1)]);
+
+        layers.remove_layer("a").unwrap();
+        assert_eq!(layers.layers(), vec![DEFAULT_LAYER, "b"]);
+        assert_eq!(layers.layer(POINT, Object(1)), DEFAULT_LAYER);
+        assert_eq!(layers.layer(POINT, Object(2)), "b");
+        assert_eq!(layers.active_layer(), "b");
+        layers.remove_layer("b").unwrap();
+        assert_eq!(layers.active_layer(), DEFAULT_LAYER);
+        assert_eq!(layers.objects(POINT, DEFAULT_LAYER).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn locked() {
+        let mut layers = layers();
+        layers.set_locked("a", true).unwrap();
+        assert!(layers.is_locked("a").unwrap());
+        expect_locked(layers.update(POINT, Object(1), &[5.0, 5.0]));
+        expect_locked(layers.delete(POINT, Object(1)));
+        expect_locked(layers.replace(POINT, Object(1), Object(0)));
+        expect_locked(layers.set_layer(POINT, Object(1), "b"));
+        expect_locked(layers.set_layer(POINT, Object(0), "a"));
+        assert_eq!(hits(&layers), vec![Object(0), Object(2)]);
+        // Locked objects are still shown and can be selected directly.
+        assert_eq!(layers.visible(POINT).len(), 3);
+        layers.select(POINT, Object(1)).unwrap();
+
+        layers.set_locked("a", false).unwrap();
+        layers.update(POINT, Object(1), &[5.0, 5.0]).unwrap();
+        layers.set_layer(POINT, Object(1), "b").unwrap();
+        assert_eq!(layers.objects(POINT, "b").unwrap(), vec![Object(1), Object(2)]);
+    }
+
+    #[test]
+    fn hidden_and_solo() {
+        let mut layers = layers();
+        layers.set_hidden("a", true).unwrap();
+        assert!(!layers.is_shown("a").unwrap());
+        assert_eq!(layers.visible(POINT), vec![Object(0), Object(2)]);
+        assert_eq!(hits(&layers), vec![Object(0), Object(2)]);
+
+        // Soloing shows only soloed layers, even hidden ones.
+        layers.set_solo("a", true).unwrap();
+        layers.set_solo("b", true).unwrap();
+        assert!(layers.is_solo("b").unwrap());
+        assert!(!layers.is_shown(DEFAULT_LAYER).unwrap());
+        assert_eq!(layers.visible(POINT), vec![Object(1), Object(2)]);
+        assert_eq!(hits(&layers), vec![Object(1), Object(2)]);
+
+        layers.set_solo("a", false).unwrap();
+        layers.set_solo("b", false).unwrap();
+        assert_eq!(hits(&layers), vec![Object(0), Object(2)]);
+    }
+
+    #[test]
+    fn follow_swap_remove() {
+        let mut layers = layers();
+        // Deleting 0 moves 2 into its place, which keeps its layer.
+        assert_eq!(layers.delete(POINT, Object(0)).unwrap(), Some(Object(2)));
+        assert_eq!(layers.layer(POINT, Object(0)), "b");
+        assert_eq!(layers.layer(POINT, Object(1)), "a");
+        // The new object gets the old id of the moved one, on the active layer.
+        layers.set_active_layer(DEFAULT_LAYER).unwrap();
+        let obj = layers.insert(POINT, &[3.0, 0.0]).unwrap();
+        assert_eq!(obj, Object(2));
+        assert_eq!(layers.layer(POINT, obj), DEFAULT_LAYER);
+    }
+}
@@ -2,21 +2,100 @@
 
 //! Editor interface.
 
+#[cfg(feature = "derive")]
+extern crate editor_derive;
+
 use std::any::Any;
 
+pub use action::{Action, ActionRegistry};
+pub use autosave::{Autosave, Recovery};
+pub use bounds::{polygon_contains, Bounds2, Bounds3, Plane};
+pub use bvh::Bvh;
+pub use clipboard::Clipboard;
+pub use cloner::Cloner;
+pub use conformance::Conformance;
+pub use document::Document;
+pub use duplicate::Duplicate;
+#[cfg(feature = "derive")]
+pub use editor_derive::Editor;
+pub use error::EditorError;
+pub use events::{Event, EventBus, Subscription};
+pub use grid::Grid;
+pub use hierarchy::{DeleteSubtree, Group, Hierarchy, Parents, SelectChildren, Ungroup};
+pub use journal::{Journal, Operation, Recording};
+pub use layers::{Layers, DEFAULT_LAYER};
+pub use ray::{Hit, Ray};
+pub use references::References;
+pub use selection::{
+    select_hits, GrowSelection, InvertSelection, SelectAllVisible, SelectFrustum, SelectLasso,
+    SelectMode, SelectRect,
+};
+pub use selection_sets::SelectionSets;
+pub use slot_map::{Handle, SlotMap};
+pub use state::EditorState;
+pub use transaction::Transaction;
+pub use type_registry::{Field, FieldKind, Schema, SchemaBuilder, TypeRegistry};
+pub use undo::UndoStack;
+pub use value::{EditorExt, FromValue, ToValue, Value};
+pub use vec_editor::VecEditor;
+pub use view::{Camera, Matrix4, View, ViewId, Views, IDENTITY};
+
+mod action;
+mod autosave;
+mod binary;
+mod bounds;
+mod bvh;
+mod clipboard;
+mod cloner;
+mod conformance;
+mod document;
+mod duplicate;
+mod error;
+mod events;
+mod grid;
+mod hierarchy;
+mod journal;
+mod json;
+mod layers;
+mod ray;
+mod references;
+mod selection;
+mod selection_sets;
+mod slot_map;
+mod state;
+mod transaction;
+mod type_registry;
+mod undo;
+mod value;
+mod vec_editor;
+mod view;
+
+#[cfg(test)]
+mod fixtures;
+
 /// A generic interface for editors, implemented on controllers.
 ///
 /// Provides all information necessary to execute actions,
 /// select objects, navigate and update.
-/// This makes it possible to write reusable generic actions.
+/// This makes it possible to write reusable generic actions, see `Action`.
 ///
 /// Methods that returns `Result` can trigger a rollback in actions.
 /// This is to prevent logical errors from affecting data.
 /// Concurrent actions are not permitted at the same time.
+/// See `Transaction` for running actions with automatic rollback.
 ///
 /// View information must be stored internally in the editor.
 /// If the editor state depends on the view state, then it should not be
 /// updated before `refresh_views` is called.
+///
+/// An editor can have multiple views, for example in a split-screen editor.
+/// `visible` and the `hit_*` methods are answered for the active view,
+/// so generic code can loop over `views` and call `set_active_view`.
+/// Editors without views use the default methods,
+/// which behave as a single view showing everything.
+///
+/// With the `derive` feature, `#[derive(Editor)]` implements this trait
+/// for a struct of `Vec` fields, see `EditorState`.
 pub trait Editor {
     /// Gets the current cursor position in 2D.
     fn cursor_2d(&self) -> Option<[f64; 2]>;
@@ -24,29 +103,52 @@ pub trait Editor {
     fn cursor_3d(&self) -> Option<[f64; 3]>;
     /// Try to hit objects at 2D position.
     fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)>;
+    /// Try to hit objects inside a rectangle in 2D, for rubber-band selection.
+    ///
+    /// See `SelectRect` for selecting the hit objects.
+    fn hit_rect_2d(&self, _rect: Bounds2) -> Vec<(Type, Object)> { vec![] }
+    /// Try to hit objects inside a closed polygon in 2D, for lasso selection.
+    ///
+    /// See `SelectLasso` for selecting the hit objects.
+    fn hit_polygon_2d(&self, _points: &[[f64; 2]]) -> Vec<(Type, Object)> { vec![] }
     /// Try to hit objects at 3D position.
     fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)>;
+    /// Try to hit objects inside all six planes of a frustum, for box selection in 3D.
+    ///
+    /// See `View::frustum` for the frustum of a rectangle and
+    /// `SelectFrustum` for selecting the hit objects.
+    fn hit_frustum(&self, _planes: &[Plane; 6]) -> Vec<(Type, Object)> { vec![] }
+    /// Try to hit objects along a ray, nearest first.
+    ///
+    /// See `Ray::from_cursor` for picking with the cursor.
+    fn hit_ray(&self, _origin: [f64; 3], _direction: [f64; 3]) -> Vec<Hit> { vec![] }
     /// Select a single object.
-    fn select(&mut self, ty: Type, obj: Object) -> Result<(), ()>;
+    ///
+    /// If the object is among the multiple selected ones, it becomes the selected object
+    /// and the multiple selection is kept, otherwise only the object is selected.
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError>;
     /// Select multiple objects.
     /// Adds to the current selection.
-    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), ()>;
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError>;
     /// Deselect multiple objects.
     /// Removes from the current selection.
-    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), ()>;
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError>;
     /// Deselect everything of a type.
-    fn select_none(&mut self, ty: Type) -> Result<(), ()>;
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError>;
     /// Inserts a new object.
-    fn insert(&mut self, ty: Type, args: &Any) -> Result<Object, ()>;
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError>;
+    /// Deletes an object.
+    ///
     /// Returns an object which references must be updated when
     /// using swap-remove by replacing object with last one in same table.
-    fn delete(&mut self, ty: Type, obj: Object) -> Result<(), ()>;
+    /// See `References` for updating references automatically.
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError>;
     /// Updates an object with new values.
-    fn update(&mut self, ty: Type, obj: Object, args: &Any) -> Result<(), ()>;
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError>;
     /// Replaces an object with another.
-    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), ()>;
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError>;
     /// Get the value of an object.
-    fn get<'a>(&'a self, ty: Type, obj: Object) -> Result<&'a Any, ()>;
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError>;
     /// Get the visible objects of a type.
     fn visible(&self, ty: Type) -> Vec<Object>;
     /// Gets the selected object of a type.
@@ -59,21 +161,44 @@ pub trait Editor {
     /// Get all objects of a type.
     fn all(&self, ty: Type) -> Vec<Object>;
     /// Navigate to an object such that it becomes visible.
-    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), ()>;
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError>;
+    /// Updates the view state, for example the visible objects of each view.
+    fn refresh_views(&mut self) {}
+    /// Gets the views.
+    fn views(&self) -> Vec<ViewId> { vec![] }
+    /// Gets a view.
+    fn view(&self, _id: ViewId) -> Option<&View> { None }
+    /// Gets the active view.
+    fn active_view(&self) -> Option<ViewId> { None }
+    /// Sets the active view.
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        Err(view::no_view(id))
+    }
 }
 
 /// The type of an object.
 /// This does not have be unique for Rust types.
 /// Dynamically typed objects should use same id.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Type(pub &'static str);
 /// The object id.
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Object(pub usize);
 
+/// A helper function for `Editor::insert` implementation.
+pub fn insert<T: Any + Clone>(items: &mut Vec<T>, args: &dyn Any) -> Result<Object, EditorError> {
+    let val = match args.downcast_ref::<T>() {
+        None => { return Err(EditorError::downcast::<T>(error::type_name_of(args))); }
+        Some(val) => val
+    };
+    items.push(val.clone());
+    Ok(Object(items.len() - 1))
+}
+
 /// A helper function for `Editor::delete` implementation.
-pub fn delete<T>(items: &mut Vec<T>, obj: Object) -> Result<Option<Object>, ()> {
-    if items.len() == 0 { return Err(()); }
+pub fn delete<T>(ty: Type, items: &mut Vec<T>, obj: Object)
+-> Result<Option<Object>, EditorError> {
+    if obj.0 >= items.len() { return Err(EditorError::StaleObject(ty, obj)); }
 
     let upd_obj = if obj.0 == items.len() - 1 {
         // The deleted object was last, no update needed.
@@ -88,23 +213,43 @@ pub fn delete<T>(items: &mut Vec<T>, obj: Object) -> Result<Option<Object>, ()>
 }
 
 /// A helper function for `Editor::update` implementation.
-pub fn update<T: Any + Clone>(items: &mut Vec<T>, obj: Object, args: &Any)
--> Result<(), ()> {
-    match args.downcast_ref::<T>() {
-        None => { return Err(()); }
-        Some(val) => {
-            items[obj.0] = val.clone();
+pub fn update<T: Any + Clone>(ty: Type, items: &mut [T], obj: Object, args: &dyn Any)
+-> Result<(), EditorError> {
+    let val = match args.downcast_ref::<T>() {
+        None => { return Err(EditorError::downcast::<T>(error::type_name_of(args))); }
+        Some(val) => val
+    };
+    match items.get_mut(obj.0) {
+        None => Err(EditorError::StaleObject(ty, obj)),
+        Some(item) => {
+            *item = val.clone();
+            Ok(())
+        }
+    }
+}
+
+/// A helper function for `Editor::replace` implementation.
+pub fn replace<T: Clone>(ty: Type, items: &mut [T], from: Object, to: Object)
+-> Result<(), EditorError> {
+    let val = match items.get(to.0) {
+        None => { return Err(EditorError::StaleObject(ty, to)); }
+        Some(val) => val.clone()
+    };
+    match items.get_mut(from.0) {
+        None => Err(EditorError::StaleObject(ty, from)),
+        Some(item) => {
+            *item = val;
             Ok(())
         }
     }
 }
 
 /// A helper function for `Editor::all` implementation.
-pub fn all<T>(items: &Vec<T>) -> Vec<Object> {
-    (0..items.len()).map(|i| Object(i)).collect()
+pub fn all<T>(items: &[T]) -> Vec<Object> {
+    (0..items.len()).map(Object).collect()
 }
 
 /// A helper function for `Editor::get` implementation.
-pub fn get<T: Any>(items: &Vec<T>, obj: Object) -> Result<&Any, ()> {
-    Ok(try!(items.get(obj.0).ok_or(())))
+pub fn get<T: Any>(ty: Type, items: &[T], obj: Object) -> Result<&dyn Any, EditorError> {
+    Ok(items.get(obj.0).ok_or(EditorError::StaleObject(ty, obj))?)
 }
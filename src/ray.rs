@@ -0,0 +1,196 @@
+use {Editor, Matrix4, Object, Type};
+
+/// A ray in 3D world coordinates.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Ray {
+    /// The start of the ray.
+    pub origin: [f64; 3],
+    /// The direction of the ray, of unit length when created by views.
+    pub direction: [f64; 3],
+}
+
+impl Ray {
+    /// Creates a ray through the cursor of the active view of an editor.
+    ///
+    /// Returns `None` if there is no cursor or active view.
+    pub fn from_cursor(editor: &dyn Editor) -> Option<Ray> {
+        let cursor = editor.cursor_2d()?;
+        let view = editor.view(editor.active_view()?)?;
+        view.ray(cursor)
+    }
+
+    /// Gets the point at a distance along the ray.
+    pub fn at(&self, distance: f64) -> [f64; 3] {
+        let (o, d) = (self.origin, self.direction);
+        [o[0] + d[0] * distance, o[1] + d[1] * distance, o[2] + d[2] * distance]
+    }
+
+    /// Intersects the ray with a sphere, returning the distance and the normal.
+    ///
+    /// A helper for functions passed to `VecEditor::set_hit_ray`.
+    pub fn hit_sphere(&self, center: [f64; 3], radius: f64) -> Option<(f64, [f64; 3])> {
+        let oc = sub(self.origin, center);
+        let a = dot(self.direction, self.direction);
+        let b = dot(oc, self.direction);
+        let c = dot(oc, oc) - radius * radius;
+        let d = b * b - a * c;
+        if a == 0.0 || d < 0.0 { return None; }
+
+        let sqrt_d = d.sqrt();
+        let mut t = (-b - sqrt_d) / a;
+        // Use the far intersection when the ray starts inside the sphere.
+        if t < 0.0 { t = (-b + sqrt_d) / a; }
+        if t < 0.0 { return None; }
+        let normal = normalize(sub(self.at(t), center))?;
+        Some((t, normal))
+    }
+}
+
+/// An object hit by a ray.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    /// The type of the object.
+    pub ty: Type,
+    /// The object.
+    pub obj: Object,
+    /// The distance along the ray, in units of the ray direction length.
+    pub distance: f64,
+    /// The hit position in world coordinates.
+    pub position: [f64; 3],
+    /// The surface normal at the hit position.
+    pub normal: [f64; 3],
+}
+
+/// Sorts hits by distance, nearest first.
+pub(crate) fn sort_hits(hits: &mut [Hit]) {
+    hits.sort_by(|a, b| a.distance.partial_cmp(&b.distance)
+        .unwrap_or(::std::cmp::Ordering::Equal));
+}
+
+pub(crate) fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub(crate) fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+pub(crate) fn normalize(a: [f64; 3]) -> Option<[f64; 3]> {
+    let len = dot(a, a).sqrt();
+    if len == 0.0 || !len.is_finite() { return None; }
+    Some([a[0] / len, a[1] / len, a[2] / len])
+}
+
+/// Multiplies two column major matrices.
+pub(crate) fn mul(a: &Matrix4, b: &Matrix4) -> Matrix4 {
+    let mut res = [[0.0; 4]; 4];
+    for (c, col) in res.iter_mut().enumerate() {
+        for (r, x) in col.iter_mut().enumerate() {
+            *x = (0..4).map(|k| a[k][r] * b[c][k]).sum();
+        }
+    }
+    res
+}
+
+/// Transforms a point, dividing by the homogeneous coordinate.
+pub(crate) fn transform(m: &Matrix4, p: [f64; 3]) -> Option<[f64; 3]> {
+    let v = [p[0], p[1], p[2], 1.0];
+    let mut res = [0.0; 4];
+    for (r, x) in res.iter_mut().enumerate() {
+        *x = (0..4).map(|c| m[c][r] * v[c]).sum();
+    }
+    if res[3] == 0.0 { return None; }
+    Some([res[0] / res[3], res[1] / res[3], res[2] / res[3]])
+}
+
+/// Inverts a matrix with Gauss-Jordan elimination.
+///
+/// Returns `None` if the matrix is singular.
+pub(crate) fn invert(m: &Matrix4) -> Option<Matrix4> {
+    // Work on rows of `[m | identity]`.
+    let mut a = [[0.0; 8]; 4];
+    for (r, row) in a.iter_mut().enumerate() {
+        for c in 0..4 { row[c] = m[c][r]; }
+        row[4 + r] = 1.0;
+    }
+    for c in 0..4 {
+        let pivot = (c..4).max_by(|&i, &j| a[i][c].abs().partial_cmp(&a[j][c].abs())
+            .unwrap_or(::std::cmp::Ordering::Equal))?;
+        if a[pivot][c].abs() < 1e-12 { return None; }
+        a.swap(c, pivot);
+        let p = a[c][c];
+        for x in a[c].iter_mut() { *x /= p; }
+        for r in 0..4 {
+            if r == c { continue; }
+            let f = a[r][c];
+            if f == 0.0 { continue; }
+            let pivot_row = a[c];
+            for (x, &p) in a[r].iter_mut().zip(pivot_row.iter()) { *x -= f * p; }
+        }
+    }
+    let mut res = [[0.0; 4]; 4];
+    for (c, col) in res.iter_mut().enumerate() {
+        for (r, x) in col.iter_mut().enumerate() { *x = a[r][4 + c]; }
+    }
+    Some(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Camera, VecEditor, IDENTITY};
+
+    const BALL: Type = Type("ball");
+
+    fn close(a: [f64; 3], b: [f64; 3]) -> bool {
+        (0..3).all(|i| (a[i] - b[i]).abs() < 1e-9)
+    }
+
+    #[test]
+    fn hit_sphere() {
+        let ray = Ray { origin: [0.0, 0.0, -5.0], direction: [0.0, 0.0, 1.0] };
+        let (t, normal) = ray.hit_sphere([0.0, 0.0, 0.0], 1.0).unwrap();
+        assert_eq!((t, normal), (4.0, [0.0, 0.0, -1.0]));
+        let inside = Ray { origin: [0.0; 3], direction: [0.0, 0.0, 1.0] };
+        assert_eq!(inside.hit_sphere([0.0; 3], 2.0).unwrap().0, 2.0);
+        assert!(ray.hit_sphere([0.0, 3.0, 0.0], 1.0).is_none());
+        assert!(ray.hit_sphere([0.0, 0.0, -10.0], 1.0).is_none());
+    }
+
+    #[test]
+    fn matrices() {
+        let mut m = IDENTITY;
+        m[3] = [1.0, 2.0, 3.0, 1.0];
+        m[0][0] = 2.0;
+        let inv = invert(&m).unwrap();
+        assert!(close(transform(&inv, transform(&m, [1.0, 1.0, 1.0]).unwrap()).unwrap(),
+                      [1.0, 1.0, 1.0]));
+        assert_eq!(transform(&mul(&m, &inv), [5.0, 6.0, 7.0]), Some([5.0, 6.0, 7.0]));
+        assert!(invert(&[[0.0; 4]; 4]).is_none());
+    }
+
+    #[test]
+    fn view_ray() {
+        let mut editor = VecEditor::new();
+        assert!(Ray::from_cursor(&editor).is_none());
+        editor.add_view([0.0, 0.0, 200.0, 100.0], Camera::default());
+        editor.set_cursor_2d(Some([150.0, 25.0]));
+        let ray = Ray::from_cursor(&editor).unwrap();
+        assert!(close(ray.origin, [0.5, 0.5, -1.0]));
+        assert!(close(ray.direction, [0.0, 0.0, 1.0]));
+    }
+
+    #[test]
+    fn nearest_first() {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 3]>(BALL);
+        editor.set_hit_ray::<[f64; 3]>(BALL, |c, ray| ray.hit_sphere(*c, 1.0)).unwrap();
+        for &z in &[5.0, 2.0, 9.0] { editor.insert(BALL, &[0.0, 0.0, z]).unwrap(); }
+        editor.insert(BALL, &[4.0, 0.0, 0.0]).unwrap();
+        let hits = editor.hit_ray([0.0; 3], [0.0, 0.0, 1.0]);
+        let objs: Vec<_> = hits.iter().map(|hit| hit.obj).collect();
+        assert_eq!(objs, vec![Object(1), Object(0), Object(2)]);
+        assert_eq!(hits[0].position, [0.0, 0.0, 1.0]);
+        assert_eq!(hits[0].normal, [0.0, 0.0, -1.0]);
+    }
+}
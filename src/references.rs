@@ -0,0 +1,291 @@
+use std::any::Any;
+use std::collections::HashMap;
+
+use error::downcast;
+use {Editor, EditorError, Object, Type};
+
+type ReadFn = Box<dyn Fn(&dyn Any) -> Result<Vec<Object>, EditorError>>;
+type RewriteFn = Box<dyn Fn(&dyn Any, Object, Object) -> Result<Box<dyn Any>, EditorError>>;
+
+/// Declares that objects of one type refer to objects of another type.
+struct Declaration {
+    owner: Type,
+    target: Type,
+    read: ReadFn,
+    rewrite: RewriteFn,
+}
+
+/// Keeps track of which fields hold references between types,
+/// such that references can be updated when objects are moved.
+///
+/// Deleting with swap-remove moves the last object of a table
+/// into the place of the deleted one.
+/// Use `References::delete` instead of `Editor::delete`
+/// to update references to the moved object automatically.
+#[derive(Default)]
+pub struct References {
+    declarations: Vec<Declaration>,
+}
+
+impl References {
+    /// Creates a new empty reference registry.
+    pub fn new() -> References {
+        References::default()
+    }
+
+    /// Declares the fields of `T` that refer to objects of type `target`.
+    ///
+    /// `T` is the Rust type used to store objects of type `owner`.
+    /// `read` gets the references and `write` gives access to the same fields,
+    /// for example `|line: &Line| vec![line.a, line.b]`
+    /// and `|line: &mut Line| vec![&mut line.a, &mut line.b]`.
+    pub fn register<T: Any + Clone>(
+        &mut self,
+        owner: Type,
+        target: Type,
+        read: fn(&T) -> Vec<Object>,
+        write: fn(&mut T) -> Vec<&mut Object>
+    ) {
+        let read = move |val: &dyn Any| -> Result<Vec<Object>, EditorError> {
+            Ok(read(downcast::<T>(val)?))
+        };
+        let rewrite = move |val: &dyn Any, from: Object, to: Object|
+        -> Result<Box<dyn Any>, EditorError> {
+            let mut val = downcast::<T>(val)?.clone();
+            for obj in write(&mut val) {
+                if *obj == from { *obj = to; }
+            }
+            Ok(Box::new(val))
+        };
+        self.declarations.push(Declaration {
+            owner,
+            target,
+            read: Box::new(read),
+            rewrite: Box::new(rewrite),
+        });
+    }
+
+    /// Gets the objects referred to by an object.
+    pub fn references(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut res = vec![];
+        for decl in self.declarations.iter().filter(|decl| decl.owner == ty) {
+            let val = editor.get(ty, obj)?;
+            for target in (decl.read)(val)? {
+                res.push((decl.target, target));
+            }
+        }
+        Ok(res)
+    }
+
+    /// Gets the objects that refer to an object.
+    pub fn referencing(&self, editor: &dyn Editor, ty: Type, obj: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut res = vec![];
+        for decl in self.declarations.iter().filter(|decl| decl.target == ty) {
+            for owner_obj in editor.all(decl.owner) {
+                let val = editor.get(decl.owner, owner_obj)?;
+                if (decl.read)(val)?.contains(&obj) && !res.contains(&(decl.owner, owner_obj)) {
+                    res.push((decl.owner, owner_obj));
+                }
+            }
+        }
+        Ok(res)
+    }
+
+    /// Makes every reference to `from` refer to `to` instead.
+    ///
+    /// The new values are computed before any object is updated,
+    /// such that a value that can not be rewritten leaves every object unchanged.
+    /// Returns the objects that were updated.
+    pub fn rewrite(&self, editor: &mut dyn Editor, ty: Type, from: Object, to: Object)
+    -> Result<Vec<(Type, Object)>, EditorError> {
+        let mut new_vals: Vec<((Type, Object), Box<dyn Any>)> = vec![];
+        for decl in self.declarations.iter().filter(|decl| decl.target == ty) {
+            for owner_obj in editor.all(decl.owner) {
+                let key = (decl.owner, owner_obj);
+                // An object with several declarations is rewritten once per declaration.
+                match new_vals.iter_mut().find(|&&mut (k, _)| k == key) {
+                    Some(&mut (_, ref mut new_val)) => {
+                        if (decl.read)(&**new_val)?.contains(&from) {
+                            *new_val = (decl.rewrite)(&**new_val, from, to)?;
+                        }
+                    }
+                    None => {
+                        let val = editor.get(decl.owner, owner_obj)?;
+                        if (decl.read)(val)?.contains(&from) {
+                            new_vals.push((key, (decl.rewrite)(val, from, to)?));
+                        }
+                    }
+                }
+            }
+        }
+        for &((owner, owner_obj), ref new_val) in &new_vals {
+            editor.update(owner, owner_obj, &**new_val)?;
+        }
+        Ok(new_vals.into_iter().map(|(key, _)| key).collect())
+    }
+
+    /// Changes the references of one object using a map from old to new objects.
+    ///
+    /// References to objects not in the map are kept.
+    /// The new objects must not be among the old ones,
+    /// since references are rewritten one at a time.
+    /// Returns `true` if the object was updated.
+    pub fn remap(
+        &self,
+        editor: &mut dyn Editor,
+        ty: Type,
+        obj: Object,
+        map: &HashMap<(Type, Object), Object>
+    ) -> Result<bool, EditorError> {
+        let mut rewrites = vec![];
+        {
+            let val = editor.get(ty, obj)?;
+            for decl in self.declarations.iter().filter(|decl| decl.owner == ty) {
+                for target in (decl.read)(val)? {
+                    if let Some(&to) = map.get(&(decl.target, target)) {
+                        rewrites.push((decl, target, to));
+                    }
+                }
+            }
+        }
+        let mut new_val: Option<Box<dyn Any>> = None;
+        for (decl, from, to) in rewrites {
+            new_val = Some(match new_val {
+                None => (decl.rewrite)(editor.get(ty, obj)?, from, to)?,
+                Some(val) => (decl.rewrite)(&*val, from, to)?,
+            });
+        }
+        match new_val {
+            None => Ok(false),
+            Some(val) => {
+                editor.update(ty, obj, &*val)?;
+                Ok(true)
+            }
+        }
+    }
+
+    /// Deletes an object and updates references to the object moved by swap-remove.
+    ///
+    /// Refuses to delete an object that is referred to by other objects,
+    /// since those references would point to the moved object.
+    /// The error lists the referring objects, see `References::referencing`.
+    ///
+    /// The references are updated after the delete, so an update refused by the editor
+    /// leaves the object deleted and references to the moved object stale.
+    /// Run the delete inside `Transaction::run` to roll back on failure.
+    pub fn delete(&self, editor: &mut dyn Editor, ty: Type, obj: Object)
+    -> Result<Option<Object>, EditorError> {
+        let mut dangling = self.referencing(editor, ty, obj)?;
+        dangling.retain(|&referrer| referrer != (ty, obj));
+        if !dangling.is_empty() {
+            let list: Vec<String> = dangling.iter()
+                .map(|&(owner, owner_obj)| format!("`{}` {}", owner.0, owner_obj.0))
+                .collect();
+            return Err(EditorError::ConstraintViolation(format!(
+                "`{}` {} is referenced by {}", ty.0, obj.0, list.join(", ")
+            )));
+        }
+        let moved = editor.delete(ty, obj)?;
+        if let Some(last) = moved {
+            // The last object now takes the place of the deleted object.
+            self.rewrite(editor, ty, last, obj)?;
+        }
+        Ok(moved)
+    }
+
+    /// Replaces an object with another and makes references to `from` refer to `to`.
+    pub fn replace(&self, editor: &mut dyn Editor, ty: Type, from: Object, to: Object)
+    -> Result<(), EditorError> {
+        editor.replace(ty, from, to)?;
+        self.rewrite(editor, ty, from, to)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fixtures::{line, Line, LINE, POINT};
+    use {Cloner, Transaction, VecEditor};
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        editor.register::<Line>(LINE);
+        for i in 0..4u32 { editor.insert(POINT, &i).unwrap(); }
+        editor.insert(LINE, &line(0, 1)).unwrap();
+        editor.insert(LINE, &line(1, 3)).unwrap();
+        editor
+    }
+
+    fn references() -> References {
+        let mut refs = References::new();
+        refs.register::<Line>(LINE, POINT, |l| vec![l.a], |l| vec![&mut l.a]);
+        refs.register::<Line>(LINE, POINT, |l| vec![l.b], |l| vec![&mut l.b]);
+        refs
+    }
+
+    #[test]
+    fn read() {
+        let editor = editor();
+        let refs = references();
+        assert_eq!(refs.references(&editor, LINE, Object(1)).unwrap(),
+            vec![(POINT, Object(1)), (POINT, Object(3))]);
+        let mut referencing = refs.referencing(&editor, POINT, Object(1)).unwrap();
+        referencing.sort_by_key(|&(_, obj)| obj.0);
+        assert_eq!(referencing, vec![(LINE, Object(0)), (LINE, Object(1))]);
+    }
+
+    #[test]
+    fn delete() {
+        let mut editor = editor();
+        let refs = references();
+        assert!(refs.delete(&mut editor, POINT, Object(1)).is_err());
+        assert_eq!(editor.all(POINT).len(), 4);
+
+        assert_eq!(refs.delete(&mut editor, POINT, Object(2)).unwrap(), Some(Object(3)));
+        assert_eq!(editor.items::<Line>(LINE).unwrap(), &[line(0, 1), line(1, 2)][..]);
+    }
+
+    #[test]
+    fn rewrite_several_declarations() {
+        let mut editor = editor();
+        editor.insert(LINE, &line(1, 1)).unwrap();
+        let refs = references();
+        let mut updated = refs.rewrite(&mut editor, POINT, Object(1), Object(2)).unwrap();
+        updated.sort_by_key(|&(_, obj)| obj.0);
+        assert_eq!(updated, vec![(LINE, Object(0)), (LINE, Object(1)), (LINE, Object(2))]);
+        assert_eq!(editor.items::<Line>(LINE).unwrap(),
+            &[line(0, 2), line(2, 3), line(2, 2)][..]);
+    }
+
+    #[test]
+    fn failed_rewrite_changes_nothing() {
+        let mut editor = editor();
+        let mut refs = references();
+        // Declared with the wrong Rust type, so reading fails.
+        refs.register::<u32>(LINE, POINT, |_| vec![], |_| vec![]);
+        assert!(refs.rewrite(&mut editor, POINT, Object(1), Object(2)).is_err());
+        assert_eq!(editor.items::<Line>(LINE).unwrap(), &[line(0, 1), line(1, 3)][..]);
+    }
+
+    #[test]
+    fn failed_delete_in_transaction() {
+        let mut editor = editor();
+        let refs = references();
+        // The transaction can not record changes of lines, so moving the point 3 is refused.
+        let mut cloner = Cloner::new();
+        cloner.register::<u32>(POINT);
+        let res = Transaction::run(&mut editor, &cloner, |editor| {
+            refs.delete(editor, POINT, Object(2))
+        });
+        match res {
+            Err(EditorError::UnknownType(LINE)) => {}
+            _ => panic!("expected an unknown type error"),
+        }
+        assert_eq!(editor.items::<u32>(POINT).unwrap(), &[0, 1, 2, 3][..]);
+        assert_eq!(editor.items::<Line>(LINE).unwrap(), &[line(0, 1), line(1, 3)][..]);
+    }
+}
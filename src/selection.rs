@@ -0,0 +1,385 @@
+use std::rc::Rc;
+
+use {Action, Bounds2, Editor, EditorError, Object, Plane, References, Type};
+
+/// How hit objects are combined with the current selection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SelectMode {
+    /// Select only the hit objects.
+    Replace,
+    /// Add the hit objects to the selection.
+    Add,
+    /// Remove the hit objects from the selection.
+    Subtract,
+    /// Select hit objects that are not selected and deselect those that are.
+    Toggle,
+}
+
+/// Changes the selection using hit objects, for example from `Editor::hit_rect_2d`.
+///
+/// Only objects of the types in `filter` are used, or every type when empty.
+/// In `SelectMode::Replace`, the types in `filter` are deselected even without hits.
+pub fn select_hits(
+    editor: &mut dyn Editor,
+    hits: &[(Type, Object)],
+    filter: &[Type],
+    mode: SelectMode
+) -> Result<(), EditorError> {
+    let mut types: Vec<Type> = filter.to_vec();
+    if types.is_empty() {
+        for &(ty, _) in hits {
+            if !types.contains(&ty) { types.push(ty); }
+        }
+    }
+    for ty in types {
+        let objs: Vec<Object> = hits.iter()
+            .filter(|&&(t, _)| t == ty)
+            .map(|&(_, obj)| obj)
+            .collect();
+        match mode {
+            SelectMode::Replace => {
+                editor.select_none(ty)?;
+                if !objs.is_empty() { editor.select_multiple(ty, &objs)?; }
+            }
+            SelectMode::Add => if !objs.is_empty() { editor.select_multiple(ty, &objs)?; },
+            SelectMode::Subtract => if !objs.is_empty() { editor.deselect_multiple(ty, &objs)?; },
+            SelectMode::Toggle => {
+                let selected = editor.multiple_selected(ty);
+                let (deselect, select): (Vec<Object>, Vec<Object>) =
+                    objs.into_iter().partition(|obj| selected.contains(obj));
+                if !deselect.is_empty() { editor.deselect_multiple(ty, &deselect)?; }
+                if !select.is_empty() { editor.select_multiple(ty, &select)?; }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Selects objects inside a rectangle, for rubber-band selection.
+#[derive(Clone, Debug)]
+pub struct SelectRect {
+    /// The rectangle in the same coordinates as `Editor::hit_2d`.
+    pub rect: Bounds2,
+    /// How to combine with the current selection.
+    pub mode: SelectMode,
+    /// The types to select, or every type when empty.
+    pub filter: Vec<Type>,
+}
+
+impl Action for SelectRect {
+    fn name(&self) -> &str {
+        "select_rect"
+    }
+
+    fn description(&self) -> &str {
+        "Select objects inside a rectangle"
+    }
+
+    fn types(&self) -> &[Type] {
+        &[]
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let hits = editor.hit_rect_2d(self.rect);
+        select_hits(editor, &hits, &self.filter, self.mode)
+    }
+}
+
+/// Selects objects inside a lasso.
+#[derive(Clone, Debug)]
+pub struct SelectLasso {
+    /// The points of the closed polygon,
+    /// in the same coordinates as `Editor::hit_2d`.
+    pub points: Vec<[f64; 2]>,
+    /// How to combine with the current selection.
+    pub mode: SelectMode,
+    /// The types to select, or every type when empty.
+    pub filter: Vec<Type>,
+}
+
+impl Action for SelectLasso {
+    fn name(&self) -> &str {
+        "select_lasso"
+    }
+
+    fn description(&self) -> &str {
+        "Select objects inside a lasso"
+    }
+
+    fn types(&self) -> &[Type] {
+        &[]
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let hits = editor.hit_polygon_2d(&self.points);
+        select_hits(editor, &hits, &self.filter, self.mode)
+    }
+}
+
+/// Selects objects inside a frustum, for box selection in 3D.
+///
+/// See `View::frustum` for the frustum of a rectangle.
+#[derive(Clone, Debug)]
+pub struct SelectFrustum {
+    /// The planes of the frustum, with normals pointing to the inside.
+    pub planes: [Plane; 6],
+    /// How to combine with the current selection.
+    pub mode: SelectMode,
+    /// The types to select, or every type when empty.
+    pub filter: Vec<Type>,
+}
+
+impl Action for SelectFrustum {
+    fn name(&self) -> &str {
+        "select_frustum"
+    }
+
+    fn description(&self) -> &str {
+        "Select objects inside a frustum"
+    }
+
+    fn types(&self) -> &[Type] {
+        &[]
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let hits = editor.hit_frustum(&self.planes);
+        select_hits(editor, &hits, &self.filter, self.mode)
+    }
+}
+
+/// Inverts the selection among visible objects.
+///
+/// Hidden objects keep their selection.
+/// Unlike the actions selecting hits, an empty filter changes nothing,
+/// since an editor can not list its types.
+#[derive(Clone, Debug)]
+pub struct InvertSelection {
+    /// The types to invert.
+    pub filter: Vec<Type>,
+}
+
+impl Action for InvertSelection {
+    fn name(&self) -> &str {
+        "invert_selection"
+    }
+
+    fn description(&self) -> &str {
+        "Select visible objects that are not selected and deselect those that are"
+    }
+
+    fn types(&self) -> &[Type] {
+        &[]
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let hits = visible(editor, &self.filter);
+        select_hits(editor, &hits, &self.filter, SelectMode::Toggle)
+    }
+}
+
+/// Selects all visible objects.
+///
+/// Like `InvertSelection`, an empty filter changes nothing.
+#[derive(Clone, Debug)]
+pub struct SelectAllVisible {
+    /// The types to select.
+    pub filter: Vec<Type>,
+}
+
+impl Action for SelectAllVisible {
+    fn name(&self) -> &str {
+        "select_all_visible"
+    }
+
+    fn description(&self) -> &str {
+        "Select all visible objects"
+    }
+
+    fn types(&self) -> &[Type] {
+        &[]
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let hits = visible(editor, &self.filter);
+        select_hits(editor, &hits, &self.filter, SelectMode::Replace)
+    }
+}
+
+fn visible(editor: &dyn Editor, types: &[Type]) -> Vec<(Type, Object)> {
+    types.iter()
+        .flat_map(|&ty| editor.visible(ty).into_iter().map(move |obj| (ty, obj)))
+        .collect()
+}
+
+/// Adds objects connected by references to the selection,
+/// both those referred to and those referring to selected objects.
+///
+/// Connected objects of any type are selected, not only the types in `filter`.
+pub struct GrowSelection {
+    /// The references to follow.
+    pub references: Rc<References>,
+    /// The types which selection is grown.
+    pub filter: Vec<Type>,
+}
+
+impl Action for GrowSelection {
+    fn name(&self) -> &str {
+        "grow_selection"
+    }
+
+    fn description(&self) -> &str {
+        "Select objects connected to the selection"
+    }
+
+    fn types(&self) -> &[Type] {
+        &self.filter
+    }
+
+    fn execute(&self, editor: &mut dyn Editor) -> Result<(), EditorError> {
+        let mut hits = vec![];
+        for &ty in &self.filter {
+            for obj in editor.multiple_selected(ty) {
+                hits.extend(self.references.references(editor, ty, obj)?);
+                hits.extend(self.references.referencing(editor, ty, obj)?);
+            }
+        }
+        select_hits(editor, &hits, &[], SelectMode::Add)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use {Bounds3, Camera, VecEditor};
+
+    const BALL: Type = Type("ball");
+    const POINT: Type = Type("point");
+    const LINE: Type = Type("line");
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 2]>(POINT);
+        editor.register::<[f64; 2]>(LINE);
+        editor.set_bounds_2d::<[f64; 2]>(POINT, |p| Bounds2::point(*p)).unwrap();
+        editor.set_bounds_2d::<[f64; 2]>(LINE, |p| Bounds2::new(*p, [p[0] + 1.0, p[1]])).unwrap();
+        for i in 0..4 { editor.insert(POINT, &[i as f64, 0.0]).unwrap(); }
+        editor.insert(LINE, &[0.0, 1.0]).unwrap();
+        editor
+    }
+
+    fn rect(mode: SelectMode, filter: Vec<Type>) -> SelectRect {
+        SelectRect { rect: Bounds2::new([-0.5, -0.5], [1.5, 1.5]), mode, filter }
+    }
+
+    #[test]
+    fn modes() {
+        let mut editor = editor();
+        editor.select(POINT, Object(3)).unwrap();
+        rect(SelectMode::Add, vec![]).execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(3), Object(0), Object(1)]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+
+        let hits = [(POINT, Object(1)), (POINT, Object(2))];
+        select_hits(&mut editor, &hits, &[], SelectMode::Toggle).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(3), Object(0), Object(2)]);
+        select_hits(&mut editor, &hits, &[], SelectMode::Subtract).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(3), Object(0)]);
+
+        rect(SelectMode::Replace, vec![POINT]).execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(0), Object(1)]);
+        assert_eq!(editor.selected(POINT), Some(Object(0)));
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+    }
+
+    #[test]
+    fn replace_without_hits() {
+        let mut editor = editor();
+        editor.select(POINT, Object(0)).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+        let empty = SelectRect {
+            rect: Bounds2::new([10.0, 10.0], [11.0, 11.0]),
+            mode: SelectMode::Replace,
+            filter: vec![POINT],
+        };
+        empty.execute(&mut editor).unwrap();
+        assert!(editor.multiple_selected(POINT).is_empty());
+        assert_eq!(editor.selected(LINE), Some(Object(0)));
+    }
+
+    #[test]
+    fn lasso() {
+        let mut editor = editor();
+        // A triangle around the first two points, crossing the line.
+        let lasso = SelectLasso {
+            points: vec![[-0.5, -0.5], [1.5, -0.5], [-0.5, 1.5]],
+            mode: SelectMode::Replace,
+            filter: vec![],
+        };
+        lasso.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(0), Object(1)]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+
+        let outside = Bounds2::new([0.9, 0.9], [1.0, 1.0]);
+        assert!(!outside.intersects_polygon(&lasso.points));
+        assert!(!Bounds2::point([0.0, 0.0]).intersects_polygon(&[]));
+    }
+
+    #[test]
+    fn frustum() {
+        let mut editor = VecEditor::new();
+        editor.register::<[f64; 3]>(BALL);
+        editor.set_bounds_3d::<[f64; 3]>(BALL, |c| Bounds3::sphere(*c, 0.1)).unwrap();
+        for &c in &[[0.5, 0.5, 0.0], [-0.5, 0.5, 0.0], [0.5, 0.5, 5.0], [0.95, 0.05, 0.9]] {
+            editor.insert(BALL, &c).unwrap();
+        }
+        let view = editor.add_view([0.0, 0.0, 100.0, 100.0], Camera::default());
+        editor.refresh_views();
+        // The upper right quarter of the window.
+        let rect = Bounds2::new([50.0, 0.0], [100.0, 50.0]);
+        let planes = editor.view(view).unwrap().frustum(rect).unwrap();
+        let action = SelectFrustum { planes, mode: SelectMode::Replace, filter: vec![] };
+        action.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(BALL), vec![Object(0), Object(3)]);
+
+        let empty = Bounds2::new([50.0, 0.0], [50.0, 50.0]);
+        assert!(editor.view(view).unwrap().frustum(empty).is_none());
+    }
+
+    #[test]
+    fn invert_and_select_all() {
+        let mut editor = editor();
+        editor.set_visible(POINT, Object(3), false).unwrap();
+        editor.select_multiple(POINT, &[Object(1), Object(3)]).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+
+        InvertSelection { filter: vec![] }.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1), Object(3)]);
+        InvertSelection { filter: vec![POINT] }.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(3), Object(0), Object(2)]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+
+        SelectAllVisible { filter: vec![] }.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT).len(), 3);
+        SelectAllVisible { filter: vec![POINT] }.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(0), Object(1), Object(2)]);
+    }
+
+    #[test]
+    fn grow() {
+        let mut editor = editor();
+        let mut references = References::new();
+        // Each line refers to the point at its start.
+        references.register::<[f64; 2]>(LINE, POINT, |p| vec![Object(p[0] as usize)], |_| vec![]);
+        editor.select(LINE, Object(0)).unwrap();
+        let action = GrowSelection { references: Rc::new(references), filter: vec![LINE] };
+        action.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(0)]);
+        editor.select_none(LINE).unwrap();
+        action.execute(&mut editor).unwrap();
+        assert!(editor.multiple_selected(LINE).is_empty());
+        let action = GrowSelection { filter: vec![POINT], ..action };
+        action.execute(&mut editor).unwrap();
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+    }
+}
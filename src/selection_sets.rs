@@ -0,0 +1,242 @@
+use std::collections::BTreeMap;
+
+use document::find_type;
+use selection::select_hits;
+use value::mismatch;
+use {Editor, EditorError, Object, SelectMode, ToValue, Type, TypeRegistry, Value};
+
+/// The selected objects of each type.
+type Set = Vec<(Type, Vec<Object>)>;
+
+/// Named selection sets, for saving and recalling selections.
+///
+/// Sets must be kept in sync with the editor by calling `deleted` on delete.
+/// Sets are persisted with `ToValue` and `SelectionSets::from_value`
+/// as maps from type names to lists of references,
+/// so remap them like other values when saving documents.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SelectionSets {
+    sets: BTreeMap<String, Set>,
+}
+
+impl SelectionSets {
+    /// Creates a new empty list of selection sets.
+    pub fn new() -> SelectionSets {
+        SelectionSets::default()
+    }
+
+    /// Gets the names of the sets, in sorted order.
+    pub fn names(&self) -> Vec<&str> {
+        self.sets.keys().map(|name| &name[..]).collect()
+    }
+
+    /// Gets the objects of a set, sorted by the name of the type.
+    pub fn get(&self, name: &str) -> Option<&[(Type, Vec<Object>)]> {
+        self.sets.get(name).map(|set| &set[..])
+    }
+
+    /// Saves the multiple selection of some types, replacing any set with the same name.
+    pub fn save(&mut self, name: &str, editor: &dyn Editor, types: &[Type]) {
+        let mut set: Set = types.iter().map(|&ty| (ty, editor.multiple_selected(ty))).collect();
+        // Sorted the same way as when persisted.
+        set.sort_by_key(|&(ty, _)| ty.0);
+        set.dedup_by_key(|&mut (ty, _)| ty);
+        self.sets.insert(name.into(), set);
+    }
+
+    /// Recalls a set, combining it with the current selection.
+    ///
+    /// In `SelectMode::Replace`, only the types in the set are changed.
+    /// Fails with `EditorError::StaleObject` without changing the selection
+    /// if an object in the set no longer exists.
+    pub fn recall(&self, name: &str, editor: &mut dyn Editor, mode: SelectMode)
+    -> Result<(), EditorError> {
+        let set = self.sets.get(name).ok_or_else(|| EditorError::ConstraintViolation(
+            format!("no selection set named `{}`", name)
+        ))?;
+        for &(ty, ref objs) in set {
+            let all = editor.all(ty);
+            if let Some(&obj) = objs.iter().find(|obj| !all.contains(obj)) {
+                return Err(EditorError::StaleObject(ty, obj));
+            }
+        }
+        let types: Vec<Type> = set.iter().map(|&(ty, _)| ty).collect();
+        let hits: Vec<(Type, Object)> = set.iter()
+            .flat_map(|&(ty, ref objs)| objs.iter().map(move |&obj| (ty, obj)))
+            .collect();
+        select_hits(editor, &hits, &types, mode)
+    }
+
+    /// Removes a set, returning `true` if it was found.
+    pub fn remove(&mut self, name: &str) -> bool {
+        self.sets.remove(name).is_some()
+    }
+
+    /// Updates the sets after deleting an object with swap-remove,
+    /// see `Editor::delete`.
+    pub fn deleted(&mut self, ty: Type, obj: Object, moved: Option<Object>) {
+        for set in self.sets.values_mut() {
+            for &mut (t, ref mut objs) in set.iter_mut() {
+                if t != ty { continue; }
+                objs.retain(|&o| o != obj);
+                if let Some(last) = moved {
+                    for o in objs.iter_mut() {
+                        if *o == last { *o = obj; }
+                    }
+                }
+            }
+        }
+    }
+
+    /// Reads sets written with `ToValue`, looking up types by name.
+    ///
+    /// Types without selected objects are kept,
+    /// such that recalling in `SelectMode::Replace` clears their selection.
+    pub fn from_value(val: &Value, types: &TypeRegistry) -> Result<SelectionSets, EditorError> {
+        let map = match *val {
+            Value::Map(ref map) => map,
+            _ => return Err(mismatch::<SelectionSets>(val)),
+        };
+        let mut sets = BTreeMap::new();
+        for (name, set_val) in map {
+            let set_map = match *set_val {
+                Value::Map(ref set_map) => set_map,
+                _ => return Err(mismatch::<BTreeMap<String, Vec<Object>>>(set_val)),
+            };
+            let mut set: Set = vec![];
+            for (ty_name, refs) in set_map {
+                let ty = find_type(types, ty_name)?;
+                let refs = match *refs {
+                    Value::List(ref refs) => refs,
+                    _ => return Err(mismatch::<Vec<Object>>(refs)),
+                };
+                let mut objs = vec![];
+                for r in refs {
+                    match *r {
+                        Value::Reference(t, obj) if t == ty => objs.push(obj),
+                        _ => return Err(mismatch::<Object>(r)),
+                    }
+                }
+                set.push((ty, objs));
+            }
+            sets.insert(name.clone(), set);
+        }
+        Ok(SelectionSets { sets })
+    }
+}
+
+impl ToValue for SelectionSets {
+    fn to_value(&self) -> Value {
+        Value::Map(self.sets.iter().map(|(name, set)| {
+            let set = set.iter().map(|&(ty, ref objs)| {
+                let refs = objs.iter().map(|&obj| Value::Reference(ty, obj)).collect();
+                (ty.0.to_string(), Value::List(refs))
+            }).collect();
+            (name.clone(), Value::Map(set))
+        }).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+    const LINE: Type = Type("line");
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        editor.register::<u32>(LINE);
+        for i in 0..4u32 { editor.insert(POINT, &i).unwrap(); }
+        editor.insert(LINE, &0u32).unwrap();
+        editor
+    }
+
+    #[test]
+    fn save_and_recall() {
+        let mut editor = editor();
+        let mut sets = SelectionSets::new();
+        editor.select_multiple(POINT, &[Object(2), Object(0)]).unwrap();
+        sets.save("a", &editor, &[POINT]);
+        editor.select_none(POINT).unwrap();
+        editor.select(POINT, Object(1)).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+
+        sets.recall("a", &mut editor, SelectMode::Add).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1), Object(2), Object(0)]);
+        sets.recall("a", &mut editor, SelectMode::Replace).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(2), Object(0)]);
+        assert_eq!(editor.selected(LINE), Some(Object(0)));
+        assert!(sets.recall("b", &mut editor, SelectMode::Replace).is_err());
+        assert_eq!(sets.names(), vec!["a"]);
+        assert!(sets.remove("a"));
+        assert!(sets.get("a").is_none());
+    }
+
+    #[test]
+    fn recall_stale() {
+        let mut editor = editor();
+        let mut sets = SelectionSets::new();
+        editor.select_multiple(POINT, &[Object(0), Object(3)]).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+        sets.save("a", &editor, &[LINE, POINT]);
+        editor.select_none(POINT).unwrap();
+        editor.select(POINT, Object(1)).unwrap();
+        // Delete without telling the sets, such that the set refers to a missing object.
+        editor.delete(POINT, Object(3)).unwrap();
+        match sets.recall("a", &mut editor, SelectMode::Replace) {
+            Err(EditorError::StaleObject(POINT, Object(3))) => {}
+            _ => panic!("expected a stale object error"),
+        }
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1)]);
+        assert_eq!(editor.multiple_selected(LINE), vec![Object(0)]);
+    }
+
+    #[test]
+    fn deleted() {
+        let mut sets = SelectionSets::new();
+        let mut editor = editor();
+        editor.select_multiple(POINT, &[Object(0), Object(3)]).unwrap();
+        sets.save("a", &editor, &[POINT]);
+        let moved = editor.delete(POINT, Object(0)).unwrap();
+        sets.deleted(POINT, Object(0), moved);
+        assert_eq!(sets.get("a").unwrap(), &[(POINT, vec![Object(0)])]);
+    }
+
+    #[test]
+    fn values() {
+        let mut types = TypeRegistry::new();
+        types.register::<u32>(POINT);
+        types.register::<u32>(LINE);
+        let mut sets = SelectionSets::new();
+        let mut editor = editor();
+        editor.select_multiple(POINT, &[Object(1), Object(2)]).unwrap();
+        editor.select(LINE, Object(0)).unwrap();
+        sets.save("a", &editor, &[POINT, LINE]);
+        sets.save("b", &editor, &[]);
+        editor.select_none(LINE).unwrap();
+        sets.save("c", &editor, &[LINE]);
+        let sets = SelectionSets::from_value(&sets.to_value(), &types).unwrap();
+        assert_eq!(sets.get("a").unwrap(), &[
+            (LINE, vec![Object(0)]),
+            (POINT, vec![Object(1), Object(2)]),
+        ]);
+        assert_eq!(sets.get("b").unwrap(), &[]);
+        assert_eq!(sets.get("c").unwrap(), &[(LINE, vec![])]);
+
+        // The type without selected objects is kept, so its selection is cleared.
+        editor.select(LINE, Object(0)).unwrap();
+        sets.recall("c", &mut editor, SelectMode::Replace).unwrap();
+        assert!(editor.multiple_selected(LINE).is_empty());
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1), Object(2)]);
+
+        assert!(SelectionSets::from_value(&Value::Int(0), &types).is_err());
+        assert!(SelectionSets::from_value(&sets.to_value(), &TypeRegistry::new()).is_err());
+        let wrong_type = Value::map(vec![("a", Value::map(vec![
+            ("line", Value::List(vec![Value::Reference(POINT, Object(0))])),
+        ]))]);
+        assert!(SelectionSets::from_value(&wrong_type, &types).is_err());
+    }
+}
@@ -0,0 +1,268 @@
+use std::any::Any;
+
+use error::type_name_of;
+use {EditorError, Object, Type};
+
+const INDEX_BITS: u32 = usize::BITS / 2;
+const INDEX_MASK: usize = (1 << INDEX_BITS) - 1;
+const MAX_GENERATION: usize = usize::MAX >> INDEX_BITS;
+
+/// A generational object handle.
+///
+/// The generation is increased every time a slot is reused,
+/// such that handles to deleted objects can be detected.
+/// A handle is packed into an `Object` with the index in the lower half
+/// of the bits and the generation in the upper half.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Handle {
+    /// The slot index.
+    pub index: usize,
+    /// The generation of the slot.
+    pub generation: usize,
+}
+
+impl From<Handle> for Object {
+    fn from(handle: Handle) -> Object {
+        Object((handle.generation << INDEX_BITS) | (handle.index & INDEX_MASK))
+    }
+}
+
+impl From<Object> for Handle {
+    fn from(obj: Object) -> Handle {
+        Handle {
+            index: obj.0 & INDEX_MASK,
+            generation: obj.0 >> INDEX_BITS,
+        }
+    }
+}
+
+struct Slot<T> {
+    generation: usize,
+    value: Option<T>,
+}
+
+/// Stores objects in slots that are reused with a new generation.
+///
+/// Unlike a `Vec` with swap-remove, deleting an object never moves
+/// another object, and ids of deleted objects never point to other objects.
+/// Use `Object::from(handle)` and `Handle::from(obj)` to convert ids.
+///
+/// `insert_any`, `delete`, `update`, `replace`, `get` and `all` are helpers
+/// for implementing the `Editor` methods of the same names.
+pub struct SlotMap<T> {
+    slots: Vec<Slot<T>>,
+    free: Vec<usize>,
+    len: usize,
+}
+
+impl<T> Default for SlotMap<T> {
+    fn default() -> SlotMap<T> {
+        SlotMap {
+            slots: vec![],
+            free: vec![],
+            len: 0,
+        }
+    }
+}
+
+impl<T> SlotMap<T> {
+    /// Creates a new empty slot map.
+    pub fn new() -> SlotMap<T> {
+        SlotMap::default()
+    }
+
+    /// Returns the number of objects.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Returns `true` if there are no objects.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Returns `true` if the object exists.
+    pub fn contains(&self, obj: Object) -> bool {
+        self.value(obj).is_some()
+    }
+
+    /// Inserts a new object.
+    ///
+    /// Panics when every slot index is in use,
+    /// see `insert_any` for an `Editor::insert` helper returning an error instead.
+    pub fn insert(&mut self, val: T) -> Object {
+        self.try_insert(val).expect("too many slots")
+    }
+
+    fn try_insert(&mut self, val: T) -> Option<Object> {
+        let obj = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index];
+                slot.value = Some(val);
+                Handle { index, generation: slot.generation }.into()
+            }
+            None => {
+                let index = self.slots.len();
+                if index > INDEX_MASK { return None; }
+                self.slots.push(Slot { generation: 0, value: Some(val) });
+                Handle { index, generation: 0 }.into()
+            }
+        };
+        self.len += 1;
+        Some(obj)
+    }
+
+    /// Removes an object, returning its value.
+    pub fn remove(&mut self, obj: Object) -> Option<T> {
+        let Handle { index, generation } = obj.into();
+        let slot = match self.slots.get_mut(index) {
+            Some(slot) if slot.generation == generation => slot,
+            _ => return None,
+        };
+        let val = slot.value.take()?;
+        self.len -= 1;
+        // Retire the slot when the generation runs out,
+        // to keep old handles from becoming valid again.
+        if slot.generation < MAX_GENERATION {
+            slot.generation += 1;
+            self.free.push(index);
+        }
+        Some(val)
+    }
+
+    /// Gets the value of an object.
+    pub fn value(&self, obj: Object) -> Option<&T> {
+        let Handle { index, generation } = obj.into();
+        match self.slots.get(index) {
+            Some(slot) if slot.generation == generation => slot.value.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Gets the mutable value of an object.
+    pub fn value_mut(&mut self, obj: Object) -> Option<&mut T> {
+        let Handle { index, generation } = obj.into();
+        match self.slots.get_mut(index) {
+            Some(slot) if slot.generation == generation => slot.value.as_mut(),
+            _ => None,
+        }
+    }
+
+    /// A helper function for `Editor::delete` implementation.
+    ///
+    /// Never moves other objects, so there are no references to update.
+    pub fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        match self.remove(obj) {
+            None => Err(EditorError::StaleObject(ty, obj)),
+            Some(_) => Ok(None),
+        }
+    }
+
+    /// A helper function for `Editor::all` implementation.
+    pub fn all(&self) -> Vec<Object> {
+        self.slots.iter().enumerate()
+            .filter(|&(_, slot)| slot.value.is_some())
+            .map(|(index, slot)| Handle { index, generation: slot.generation }.into())
+            .collect()
+    }
+}
+
+impl<T: Any> SlotMap<T> {
+    /// A helper function for `Editor::get` implementation.
+    pub fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        match self.value(obj) {
+            None => Err(EditorError::StaleObject(ty, obj)),
+            Some(val) => Ok(val),
+        }
+    }
+}
+
+impl<T: Any + Clone> SlotMap<T> {
+    /// A helper function for `Editor::insert` implementation.
+    ///
+    /// Returns `EditorError::ConstraintViolation` when every slot index is in use.
+    pub fn insert_any(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let val = match args.downcast_ref::<T>() {
+            None => { return Err(EditorError::downcast::<T>(type_name_of(args))); }
+            Some(val) => val
+        };
+        self.try_insert(val.clone()).ok_or_else(|| EditorError::ConstraintViolation(format!(
+            "too many objects of type `{}`", ty.0)))
+    }
+
+    /// A helper function for `Editor::replace` implementation.
+    pub fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        let val = match self.value(to) {
+            None => { return Err(EditorError::StaleObject(ty, to)); }
+            Some(val) => val.clone()
+        };
+        match self.value_mut(from) {
+            None => Err(EditorError::StaleObject(ty, from)),
+            Some(item) => {
+                *item = val;
+                Ok(())
+            }
+        }
+    }
+
+    /// A helper function for `Editor::update` implementation.
+    pub fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        let val = match args.downcast_ref::<T>() {
+            None => { return Err(EditorError::downcast::<T>(type_name_of(args))); }
+            Some(val) => val
+        };
+        match self.value_mut(obj) {
+            None => Err(EditorError::StaleObject(ty, obj)),
+            Some(item) => {
+                *item = val.clone();
+                Ok(())
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    #[test]
+    fn handles() {
+        let handle = Handle { index: 5, generation: 3 };
+        assert_eq!(Handle::from(Object::from(handle)), handle);
+    }
+
+    #[test]
+    fn reuse_slots() {
+        let mut map = SlotMap::new();
+        let a = map.insert(1u32);
+        let b = map.insert(2u32);
+        assert_eq!(map.remove(a), Some(1));
+        assert_eq!(map.remove(a), None);
+        let c = map.insert(3u32);
+        assert_eq!(Handle::from(c).index, Handle::from(a).index);
+        assert!(!map.contains(a));
+        assert_eq!(map.value(c), Some(&3));
+        assert_eq!(map.all(), vec![c, b]);
+        assert_eq!(map.len(), 2);
+    }
+
+    #[test]
+    fn editor_helpers() {
+        let mut map = SlotMap::<u32>::new();
+        let a = map.insert_any(POINT, &1u32).unwrap();
+        let b = map.insert_any(POINT, &2u32).unwrap();
+        assert!(map.insert_any(POINT, &1.0f64).is_err());
+        map.replace(POINT, a, b).unwrap();
+        assert_eq!(map.get(POINT, a).unwrap().downcast_ref::<u32>(), Some(&2));
+        map.update(POINT, b, &7u32).unwrap();
+        assert_eq!(map.value(b), Some(&7));
+        assert_eq!(map.delete(POINT, a).unwrap(), None);
+        match map.replace(POINT, b, a) {
+            Err(EditorError::StaleObject(POINT, obj)) => assert_eq!(obj, a),
+            _ => panic!("expected a stale object error"),
+        }
+        assert!(map.update(POINT, a, &0u32).is_err());
+    }
+}
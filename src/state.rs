@@ -0,0 +1,194 @@
+use std::collections::HashSet;
+
+use {EditorError, Object, Type};
+
+/// The selection and visibility of one type.
+#[derive(Clone, Debug, Default)]
+struct TypeState {
+    selected: Option<Object>,
+    multiple: Vec<Object>,
+    hidden: HashSet<Object>,
+}
+
+/// Stores the selection, visibility and cursors of an editor,
+/// such that an editor only has to store objects.
+///
+/// Used by `#[derive(Editor)]`, but can also be used to implement `Editor` by hand.
+/// Methods that check objects take the number of objects of the type,
+/// since objects are stored elsewhere.
+/// Every object is visible until hidden with `set_visible`.
+#[derive(Clone, Debug, Default)]
+pub struct EditorState {
+    /// The cursor position in 2D.
+    pub cursor_2d: Option<[f64; 2]>,
+    /// The cursor position in 3D world coordinates.
+    pub cursor_3d: Option<[f64; 3]>,
+    types: Vec<(Type, TypeState)>,
+}
+
+impl EditorState {
+    /// Creates a new state with nothing selected or hidden.
+    pub fn new() -> EditorState {
+        EditorState::default()
+    }
+
+    /// Selects a single object, see `Editor::select`.
+    pub fn select(&mut self, ty: Type, obj: Object, len: usize) -> Result<(), EditorError> {
+        check(ty, obj, len)?;
+        let state = self.state_mut(ty);
+        state.selected = Some(obj);
+        if !state.multiple.contains(&obj) { state.multiple = vec![obj]; }
+        Ok(())
+    }
+
+    /// Adds objects to the selection, see `Editor::select_multiple`.
+    pub fn select_multiple(&mut self, ty: Type, objs: &[Object], len: usize)
+    -> Result<(), EditorError> {
+        for &obj in objs { check(ty, obj, len)?; }
+        let state = self.state_mut(ty);
+        for &obj in objs {
+            if !state.multiple.contains(&obj) { state.multiple.push(obj); }
+        }
+        if state.selected.is_none() { state.selected = state.multiple.first().cloned(); }
+        Ok(())
+    }
+
+    /// Removes objects from the selection, see `Editor::deselect_multiple`.
+    pub fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) {
+        let state = self.state_mut(ty);
+        state.multiple.retain(|obj| !objs.contains(obj));
+        if let Some(obj) = state.selected {
+            if objs.contains(&obj) { state.selected = state.multiple.first().cloned(); }
+        }
+    }
+
+    /// Deselects everything of a type.
+    pub fn select_none(&mut self, ty: Type) {
+        let state = self.state_mut(ty);
+        state.selected = None;
+        state.multiple.clear();
+    }
+
+    /// Gets the selected object of a type.
+    pub fn selected(&self, ty: Type) -> Option<Object> {
+        self.state(ty).and_then(|state| state.selected)
+    }
+
+    /// Gets the multiple selected objects of a type.
+    pub fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.state(ty).map(|state| state.multiple.clone()).unwrap_or_default()
+    }
+
+    /// Shows or hides an object.
+    pub fn set_visible(&mut self, ty: Type, obj: Object, visible: bool, len: usize)
+    -> Result<(), EditorError> {
+        check(ty, obj, len)?;
+        let state = self.state_mut(ty);
+        if visible { state.hidden.remove(&obj); } else { state.hidden.insert(obj); }
+        Ok(())
+    }
+
+    /// Gets the visible objects of a type.
+    pub fn visible(&self, ty: Type, len: usize) -> Vec<Object> {
+        (0..len).map(Object).filter(|&obj| self.is_visible(ty, obj)).collect()
+    }
+
+    /// Returns `true` if an object is not hidden.
+    pub fn is_visible(&self, ty: Type, obj: Object) -> bool {
+        self.state(ty).map(|state| !state.hidden.contains(&obj)).unwrap_or(true)
+    }
+
+    /// Shows all objects of a type.
+    pub fn show_all(&mut self, ty: Type) {
+        self.state_mut(ty).hidden.clear();
+    }
+
+    /// Deselects and shows all objects of a type, for example after removing them.
+    pub fn clear(&mut self, ty: Type) {
+        self.types.retain(|&(t, _)| t != ty);
+    }
+
+    /// Updates selection and visibility after deleting an object with swap-remove,
+    /// see `Editor::delete`.
+    pub fn deleted(&mut self, ty: Type, obj: Object, moved: Option<Object>) {
+        let state = self.state_mut(ty);
+        if state.selected == Some(obj) { state.selected = None; }
+        state.multiple.retain(|&o| o != obj);
+        state.hidden.remove(&obj);
+        if let Some(last) = moved {
+            if state.selected == Some(last) { state.selected = Some(obj); }
+            for o in &mut state.multiple {
+                if *o == last { *o = obj; }
+            }
+            if state.hidden.remove(&last) { state.hidden.insert(obj); }
+        }
+        if state.selected.is_none() { state.selected = state.multiple.first().cloned(); }
+    }
+
+    fn state(&self, ty: Type) -> Option<&TypeState> {
+        self.types.iter().find(|&&(t, _)| t == ty).map(|(_, state)| state)
+    }
+
+    fn state_mut(&mut self, ty: Type) -> &mut TypeState {
+        let i = match self.types.iter().position(|&(t, _)| t == ty) {
+            Some(i) => i,
+            None => {
+                self.types.push((ty, TypeState::default()));
+                self.types.len() - 1
+            }
+        };
+        &mut self.types[i].1
+    }
+}
+
+fn check(ty: Type, obj: Object, len: usize) -> Result<(), EditorError> {
+    if obj.0 < len { Ok(()) } else { Err(EditorError::StaleObject(ty, obj)) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    #[test]
+    fn selection() {
+        let mut state = EditorState::new();
+        assert!(state.select(POINT, Object(3), 3).is_err());
+        state.select_multiple(POINT, &[Object(2), Object(0)], 3).unwrap();
+        assert_eq!(state.selected(POINT), Some(Object(2)));
+        state.select(POINT, Object(0), 3).unwrap();
+        assert_eq!(state.multiple_selected(POINT), vec![Object(2), Object(0)]);
+        state.deselect_multiple(POINT, &[Object(0)]);
+        assert_eq!(state.selected(POINT), Some(Object(2)));
+        state.select_none(POINT);
+        assert_eq!(state.selected(POINT), None);
+        assert!(state.multiple_selected(POINT).is_empty());
+    }
+
+    #[test]
+    fn deleted() {
+        let mut state = EditorState::new();
+        state.select_multiple(POINT, &[Object(0), Object(3)], 4).unwrap();
+        state.set_visible(POINT, Object(3), false, 4).unwrap();
+        state.deleted(POINT, Object(0), Some(Object(3)));
+        assert_eq!(state.selected(POINT), Some(Object(0)));
+        assert_eq!(state.multiple_selected(POINT), vec![Object(0)]);
+        assert_eq!(state.visible(POINT, 3), vec![Object(1), Object(2)]);
+    }
+
+    #[test]
+    fn visibility() {
+        let mut state = EditorState::new();
+        assert!(state.set_visible(POINT, Object(2), false, 2).is_err());
+        state.set_visible(POINT, Object(1), false, 2).unwrap();
+        assert!(!state.is_visible(POINT, Object(1)));
+        state.show_all(POINT);
+        assert_eq!(state.visible(POINT, 2), vec![Object(0), Object(1)]);
+        state.set_visible(POINT, Object(0), false, 2).unwrap();
+        state.select(POINT, Object(1), 2).unwrap();
+        state.clear(POINT);
+        assert!(state.is_visible(POINT, Object(0)));
+        assert_eq!(state.selected(POINT), None);
+    }
+}
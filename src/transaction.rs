@@ -0,0 +1,263 @@
+use std::any::Any;
+
+use undo::{undo_changes, Change, Recorder};
+use {Bounds2, Cloner, Editor, EditorError, Hit, Object, Plane, Type, View, ViewId};
+
+/// Wraps an editor and records changes such that they can be rolled back.
+///
+/// A transaction has exclusive access to the editor while it lives,
+/// so concurrent actions are not possible.
+/// Changes to types not registered in the cloner are refused
+/// with `EditorError::UnknownType`, since they can not be rolled back.
+///
+/// Use `Transaction::run` to roll back automatically when an action fails.
+pub struct Transaction<'a> {
+    editor: &'a mut dyn Editor,
+    cloner: &'a Cloner,
+    changes: Vec<Change>,
+}
+
+impl<'a> Transaction<'a> {
+    /// Starts a new transaction.
+    pub fn new(editor: &'a mut dyn Editor, cloner: &'a Cloner) -> Transaction<'a> {
+        Transaction {
+            editor,
+            cloner,
+            changes: vec![],
+        }
+    }
+
+    /// Runs an action inside a transaction.
+    ///
+    /// If the action returns an error, every change made so far is reverted
+    /// before the error is returned.
+    /// If the rollback fails, the rollback error is returned instead,
+    /// because the editor state can no longer be trusted.
+    pub fn run<T, F>(editor: &'a mut dyn Editor, cloner: &'a Cloner, f: F)
+    -> Result<T, EditorError>
+        where F: FnOnce(&mut dyn Editor) -> Result<T, EditorError>
+    {
+        let mut transaction = Transaction::new(editor, cloner);
+        match f(&mut transaction) {
+            Ok(val) => Ok(val),
+            Err(err) => {
+                transaction.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns `true` if no changes have been made.
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Keeps the changes.
+    pub fn commit(self) {}
+
+    /// Reverts every change made in the transaction, last first.
+    pub fn rollback(mut self) -> Result<(), EditorError> {
+        undo_changes(&mut self.changes, self.editor, &mut vec![])
+    }
+
+    /// Keeps the changes and returns them, for use in the history.
+    pub(crate) fn into_changes(self) -> Vec<Change> {
+        self.changes
+    }
+
+    fn recorder(&self) -> Recorder<'a> {
+        Recorder { cloner: self.cloner }
+    }
+}
+
+impl<'a> Editor for Transaction<'a> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.editor.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.editor.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.editor.hit_2d(pos)
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.editor.hit_rect_2d(rect)
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.editor.hit_polygon_2d(points)
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.editor.hit_3d(pos)
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.editor.hit_frustum(planes)
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.editor.hit_ray(origin, direction)
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        let change = self.recorder().select(self.editor, ty, |e| e.select(ty, obj))?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        let change = self.recorder()
+            .select(self.editor, ty, |e| e.select_multiple(ty, objs))?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        let change = self.recorder()
+            .select(self.editor, ty, |e| e.deselect_multiple(ty, objs))?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        let change = self.recorder().select(self.editor, ty, |e| e.select_none(ty))?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let (obj, change) = self.recorder().insert(self.editor, ty, args)?;
+        self.changes.push(change);
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        let (moved, change) = self.recorder().delete(self.editor, ty, obj)?;
+        self.changes.push(change);
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        let change = self.recorder().update(self.editor, ty, obj, args)?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        let change = self.recorder().replace(self.editor, ty, from, to)?;
+        self.changes.push(change);
+        Ok(())
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.editor.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.editor.visible(ty)
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.editor.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.editor.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.editor.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.editor.navigate_to(ty, obj)
+    }
+
+    fn refresh_views(&mut self) {
+        self.editor.refresh_views()
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.editor.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.editor.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.editor.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.editor.set_active_view(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        for i in 0..3u32 { editor.insert(POINT, &i).unwrap(); }
+        editor
+    }
+
+    fn values(editor: &VecEditor) -> Vec<u32> {
+        editor.items::<u32>(POINT).unwrap().to_vec()
+    }
+
+    #[test]
+    fn commit() {
+        let mut editor = editor();
+        let cloner = editor.cloner().clone();
+        Transaction::run(&mut editor, &cloner, |e| {
+            e.delete(POINT, Object(0))?;
+            e.update(POINT, Object(0), &7u32)
+        }).unwrap();
+        assert_eq!(values(&editor), vec![7, 1]);
+    }
+
+    #[test]
+    fn rollback_on_error() {
+        let mut editor = editor();
+        editor.select_multiple(POINT, &[Object(1), Object(2)]).unwrap();
+        let cloner = editor.cloner().clone();
+        let res = Transaction::run(&mut editor, &cloner, |e| {
+            e.select_none(POINT)?;
+            e.delete(POINT, Object(0))?;
+            e.replace(POINT, Object(1), Object(0))?;
+            e.insert(POINT, &9u32)?;
+            e.update(POINT, Object(5), &0u32)
+        });
+        match res {
+            Err(EditorError::StaleObject(POINT, Object(5))) => {}
+            _ => panic!("expected a stale object error"),
+        }
+        assert_eq!(values(&editor), vec![0, 1, 2]);
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1), Object(2)]);
+    }
+
+    #[test]
+    fn refuse_unregistered() {
+        let mut editor = editor();
+        let cloner = Cloner::new();
+        let mut transaction = Transaction::new(&mut editor, &cloner);
+        match transaction.insert(POINT, &3u32) {
+            Err(EditorError::UnknownType(POINT)) => {}
+            _ => panic!("expected an unknown type error"),
+        }
+        assert!(transaction.is_empty());
+        transaction.rollback().unwrap();
+        assert_eq!(values(&editor), vec![0, 1, 2]);
+    }
+}
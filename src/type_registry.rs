@@ -0,0 +1,452 @@
+use std::any::{Any, TypeId};
+use std::marker::PhantomData;
+
+use error::downcast;
+use {Cloner, Editor, EditorError, FromValue, Object, ToValue, Type, Value};
+
+/// Describes how a field should be presented and edited.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FieldKind {
+    /// A boolean, usually `bool`.
+    Bool,
+    /// An integer, usually `i64`.
+    Int,
+    /// A floating point number, usually `f64`.
+    Float,
+    /// A text, usually `String`.
+    String,
+    /// A 2D vector, usually `[f64; 2]`.
+    Vec2,
+    /// A 3D vector, usually `[f64; 3]`.
+    Vec3,
+    /// A color, usually `[f32; 4]` in RGBA.
+    Color,
+    /// A reference to an object of another type, stored as `Object`.
+    Reference(Type),
+}
+
+/// Accesses a field of an object with the Rust types erased.
+trait Accessor {
+    fn get<'a>(&self, val: &'a dyn Any) -> Result<&'a dyn Any, EditorError>;
+    fn set(&self, val: &dyn Any, field: &dyn Any) -> Result<Box<dyn Any>, EditorError>;
+}
+
+struct Lens<T, F> {
+    get: fn(&T) -> &F,
+    get_mut: fn(&mut T) -> &mut F,
+}
+
+impl<T: Any + Clone, F: Any + Clone> Accessor for Lens<T, F> {
+    fn get<'a>(&self, val: &'a dyn Any) -> Result<&'a dyn Any, EditorError> {
+        Ok((self.get)(downcast::<T>(val)?))
+    }
+
+    fn set(&self, val: &dyn Any, field: &dyn Any) -> Result<Box<dyn Any>, EditorError> {
+        let field = downcast::<F>(field)?;
+        let mut val = downcast::<T>(val)?.clone();
+        *(self.get_mut)(&mut val) = field.clone();
+        Ok(Box::new(val))
+    }
+}
+
+/// Describes a field of a type.
+pub struct Field {
+    name: &'static str,
+    kind: FieldKind,
+    default: Option<Box<dyn Any>>,
+    range: Option<[f64; 2]>,
+    accessor: Box<dyn Accessor>,
+}
+
+impl Field {
+    /// Gets the name of the field.
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Gets the kind of the field.
+    pub fn kind(&self) -> FieldKind {
+        self.kind
+    }
+
+    /// Gets the default value of the field.
+    pub fn default(&self) -> Option<&dyn Any> {
+        self.default.as_deref()
+    }
+
+    /// Gets the minimum and maximum value of a numeric field.
+    pub fn range(&self) -> Option<[f64; 2]> {
+        self.range
+    }
+
+    /// Gets the value of the field from an object value.
+    pub fn get<'a>(&self, val: &'a dyn Any) -> Result<&'a dyn Any, EditorError> {
+        self.accessor.get(val)
+    }
+
+    /// Returns a copy of an object value with the field set.
+    ///
+    /// Numeric values outside the range of the field, including NaN,
+    /// are refused with `EditorError::ConstraintViolation`.
+    pub fn set(&self, val: &dyn Any, field: &dyn Any) -> Result<Box<dyn Any>, EditorError> {
+        if let (Some([min, max]), Some(x)) = (self.range, as_f64(field)) {
+            if x.is_nan() || x < min || x > max {
+                return Err(EditorError::ConstraintViolation(format!(
+                    "`{}` must be in range {}..{}, found {}", self.name, min, max, x
+                )));
+            }
+        }
+        self.accessor.set(val, field)
+    }
+}
+
+type ToValueFn = fn(&dyn Any) -> Result<Value, EditorError>;
+type FromValueFn = fn(&Value) -> Result<Box<dyn Any>, EditorError>;
+
+/// Describes the fields of a type.
+pub struct Schema {
+    ty: Type,
+    type_name: &'static str,
+    fields: Vec<Field>,
+    to_value: Option<ToValueFn>,
+    from_value: Option<FromValueFn>,
+}
+
+impl Schema {
+    /// Gets the type described by the schema.
+    pub fn ty(&self) -> Type {
+        self.ty
+    }
+
+    /// Gets the name of the Rust type used to store objects.
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+
+    /// Gets the fields, in the order they were added.
+    pub fn fields(&self) -> &[Field] {
+        &self.fields
+    }
+
+    /// Gets a field by name.
+    pub fn field(&self, name: &str) -> Option<&Field> {
+        self.fields.iter().find(|field| field.name == name)
+    }
+
+    /// Returns `true` if objects can be converted to and from values.
+    pub fn has_values(&self) -> bool {
+        self.to_value.is_some() && self.from_value.is_some()
+    }
+
+    /// Gets the types referenced by fields of this type.
+    pub fn references(&self) -> Vec<Type> {
+        let mut res = vec![];
+        for field in &self.fields {
+            if let FieldKind::Reference(ty) = field.kind {
+                if !res.contains(&ty) { res.push(ty); }
+            }
+        }
+        res
+    }
+}
+
+/// Adds fields to a schema of objects stored as `T`.
+///
+/// Created by `TypeRegistry::register`.
+/// `default` and `range` apply to the last added field, which is stored as `F`.
+pub struct SchemaBuilder<'a, T, F = ()> {
+    schema: &'a mut Schema,
+    _t: PhantomData<(T, F)>,
+}
+
+impl<'a, T: Any + Clone, F: Any> SchemaBuilder<'a, T, F> {
+    /// Adds a field, accessed through a shared and a mutable getter.
+    ///
+    /// For example, `.field("x", FieldKind::Float, |p| &p.x, |p| &mut p.x)`.
+    pub fn field<G: Any + Clone>(
+        self,
+        name: &'static str,
+        kind: FieldKind,
+        get: fn(&T) -> &G,
+        get_mut: fn(&mut T) -> &mut G
+    ) -> SchemaBuilder<'a, T, G> {
+        self.schema.fields.retain(|field| field.name != name);
+        self.schema.fields.push(Field {
+            name,
+            kind,
+            default: None,
+            range: None,
+            accessor: Box::new(Lens { get, get_mut }),
+        });
+        SchemaBuilder { schema: self.schema, _t: PhantomData }
+    }
+
+    /// Sets the default value of the last added field.
+    pub fn default(self, val: F) -> Self {
+        if let Some(field) = self.schema.fields.last_mut() {
+            field.default = Some(Box::new(val));
+        }
+        self
+    }
+
+    /// Sets the minimum and maximum value of the last added field.
+    ///
+    /// Panics if the field is not stored as a primitive number type.
+    pub fn range(self, min: f64, max: f64) -> Self {
+        let field = match self.schema.fields.last_mut() {
+            Some(field) if is_number(TypeId::of::<F>()) => field,
+            _ => panic!("a range can only be set on a field stored as a number, found `{}`",
+                ::std::any::type_name::<F>()),
+        };
+        field.range = Some([min, max]);
+        self
+    }
+
+    /// Converts objects to and from values using `ToValue` and `FromValue`.
+    pub fn values(self) -> Self
+        where T: ToValue + FromValue
+    {
+        self.schema.to_value = Some(to_value::<T>);
+        self.schema.from_value = Some(from_value::<T>);
+        self
+    }
+}
+
+/// Keeps the schema of every type,
+/// such that objects can be inspected and edited field by field
+/// without knowing the Rust type.
+#[derive(Default)]
+pub struct TypeRegistry {
+    schemas: Vec<Schema>,
+    cloner: Cloner,
+}
+
+impl TypeRegistry {
+    /// Creates a new empty registry.
+    pub fn new() -> TypeRegistry {
+        TypeRegistry::default()
+    }
+
+    /// Registers a type stored as `T`, returning a builder to add fields.
+    ///
+    /// Registering a type again replaces its schema.
+    pub fn register<T: Any + Clone>(&mut self, ty: Type) -> SchemaBuilder<'_, T> {
+        let schema = Schema {
+            ty,
+            type_name: ::std::any::type_name::<T>(),
+            fields: vec![],
+            to_value: None,
+            from_value: None,
+        };
+        let i = match self.schemas.iter().position(|schema| schema.ty == ty) {
+            Some(i) => {
+                self.schemas[i] = schema;
+                i
+            }
+            None => {
+                self.schemas.push(schema);
+                self.schemas.len() - 1
+            }
+        };
+        self.cloner.register::<T>(ty);
+        SchemaBuilder { schema: &mut self.schemas[i], _t: PhantomData }
+    }
+
+    /// Gets the registered types, in the order they were registered.
+    pub fn types(&self) -> Vec<Type> {
+        self.schemas.iter().map(|schema| schema.ty).collect()
+    }
+
+    /// Gets the schema of a type.
+    pub fn schema(&self, ty: Type) -> Result<&Schema, EditorError> {
+        self.schemas.iter().find(|schema| schema.ty == ty).ok_or(EditorError::UnknownType(ty))
+    }
+
+    /// Finds a registered type by name.
+    pub fn find(&self, name: &str) -> Option<Type> {
+        self.schemas.iter().map(|schema| schema.ty).find(|ty| ty.0 == name)
+    }
+
+    /// Converts an object value of a type to a value.
+    pub fn to_value(&self, ty: Type, val: &dyn Any) -> Result<Value, EditorError> {
+        match self.schema(ty)?.to_value {
+            None => Err(no_values(ty)),
+            Some(f) => f(val),
+        }
+    }
+
+    /// Converts a value to an object value of a type,
+    /// for use with `Editor::insert` and `Editor::update`.
+    pub fn from_value(&self, ty: Type, val: &Value) -> Result<Box<dyn Any>, EditorError> {
+        match self.schema(ty)?.from_value {
+            None => Err(no_values(ty)),
+            Some(f) => f(val),
+        }
+    }
+
+    /// Gets a cloner for the registered types.
+    pub fn cloner(&self) -> &Cloner {
+        &self.cloner
+    }
+
+    /// Gets the value of a field of an object.
+    pub fn get_field<'a>(&self, editor: &'a dyn Editor, ty: Type, obj: Object, name: &str)
+    -> Result<&'a dyn Any, EditorError> {
+        let field = self.field(ty, name)?;
+        field.get(editor.get(ty, obj)?)
+    }
+
+    /// Sets the value of a field of an object.
+    pub fn set_field(
+        &self,
+        editor: &mut dyn Editor,
+        ty: Type,
+        obj: Object,
+        name: &str,
+        val: &dyn Any
+    ) -> Result<(), EditorError> {
+        let field = self.field(ty, name)?;
+        let new_val = field.set(editor.get(ty, obj)?, val)?;
+        editor.update(ty, obj, &*new_val)
+    }
+
+    /// Sets a field of an object to its default value.
+    pub fn reset_field(&self, editor: &mut dyn Editor, ty: Type, obj: Object, name: &str)
+    -> Result<(), EditorError> {
+        let field = self.field(ty, name)?;
+        match field.default() {
+            None => Err(EditorError::ConstraintViolation(
+                format!("`{}` has no default value", name)
+            )),
+            Some(default) => {
+                let new_val = field.set(editor.get(ty, obj)?, default)?;
+                editor.update(ty, obj, &*new_val)
+            }
+        }
+    }
+
+    fn field(&self, ty: Type, name: &str) -> Result<&Field, EditorError> {
+        self.schema(ty)?.field(name).ok_or_else(|| EditorError::ConstraintViolation(
+            format!("`{}` has no field `{}`", ty.0, name)
+        ))
+    }
+}
+
+fn to_value<T: Any + ToValue>(val: &dyn Any) -> Result<Value, EditorError> {
+    Ok(downcast::<T>(val)?.to_value())
+}
+
+fn from_value<T: Any + FromValue>(val: &Value) -> Result<Box<dyn Any>, EditorError> {
+    Ok(Box::new(T::from_value(val)?))
+}
+
+pub(crate) fn no_values(ty: Type) -> EditorError {
+    EditorError::ConstraintViolation(format!("`{}` can not be converted to values", ty.0))
+}
+
+/// Converts a value of any primitive number type, used to check ranges.
+fn as_f64(val: &dyn Any) -> Option<f64> {
+    macro_rules! check {
+        ($($t:ty),*) => {
+            $(if let Some(&x) = val.downcast_ref::<$t>() { return Some(x as f64); })*
+        }
+    }
+    check!(f64, f32, i64, i32, i16, i8, isize, u64, u32, u16, u8, usize);
+    None
+}
+
+/// Returns `true` for the primitive number types supported by `as_f64`.
+fn is_number(id: TypeId) -> bool {
+    macro_rules! ids {
+        ($($t:ty),*) => { [$(TypeId::of::<$t>()),*] }
+    }
+    ids!(f64, f32, i64, i32, i16, i8, isize, u64, u32, u16, u8, usize).contains(&id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const NODE: Type = Type("node");
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Node {
+        x: f64,
+        level: u8,
+        parent: Object,
+    }
+
+    fn registry() -> TypeRegistry {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Node>(NODE)
+            .field("x", FieldKind::Float, |n| &n.x, |n| &mut n.x).default(1.5).range(-1e9, 1e9)
+            .field("level", FieldKind::Int, |n| &n.level, |n| &mut n.level).range(0.0, 10.0)
+            .field("parent", FieldKind::Reference(NODE), |n| &n.parent, |n| &mut n.parent);
+        registry
+    }
+
+    fn editor() -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<Node>(NODE);
+        editor.insert(NODE, &Node { x: 0.0, level: 2, parent: Object(0) }).unwrap();
+        editor
+    }
+
+    #[test]
+    fn schema() {
+        let registry = registry();
+        let schema = registry.schema(NODE).unwrap();
+        let names: Vec<&str> = schema.fields().iter().map(|f| f.name()).collect();
+        assert_eq!(names, vec!["x", "level", "parent"]);
+        assert_eq!(schema.references(), vec![NODE]);
+        assert!(!schema.has_values());
+        assert_eq!(registry.find("node"), Some(NODE));
+        assert!(registry.to_value(NODE, &0u32).is_err());
+    }
+
+    #[test]
+    fn fields() {
+        let registry = registry();
+        let mut editor = editor();
+        registry.set_field(&mut editor, NODE, Object(0), "x", &3.0f64).unwrap();
+        let x = registry.get_field(&editor, NODE, Object(0), "x").unwrap();
+        assert_eq!(x.downcast_ref::<f64>(), Some(&3.0));
+        registry.reset_field(&mut editor, NODE, Object(0), "x").unwrap();
+        assert_eq!(editor.items::<Node>(NODE).unwrap()[0].x, 1.5);
+        assert!(registry.reset_field(&mut editor, NODE, Object(0), "level").is_err());
+        assert!(registry.set_field(&mut editor, NODE, Object(0), "x", &3u8).is_err());
+        assert!(registry.set_field(&mut editor, NODE, Object(0), "y", &3.0f64).is_err());
+    }
+
+    #[test]
+    fn ranges() {
+        let registry = registry();
+        let mut editor = editor();
+        registry.set_field(&mut editor, NODE, Object(0), "level", &10u8).unwrap();
+        match registry.set_field(&mut editor, NODE, Object(0), "level", &11u8) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        assert_eq!(editor.items::<Node>(NODE).unwrap()[0].level, 10);
+        for &x in &[f64::NAN, f64::INFINITY, f64::NEG_INFINITY] {
+            match registry.set_field(&mut editor, NODE, Object(0), "x", &x) {
+                Err(EditorError::ConstraintViolation(_)) => {}
+                _ => panic!("expected a constraint violation"),
+            }
+        }
+        assert_eq!(editor.items::<Node>(NODE).unwrap()[0].x, 0.0);
+        assert_eq!(as_f64(&7usize), Some(7.0));
+        assert_eq!(as_f64(&-7i16), Some(-7.0));
+        assert_eq!(as_f64(&true), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "stored as a number")]
+    fn range_of_non_number() {
+        let mut registry = TypeRegistry::new();
+        registry.register::<Node>(NODE)
+            .field("parent", FieldKind::Reference(NODE), |n| &n.parent, |n| &mut n.parent)
+            .range(0.0, 1.0);
+    }
+}
@@ -0,0 +1,788 @@
+use std::any::Any;
+
+use {Bounds2, Cloner, Editor, EditorError, Hit, Object, Plane, Transaction, Type, View, ViewId};
+
+/// The selection state of a type.
+#[derive(Clone, Debug)]
+pub(crate) struct Selection {
+    selected: Option<Object>,
+    multiple: Vec<Object>,
+}
+
+impl Selection {
+    /// Reads the current selection of a type.
+    pub fn read<E: Editor + ?Sized>(editor: &E, ty: Type) -> Selection {
+        Selection {
+            selected: editor.selected(ty),
+            multiple: editor.multiple_selected(ty),
+        }
+    }
+
+    fn remap(&mut self, ty: Type, remap: &Remap) {
+        if let Some(ref mut obj) = self.selected { remap.apply(ty, obj); }
+        for obj in &mut self.multiple { remap.apply(ty, obj); }
+    }
+
+    /// Restores the selection of a type, keeping the order of the multiple selection.
+    pub fn restore<E: Editor + ?Sized>(&self, editor: &mut E, ty: Type)
+    -> Result<(), EditorError> {
+        editor.select_none(ty)?;
+        match self.selected {
+            Some(obj) if !self.multiple.contains(&obj) => {
+                // Selecting the object replaces the multiple selection, so it goes first.
+                editor.select(ty, obj)?;
+                if !self.multiple.is_empty() { editor.select_multiple(ty, &self.multiple)?; }
+            }
+            selected => {
+                if !self.multiple.is_empty() { editor.select_multiple(ty, &self.multiple)?; }
+                if let Some(obj) = selected {
+                    if editor.selected(ty) != Some(obj) { editor.select(ty, obj)?; }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A recorded change that can be reverted and applied again.
+pub(crate) enum Change {
+    Insert {
+        ty: Type,
+        obj: Object,
+        value: Box<dyn Any>,
+    },
+    Delete {
+        ty: Type,
+        obj: Object,
+        value: Box<dyn Any>,
+        /// The value of the object moved into the place
+        /// of the deleted one by swap-remove.
+        moved: Option<Box<dyn Any>>,
+    },
+    Update {
+        ty: Type,
+        obj: Object,
+        old: Box<dyn Any>,
+        new: Box<dyn Any>,
+    },
+    Replace {
+        ty: Type,
+        from: Object,
+        to: Object,
+        old_from: Box<dyn Any>,
+        old_to: Box<dyn Any>,
+    },
+    Select {
+        ty: Type,
+        before: Selection,
+        after: Selection,
+    },
+}
+
+/// An object id that changed because the object was inserted again.
+///
+/// Editors that do not reuse ids, for example using `SlotMap`,
+/// give a new id when a deleted object is restored,
+/// so other changes referring to the old id must be updated.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Remap {
+    ty: Type,
+    from: Object,
+    to: Object,
+}
+
+impl Remap {
+    fn new(ty: Type, from: Object, to: Object) -> Option<Remap> {
+        if from == to { None } else { Some(Remap { ty, from, to }) }
+    }
+
+    fn apply(&self, ty: Type, obj: &mut Object) {
+        if ty == self.ty && *obj == self.from { *obj = self.to; }
+    }
+}
+
+impl Change {
+    /// Reverts the change.
+    pub fn undo<E: Editor + ?Sized>(&mut self, editor: &mut E)
+    -> Result<Option<Remap>, EditorError> {
+        match *self {
+            Change::Insert { ty, obj, .. } => {
+                editor.delete(ty, obj)?;
+            }
+            Change::Delete { ty, obj, ref value, ref moved } => {
+                match *moved {
+                    None => {
+                        // The deleted object was last, so inserting it again
+                        // puts it back in place.
+                        let new_obj = editor.insert(ty, &**value)?;
+                        return Ok(Remap::new(ty, obj, new_obj));
+                    }
+                    Some(ref moved_value) => {
+                        // Move the object back to the end of the table
+                        // and restore the deleted object in its place.
+                        editor.insert(ty, &**moved_value)?;
+                        editor.update(ty, obj, &**value)?;
+                    }
+                }
+            }
+            Change::Update { ty, obj, ref old, .. } => {
+                editor.update(ty, obj, &**old)?;
+            }
+            Change::Replace { ty, from, to, ref old_from, ref old_to } => {
+                editor.update(ty, from, &**old_from)?;
+                editor.update(ty, to, &**old_to)?;
+            }
+            Change::Select { ty, ref before, .. } => {
+                before.restore(editor, ty)?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Applies the change again after it has been reverted.
+    pub fn redo<E: Editor + ?Sized>(&mut self, editor: &mut E)
+    -> Result<Option<Remap>, EditorError> {
+        match *self {
+            Change::Insert { ty, obj, ref value } => {
+                let new_obj = editor.insert(ty, &**value)?;
+                return Ok(Remap::new(ty, obj, new_obj));
+            }
+            Change::Delete { ty, obj, .. } => {
+                editor.delete(ty, obj)?;
+            }
+            Change::Update { ty, obj, ref new, .. } => {
+                editor.update(ty, obj, &**new)?;
+            }
+            Change::Replace { ty, from, to, .. } => {
+                editor.replace(ty, from, to)?;
+            }
+            Change::Select { ty, ref after, .. } => {
+                after.restore(editor, ty)?;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Updates the object ids of the change.
+    pub fn remap(&mut self, remap: &Remap) {
+        match *self {
+            Change::Insert { ty, ref mut obj, .. } |
+            Change::Delete { ty, ref mut obj, .. } |
+            Change::Update { ty, ref mut obj, .. } => {
+                remap.apply(ty, obj);
+            }
+            Change::Replace { ty, ref mut from, ref mut to, .. } => {
+                remap.apply(ty, from);
+                remap.apply(ty, to);
+            }
+            Change::Select { ty, ref mut before, ref mut after } => {
+                before.remap(ty, remap);
+                after.remap(ty, remap);
+            }
+        }
+    }
+}
+
+/// Reverts a list of changes, last first.
+///
+/// The ids changed so far are pushed to `remaps`, also when an error is returned.
+pub(crate) fn undo_changes<E: Editor + ?Sized>(
+    changes: &mut [Change],
+    editor: &mut E,
+    remaps: &mut Vec<Remap>
+) -> Result<(), EditorError> {
+    for i in (0..changes.len()).rev() {
+        if let Some(remap) = changes[i].undo(editor)? {
+            for change in &mut changes[..=i] {
+                change.remap(&remap);
+            }
+            remaps.push(remap);
+        }
+    }
+    Ok(())
+}
+
+/// Applies a list of changes again, first first.
+///
+/// The ids changed so far are pushed to `remaps`, also when an error is returned.
+pub(crate) fn redo_changes<E: Editor + ?Sized>(
+    changes: &mut [Change],
+    editor: &mut E,
+    remaps: &mut Vec<Remap>
+) -> Result<(), EditorError> {
+    for i in 0..changes.len() {
+        if let Some(remap) = changes[i].redo(editor)? {
+            for change in &mut changes[i..] {
+                change.remap(&remap);
+            }
+            remaps.push(remap);
+        }
+    }
+    Ok(())
+}
+
+/// Records changes to an editor before they are made.
+///
+/// Each method performs the operation on the editor
+/// and returns the change needed to revert it.
+pub(crate) struct Recorder<'a> {
+    pub cloner: &'a Cloner,
+}
+
+impl<'a> Recorder<'a> {
+    pub fn select<E, F>(&self, editor: &mut E, ty: Type, f: F)
+    -> Result<Change, EditorError>
+        where E: Editor + ?Sized, F: FnOnce(&mut E) -> Result<(), EditorError>
+    {
+        let before = Selection::read(editor, ty);
+        f(editor)?;
+        let after = Selection::read(editor, ty);
+        Ok(Change::Select { ty, before, after })
+    }
+
+    pub fn insert<E: Editor + ?Sized>(&self, editor: &mut E, ty: Type, args: &dyn Any)
+    -> Result<(Object, Change), EditorError> {
+        let value = self.cloner.clone_value(ty, args)?;
+        let obj = editor.insert(ty, args)?;
+        Ok((obj, Change::Insert { ty, obj, value }))
+    }
+
+    pub fn delete<E: Editor + ?Sized>(&self, editor: &mut E, ty: Type, obj: Object)
+    -> Result<(Option<Object>, Change), EditorError> {
+        let value = self.cloner.clone_value(ty, editor.get(ty, obj)?)?;
+        let moved = editor.delete(ty, obj)?;
+        let moved_value = match moved {
+            None => None,
+            // The last object now takes the place of the deleted object.
+            Some(_) => Some(self.cloner.clone_value(ty, editor.get(ty, obj)?)?),
+        };
+        Ok((moved, Change::Delete { ty, obj, value, moved: moved_value }))
+    }
+
+    pub fn update<E: Editor + ?Sized>(
+        &self,
+        editor: &mut E,
+        ty: Type,
+        obj: Object,
+        args: &dyn Any
+    ) -> Result<Change, EditorError> {
+        let old = self.cloner.clone_value(ty, editor.get(ty, obj)?)?;
+        let new = self.cloner.clone_value(ty, args)?;
+        editor.update(ty, obj, args)?;
+        Ok(Change::Update { ty, obj, old, new })
+    }
+
+    pub fn replace<E: Editor + ?Sized>(&self, editor: &mut E, ty: Type, from: Object, to: Object)
+    -> Result<Change, EditorError> {
+        let old_from = self.cloner.clone_value(ty, editor.get(ty, from)?)?;
+        let old_to = self.cloner.clone_value(ty, editor.get(ty, to)?)?;
+        editor.replace(ty, from, to)?;
+        Ok(Change::Replace { ty, from, to, old_from, old_to })
+    }
+}
+
+/// A named entry in the history, containing one or more changes.
+struct Entry {
+    name: String,
+    changes: Vec<Change>,
+}
+
+/// Wraps an editor and records changes such that they can be undone.
+///
+/// Every mutating call becomes a history entry named after the operation,
+/// unless it happens between `begin` and `end`,
+/// in which case all changes are grouped into one named entry.
+///
+/// Values are snapshot using `Editor::get`, so the Rust type
+/// of every edited type must be registered with `register`.
+/// Changes to unregistered types are refused with `EditorError::UnknownType`.
+pub struct UndoStack<E: Editor> {
+    editor: E,
+    cloner: Cloner,
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    limit: Option<usize>,
+    group: Option<Entry>,
+    depth: usize,
+}
+
+impl<E: Editor> UndoStack<E> {
+    /// Creates a new undo stack wrapping an editor.
+    pub fn new(editor: E) -> UndoStack<E> {
+        UndoStack::with_cloner(editor, Cloner::new())
+    }
+
+    /// Creates a new undo stack using an existing cloner.
+    pub fn with_cloner(editor: E, cloner: Cloner) -> UndoStack<E> {
+        UndoStack {
+            editor,
+            cloner,
+            undo: vec![],
+            redo: vec![],
+            limit: None,
+            group: None,
+            depth: 0,
+        }
+    }
+
+    /// Registers the Rust type used to store objects of a type.
+    pub fn register<T: Any + Clone>(&mut self, ty: Type) {
+        self.cloner.register::<T>(ty);
+    }
+
+    /// Gets a reference to the wrapped editor.
+    pub fn get_ref(&self) -> &E {
+        &self.editor
+    }
+
+    /// Returns the wrapped editor, dropping the history.
+    pub fn into_inner(self) -> E {
+        self.editor
+    }
+
+    /// Sets the maximum number of entries to keep.
+    /// The oldest entries are dropped first.
+    pub fn set_limit(&mut self, limit: Option<usize>) {
+        self.limit = limit;
+        self.apply_limit();
+    }
+
+    /// Gets the maximum number of entries to keep.
+    pub fn limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    /// Starts a named entry grouping all changes until `end` is called.
+    ///
+    /// Groups can be nested, in which case the outermost name is used.
+    pub fn begin(&mut self, name: &str) {
+        if self.depth == 0 {
+            self.group = Some(Entry { name: name.into(), changes: vec![] });
+        }
+        self.depth += 1;
+    }
+
+    /// Ends a named entry started by `begin`.
+    pub fn end(&mut self) {
+        if self.depth == 0 { return; }
+
+        self.depth -= 1;
+        if self.depth == 0 {
+            if let Some(entry) = self.group.take() {
+                self.push(entry);
+            }
+        }
+    }
+
+    /// Runs an action as a transaction and records it as one named entry.
+    ///
+    /// If the action fails, its changes are rolled back and nothing is recorded.
+    /// See `Transaction::run`.
+    pub fn transaction<T, F>(&mut self, name: &str, f: F) -> Result<T, EditorError>
+        where F: FnOnce(&mut dyn Editor) -> Result<T, EditorError>
+    {
+        let mut transaction = Transaction::new(&mut self.editor, &self.cloner);
+        match f(&mut transaction) {
+            Ok(val) => {
+                let changes = transaction.into_changes();
+                match self.group {
+                    Some(ref mut entry) => entry.changes.extend(changes),
+                    None => self.push(Entry { name: name.into(), changes }),
+                }
+                Ok(val)
+            }
+            Err(err) => {
+                transaction.rollback()?;
+                Err(err)
+            }
+        }
+    }
+
+    /// Returns `true` if there is an entry to undo.
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// Returns `true` if there is an entry to redo.
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// Gets the names of the entries that can be undone, oldest first.
+    pub fn undo_names(&self) -> Vec<&str> {
+        self.undo.iter().map(|entry| &entry.name[..]).collect()
+    }
+
+    /// Gets the names of the entries that can be redone, next first.
+    pub fn redo_names(&self) -> Vec<&str> {
+        self.redo.iter().rev().map(|entry| &entry.name[..]).collect()
+    }
+
+    /// Removes all entries.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    /// Reverts the last entry.
+    ///
+    /// Returns the name of the entry, or `None` if there is nothing to undo.
+    /// If the editor fails, the changes reverted so far are applied again
+    /// and the entry stays in the history, see `Transaction::run`.
+    pub fn undo(&mut self) -> Result<Option<String>, EditorError> {
+        let mut entry = match self.undo.pop() {
+            None => return Ok(None),
+            Some(entry) => entry,
+        };
+        let mut remaps = vec![];
+        let res = Transaction::run(&mut self.editor, &self.cloner, |editor| {
+            undo_changes(&mut entry.changes, editor, &mut remaps)
+        });
+        self.remap(&remaps);
+        let name = entry.name.clone();
+        match res {
+            Ok(()) => self.redo.push(entry),
+            Err(err) => {
+                self.undo.push(entry);
+                return Err(err);
+            }
+        }
+        Ok(Some(name))
+    }
+
+    /// Applies the last undone entry again.
+    ///
+    /// Returns the name of the entry, or `None` if there is nothing to redo.
+    /// If the editor fails, the changes applied so far are reverted
+    /// and the entry stays in the history, see `Transaction::run`.
+    pub fn redo(&mut self) -> Result<Option<String>, EditorError> {
+        let mut entry = match self.redo.pop() {
+            None => return Ok(None),
+            Some(entry) => entry,
+        };
+        let mut remaps = vec![];
+        let res = Transaction::run(&mut self.editor, &self.cloner, |editor| {
+            redo_changes(&mut entry.changes, editor, &mut remaps)
+        });
+        self.remap(&remaps);
+        let name = entry.name.clone();
+        match res {
+            Ok(()) => {
+                self.undo.push(entry);
+                self.apply_limit();
+            }
+            Err(err) => {
+                self.redo.push(entry);
+                return Err(err);
+            }
+        }
+        Ok(Some(name))
+    }
+
+    fn remap(&mut self, remaps: &[Remap]) {
+        for remap in remaps {
+            for entry in self.undo.iter_mut().chain(self.redo.iter_mut()) {
+                for change in &mut entry.changes {
+                    change.remap(remap);
+                }
+            }
+        }
+    }
+
+    fn record(&mut self, name: &str, change: Change) {
+        match self.group {
+            Some(ref mut entry) => entry.changes.push(change),
+            None => self.push(Entry { name: name.into(), changes: vec![change] }),
+        }
+    }
+
+    fn push(&mut self, entry: Entry) {
+        if entry.changes.is_empty() { return; }
+
+        self.redo.clear();
+        self.undo.push(entry);
+        self.apply_limit();
+    }
+
+    fn apply_limit(&mut self) {
+        if let Some(limit) = self.limit {
+            if self.undo.len() > limit {
+                let n = self.undo.len() - limit;
+                self.undo.drain(..n);
+            }
+        }
+    }
+}
+
+impl<E: Editor> Editor for UndoStack<E> {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.editor.cursor_2d()
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.editor.cursor_3d()
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        self.editor.hit_2d(pos)
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        self.editor.hit_rect_2d(rect)
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        self.editor.hit_polygon_2d(points)
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        self.editor.hit_3d(pos)
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        self.editor.hit_frustum(planes)
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        self.editor.hit_ray(origin, direction)
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .select(&mut self.editor, ty, |e| e.select(ty, obj))?;
+        self.record("select", change);
+        Ok(())
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .select(&mut self.editor, ty, |e| e.select_multiple(ty, objs))?;
+        self.record("select", change);
+        Ok(())
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .select(&mut self.editor, ty, |e| e.deselect_multiple(ty, objs))?;
+        self.record("deselect", change);
+        Ok(())
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .select(&mut self.editor, ty, |e| e.select_none(ty))?;
+        self.record("deselect", change);
+        Ok(())
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let (obj, change) = Recorder { cloner: &self.cloner }
+            .insert(&mut self.editor, ty, args)?;
+        self.record("insert", change);
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        let (moved, change) = Recorder { cloner: &self.cloner }
+            .delete(&mut self.editor, ty, obj)?;
+        self.record("delete", change);
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .update(&mut self.editor, ty, obj, args)?;
+        self.record("update", change);
+        Ok(())
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        let change = Recorder { cloner: &self.cloner }
+            .replace(&mut self.editor, ty, from, to)?;
+        self.record("replace", change);
+        Ok(())
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.editor.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        self.editor.visible(ty)
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.editor.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.editor.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.editor.all(ty)
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.editor.navigate_to(ty, obj)
+    }
+
+    fn refresh_views(&mut self) {
+        self.editor.refresh_views()
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.editor.views()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.editor.view(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.editor.active_view()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.editor.set_active_view(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    /// Fails `update` while the flag is set.
+    struct Flaky {
+        editor: VecEditor,
+        fail: Rc<Cell<bool>>,
+    }
+
+    impl Editor for Flaky {
+        fn cursor_2d(&self) -> Option<[f64; 2]> { None }
+        fn cursor_3d(&self) -> Option<[f64; 3]> { None }
+        fn hit_2d(&self, _pos: [f64; 2]) -> Vec<(Type, Object)> { vec![] }
+        fn hit_3d(&self, _pos: [f64; 3]) -> Vec<(Type, Object)> { vec![] }
+        fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+            self.editor.select(ty, obj)
+        }
+        fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+            self.editor.select_multiple(ty, objs)
+        }
+        fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+            self.editor.deselect_multiple(ty, objs)
+        }
+        fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+            self.editor.select_none(ty)
+        }
+        fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+            self.editor.insert(ty, args)
+        }
+        fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+            self.editor.delete(ty, obj)
+        }
+        fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+            if self.fail.get() { return Err(EditorError::ConstraintViolation("flaky".into())); }
+            self.editor.update(ty, obj, args)
+        }
+        fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+            self.editor.replace(ty, from, to)
+        }
+        fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+            self.editor.get(ty, obj)
+        }
+        fn visible(&self, ty: Type) -> Vec<Object> { self.editor.visible(ty) }
+        fn selected(&self, ty: Type) -> Option<Object> { self.editor.selected(ty) }
+        fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+            self.editor.multiple_selected(ty)
+        }
+        fn all(&self, ty: Type) -> Vec<Object> { self.editor.all(ty) }
+        fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+            self.editor.navigate_to(ty, obj)
+        }
+    }
+
+    fn values<E: Editor>(editor: &E) -> Vec<u32> {
+        editor.all(POINT).into_iter()
+            .map(|obj| *editor.get(POINT, obj).unwrap().downcast_ref::<u32>().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        let mut stack = UndoStack::with_cloner(editor, Cloner::new());
+        stack.register::<u32>(POINT);
+        for i in 0..3u32 { stack.insert(POINT, &i).unwrap(); }
+        stack.update(POINT, Object(1), &10u32).unwrap();
+        stack.delete(POINT, Object(0)).unwrap();
+        assert_eq!(values(&stack), vec![2, 10]);
+
+        assert_eq!(stack.undo().unwrap(), Some("delete".into()));
+        assert_eq!(values(&stack), vec![0, 10, 2]);
+        assert_eq!(stack.undo().unwrap(), Some("update".into()));
+        assert_eq!(values(&stack), vec![0, 1, 2]);
+        assert_eq!(stack.redo().unwrap(), Some("update".into()));
+        assert_eq!(stack.redo().unwrap(), Some("delete".into()));
+        assert_eq!(values(&stack), vec![2, 10]);
+        assert_eq!(stack.redo().unwrap(), None);
+    }
+
+    #[test]
+    fn restore_selection_order() {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        let mut stack = UndoStack::new(editor);
+        stack.register::<u32>(POINT);
+        for i in 0..3u32 { stack.insert(POINT, &i).unwrap(); }
+        stack.select_multiple(POINT, &[Object(2), Object(0), Object(1)]).unwrap();
+        stack.select(POINT, Object(1)).unwrap();
+        assert_eq!(stack.multiple_selected(POINT), vec![Object(2), Object(0), Object(1)]);
+        stack.select_none(POINT).unwrap();
+
+        stack.undo().unwrap();
+        assert_eq!(stack.selected(POINT), Some(Object(1)));
+        assert_eq!(stack.multiple_selected(POINT), vec![Object(2), Object(0), Object(1)]);
+        stack.undo().unwrap();
+        assert_eq!(stack.selected(POINT), Some(Object(2)));
+        assert_eq!(stack.multiple_selected(POINT), vec![Object(2), Object(0), Object(1)]);
+    }
+
+    #[test]
+    fn failed_undo_keeps_entry() {
+        let mut editor = VecEditor::new();
+        editor.register::<u32>(POINT);
+        let fail = Rc::new(Cell::new(false));
+        let mut stack = UndoStack::new(Flaky { editor, fail: fail.clone() });
+        stack.register::<u32>(POINT);
+        stack.insert(POINT, &0u32).unwrap();
+        stack.begin("edit");
+        stack.update(POINT, Object(0), &5u32).unwrap();
+        stack.insert(POINT, &1u32).unwrap();
+        stack.end();
+
+        fail.set(true);
+        assert!(stack.undo().is_err());
+        assert_eq!(values(&stack), vec![5, 1]);
+        assert_eq!(stack.undo_names(), vec!["insert", "edit"]);
+
+        fail.set(false);
+        assert_eq!(stack.undo().unwrap(), Some("edit".into()));
+        assert_eq!(values(&stack), vec![0]);
+
+        fail.set(true);
+        assert!(stack.redo().is_err());
+        assert_eq!(values(&stack), vec![0]);
+        assert_eq!(stack.redo_names(), vec!["edit"]);
+        fail.set(false);
+        stack.redo().unwrap();
+        assert_eq!(values(&stack), vec![5, 1]);
+    }
+}
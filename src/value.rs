@@ -0,0 +1,421 @@
+use std::collections::BTreeMap;
+
+use {Editor, EditorError, Object, Type, TypeRegistry};
+
+/// The maximum nesting of lists and maps when reading documents,
+/// such that deeply nested data is refused instead of overflowing the stack.
+pub(crate) const MAX_DEPTH: usize = 128;
+
+/// A dynamically typed value.
+///
+/// Used to create and edit objects from scripts, config files
+/// and network messages, without knowing the Rust types.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    /// A boolean.
+    Bool(bool),
+    /// An integer.
+    Int(i64),
+    /// An unsigned integer above `i64::MAX`.
+    ///
+    /// Smaller unsigned integers are converted to `Int`.
+    UInt(u64),
+    /// A floating point number.
+    Float(f64),
+    /// A text.
+    String(String),
+    /// A 2D vector.
+    Vec2([f64; 2]),
+    /// A 3D vector.
+    Vec3([f64; 3]),
+    /// A color in RGBA.
+    Color([f32; 4]),
+    /// A reference to an object.
+    Reference(Type, Object),
+    /// A list of values.
+    List(Vec<Value>),
+    /// Values by name, for example the fields of an object.
+    Map(BTreeMap<String, Value>),
+}
+
+impl Value {
+    /// Gets the name of the kind of value, used in error messages.
+    pub fn kind(&self) -> &'static str {
+        match *self {
+            Value::Bool(_) => "bool",
+            Value::Int(_) => "int",
+            Value::UInt(_) => "uint",
+            Value::Float(_) => "float",
+            Value::String(_) => "string",
+            Value::Vec2(_) => "vec2",
+            Value::Vec3(_) => "vec3",
+            Value::Color(_) => "color",
+            Value::Reference(_, _) => "reference",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+        }
+    }
+
+    /// Gets a value in a map by name.
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match *self {
+            Value::Map(ref map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    /// Converts a value in a map by name.
+    ///
+    /// Used to implement `FromValue` for structs.
+    pub fn field<T: FromValue>(&self, key: &str) -> Result<T, EditorError> {
+        match *self {
+            Value::Map(ref map) => match map.get(key) {
+                None => Err(EditorError::ConstraintViolation(format!("missing field `{}`", key))),
+                Some(val) => T::from_value(val),
+            },
+            _ => Err(mismatch::<BTreeMap<String, Value>>(self)),
+        }
+    }
+
+    /// Gets the object references in the value.
+    pub fn references(&self) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        self.visit_references(&mut |ty, obj| res.push((ty, obj)));
+        res
+    }
+
+    fn visit_references(&self, f: &mut dyn FnMut(Type, Object)) {
+        match *self {
+            Value::Reference(ty, obj) => f(ty, obj),
+            Value::List(ref items) => for item in items { item.visit_references(f); },
+            Value::Map(ref map) => for item in map.values() { item.visit_references(f); },
+            _ => {}
+        }
+    }
+
+    /// Changes the object references in the value.
+    pub fn map_references(
+        &mut self,
+        f: &mut dyn FnMut(Type, Object) -> Result<Object, EditorError>
+    ) -> Result<(), EditorError> {
+        match *self {
+            Value::Reference(ty, ref mut obj) => *obj = f(ty, *obj)?,
+            Value::List(ref mut items) => for item in items { item.map_references(f)?; },
+            Value::Map(ref mut map) => for item in map.values_mut() { item.map_references(f)?; },
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Creates a map from a list of named values.
+    ///
+    /// Used to implement `ToValue` for structs.
+    pub fn map(fields: Vec<(&str, Value)>) -> Value {
+        Value::Map(fields.into_iter().map(|(key, val)| (key.into(), val)).collect())
+    }
+}
+
+/// Implemented by types that can be converted to a value.
+pub trait ToValue {
+    /// Converts to a value.
+    fn to_value(&self) -> Value;
+}
+
+/// Implemented by types that can be created from a value.
+pub trait FromValue: Sized {
+    /// Converts from a value.
+    fn from_value(val: &Value) -> Result<Self, EditorError>;
+}
+
+/// Creates a downcast mismatch error for a value.
+pub(crate) fn mismatch<T: ?Sized>(val: &Value) -> EditorError {
+    EditorError::downcast::<T>(Some(val.kind()))
+}
+
+impl ToValue for Value {
+    fn to_value(&self) -> Value { self.clone() }
+}
+
+impl FromValue for Value {
+    fn from_value(val: &Value) -> Result<Value, EditorError> { Ok(val.clone()) }
+}
+
+impl ToValue for bool {
+    fn to_value(&self) -> Value { Value::Bool(*self) }
+}
+
+impl FromValue for bool {
+    fn from_value(val: &Value) -> Result<bool, EditorError> {
+        match *val {
+            Value::Bool(x) => Ok(x),
+            _ => Err(mismatch::<bool>(val)),
+        }
+    }
+}
+
+macro_rules! int {
+    ($($t:ty),*) => {$(
+        impl ToValue for $t {
+            fn to_value(&self) -> Value { Value::Int(*self as i64) }
+        }
+
+        impl FromValue for $t {
+            fn from_value(val: &Value) -> Result<$t, EditorError> {
+                match *val {
+                    Value::Int(x) if x >= <$t>::MIN as i64 && x <= <$t>::MAX as i64 =>
+                        Ok(x as $t),
+                    Value::UInt(x) if x <= <$t>::MAX as u64 => Ok(x as $t),
+                    _ => Err(mismatch::<$t>(val)),
+                }
+            }
+        }
+    )*}
+}
+
+int!(i8, i16, i32, i64, isize, u8, u16, u32);
+
+// Values above `i64::MAX` are stored as `Value::UInt`.
+// Converting back refuses values out of range, as for the other integers.
+macro_rules! uint {
+    ($($t:ty),*) => {$(
+        impl ToValue for $t {
+            fn to_value(&self) -> Value {
+                let x = *self as u64;
+                if x <= i64::MAX as u64 { Value::Int(x as i64) } else { Value::UInt(x) }
+            }
+        }
+
+        impl FromValue for $t {
+            fn from_value(val: &Value) -> Result<$t, EditorError> {
+                match *val {
+                    Value::Int(x) if x >= 0 && x as u64 <= <$t>::MAX as u64 => Ok(x as $t),
+                    Value::UInt(x) if x <= <$t>::MAX as u64 => Ok(x as $t),
+                    _ => Err(mismatch::<$t>(val)),
+                }
+            }
+        }
+    )*}
+}
+
+uint!(u64, usize);
+
+impl ToValue for f64 {
+    fn to_value(&self) -> Value { Value::Float(*self) }
+}
+
+impl FromValue for f64 {
+    fn from_value(val: &Value) -> Result<f64, EditorError> {
+        match *val {
+            Value::Float(x) => Ok(x),
+            Value::Int(x) => Ok(x as f64),
+            Value::UInt(x) => Ok(x as f64),
+            _ => Err(mismatch::<f64>(val)),
+        }
+    }
+}
+
+impl ToValue for f32 {
+    fn to_value(&self) -> Value { Value::Float(*self as f64) }
+}
+
+impl FromValue for f32 {
+    fn from_value(val: &Value) -> Result<f32, EditorError> {
+        f64::from_value(val).map(|x| x as f32).map_err(|_| mismatch::<f32>(val))
+    }
+}
+
+impl ToValue for String {
+    fn to_value(&self) -> Value { Value::String(self.clone()) }
+}
+
+impl ToValue for &str {
+    fn to_value(&self) -> Value { Value::String(self.to_string()) }
+}
+
+impl FromValue for String {
+    fn from_value(val: &Value) -> Result<String, EditorError> {
+        match *val {
+            Value::String(ref x) => Ok(x.clone()),
+            _ => Err(mismatch::<String>(val)),
+        }
+    }
+}
+
+impl ToValue for [f64; 2] {
+    fn to_value(&self) -> Value { Value::Vec2(*self) }
+}
+
+impl FromValue for [f64; 2] {
+    fn from_value(val: &Value) -> Result<[f64; 2], EditorError> {
+        match *val {
+            Value::Vec2(x) => Ok(x),
+            _ => Err(mismatch::<[f64; 2]>(val)),
+        }
+    }
+}
+
+impl ToValue for [f64; 3] {
+    fn to_value(&self) -> Value { Value::Vec3(*self) }
+}
+
+impl FromValue for [f64; 3] {
+    fn from_value(val: &Value) -> Result<[f64; 3], EditorError> {
+        match *val {
+            Value::Vec3(x) => Ok(x),
+            _ => Err(mismatch::<[f64; 3]>(val)),
+        }
+    }
+}
+
+impl ToValue for [f32; 4] {
+    fn to_value(&self) -> Value { Value::Color(*self) }
+}
+
+impl FromValue for [f32; 4] {
+    fn from_value(val: &Value) -> Result<[f32; 4], EditorError> {
+        match *val {
+            Value::Color(x) => Ok(x),
+            _ => Err(mismatch::<[f32; 4]>(val)),
+        }
+    }
+}
+
+impl FromValue for Object {
+    fn from_value(val: &Value) -> Result<Object, EditorError> {
+        match *val {
+            Value::Reference(_, obj) => Ok(obj),
+            _ => Err(mismatch::<Object>(val)),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for Vec<T> {
+    fn to_value(&self) -> Value {
+        Value::List(self.iter().map(|x| x.to_value()).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for Vec<T> {
+    fn from_value(val: &Value) -> Result<Vec<T>, EditorError> {
+        match *val {
+            Value::List(ref list) => list.iter().map(T::from_value).collect(),
+            _ => Err(mismatch::<Vec<T>>(val)),
+        }
+    }
+}
+
+impl<T: ToValue> ToValue for BTreeMap<String, T> {
+    fn to_value(&self) -> Value {
+        Value::Map(self.iter().map(|(key, x)| (key.clone(), x.to_value())).collect())
+    }
+}
+
+impl<T: FromValue> FromValue for BTreeMap<String, T> {
+    fn from_value(val: &Value) -> Result<BTreeMap<String, T>, EditorError> {
+        match *val {
+            Value::Map(ref map) => map.iter()
+                .map(|(key, x)| Ok((key.clone(), T::from_value(x)?)))
+                .collect(),
+            _ => Err(mismatch::<BTreeMap<String, T>>(val)),
+        }
+    }
+}
+
+/// Extension methods for editing objects with values.
+///
+/// The conversions of each type must be registered with
+/// `SchemaBuilder::values` in the type registry.
+pub trait EditorExt: Editor {
+    /// Inserts a new object created from a value.
+    fn insert_value(&mut self, types: &TypeRegistry, ty: Type, val: &Value)
+    -> Result<Object, EditorError> {
+        let args = types.from_value(ty, val)?;
+        self.insert(ty, &*args)
+    }
+
+    /// Updates an object with a value.
+    fn update_value(&mut self, types: &TypeRegistry, ty: Type, obj: Object, val: &Value)
+    -> Result<(), EditorError> {
+        let args = types.from_value(ty, val)?;
+        self.update(ty, obj, &*args)
+    }
+
+    /// Gets the value of an object.
+    fn get_value(&self, types: &TypeRegistry, ty: Type, obj: Object)
+    -> Result<Value, EditorError> {
+        types.to_value(ty, self.get(ty, obj)?)
+    }
+}
+
+impl<E: Editor + ?Sized> EditorExt for E {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use VecEditor;
+
+    const POINT: Type = Type("point");
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Point {
+        pos: [f64; 2],
+        tags: Vec<String>,
+    }
+
+    impl ToValue for Point {
+        fn to_value(&self) -> Value {
+            Value::map(vec![("pos", self.pos.to_value()), ("tags", self.tags.to_value())])
+        }
+    }
+
+    impl FromValue for Point {
+        fn from_value(val: &Value) -> Result<Point, EditorError> {
+            Ok(Point { pos: val.field("pos")?, tags: val.field("tags")? })
+        }
+    }
+
+    #[test]
+    fn integers() {
+        assert_eq!(u8::from_value(&Value::Int(255)).unwrap(), 255);
+        assert!(u8::from_value(&Value::Int(256)).is_err());
+        assert!(u64::from_value(&Value::Int(-1)).is_err());
+        assert_eq!(u64::MAX.to_value(), Value::UInt(u64::MAX));
+        assert_eq!(u64::from_value(&u64::MAX.to_value()).unwrap(), u64::MAX);
+        assert_eq!(usize::from_value(&usize::MAX.to_value()).unwrap(), usize::MAX);
+        assert_eq!((i64::MAX as u64).to_value(), Value::Int(i64::MAX));
+        assert!(i64::from_value(&Value::UInt(u64::MAX)).is_err());
+        assert_eq!(u8::from_value(&Value::UInt(7)).unwrap(), 7);
+        assert_eq!(u64::from_value(&7u64.to_value()).unwrap(), 7);
+        assert_eq!(isize::from_value(&(-7isize).to_value()).unwrap(), -7);
+        assert_eq!(f64::from_value(&Value::Int(2)).unwrap(), 2.0);
+        match i32::from_value(&Value::Bool(true)) {
+            Err(EditorError::DowncastMismatch { actual, .. }) => assert_eq!(actual, Some("bool")),
+            _ => panic!("expected a downcast mismatch"),
+        }
+    }
+
+    #[test]
+    fn references() {
+        let mut val = Value::List(vec![
+            Value::Reference(POINT, Object(1)),
+            Value::map(vec![("a", Value::Reference(POINT, Object(2)))]),
+        ]);
+        assert_eq!(val.references(), vec![(POINT, Object(1)), (POINT, Object(2))]);
+        val.map_references(&mut |_, obj| Ok(Object(obj.0 + 10))).unwrap();
+        assert_eq!(val.references(), vec![(POINT, Object(11)), (POINT, Object(12))]);
+    }
+
+    #[test]
+    fn editor_ext() {
+        let mut types = TypeRegistry::new();
+        types.register::<Point>(POINT).values();
+        let mut editor = VecEditor::new();
+        editor.register::<Point>(POINT);
+        let point = Point { pos: [1.0, 2.0], tags: vec!["a".into()] };
+        let obj = editor.insert_value(&types, POINT, &point.to_value()).unwrap();
+        assert_eq!(editor.get_value(&types, POINT, obj).unwrap(), point.to_value());
+        let missing = Value::map(vec![("pos", [0.0, 0.0].to_value())]);
+        assert!(editor.update_value(&types, POINT, obj, &missing).is_err());
+        assert_eq!(editor.items::<Point>(POINT).unwrap()[0], point);
+    }
+}
@@ -0,0 +1,748 @@
+use std::any::Any;
+
+use bounds::not_finite;
+use ray::sort_hits;
+use view::no_view;
+use {
+    Bounds2, Bounds3, Bvh, Camera, Cloner, Editor, EditorError, EditorState, Grid, Hit, Object,
+    Plane, Ray, Type, View, ViewId, Views,
+};
+
+/// Stores objects of one type, with the Rust type erased.
+trait Items {
+    fn len(&self) -> usize;
+    fn insert(&mut self, args: &dyn Any) -> Result<Object, EditorError>;
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError>;
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError>;
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError>;
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError>;
+    fn hit_2d(&self, obj: Object, pos: [f64; 2]) -> bool;
+    fn hit_3d(&self, obj: Object, pos: [f64; 3]) -> bool;
+    fn hit_ray(&self, obj: Object, ray: &Ray) -> Option<(f64, [f64; 3])>;
+    fn in_view(&self, obj: Object, view: &View) -> bool;
+    fn has_bounds_2d(&self) -> bool;
+    fn has_bounds_3d(&self) -> bool;
+    fn bounds_2d(&self, obj: Object) -> Option<Bounds2>;
+    fn bounds_3d(&self, obj: Object) -> Option<Bounds3>;
+    fn finite_bounds(&self, args: &dyn Any) -> bool;
+    fn type_name(&self) -> &'static str;
+    fn as_any(&self) -> &dyn Any;
+    fn as_any_mut(&mut self) -> &mut dyn Any;
+}
+
+/// Returns the distance along the ray and the normal of a hit.
+type HitRayFn<T> = fn(&T, &Ray) -> Option<(f64, [f64; 3])>;
+
+struct Column<T> {
+    items: Vec<T>,
+    hit_2d: Option<fn(&T, [f64; 2]) -> bool>,
+    hit_3d: Option<fn(&T, [f64; 3]) -> bool>,
+    hit_ray: Option<HitRayFn<T>>,
+    in_view: Option<fn(&T, &View) -> bool>,
+    bounds_2d: Option<fn(&T) -> Bounds2>,
+    bounds_3d: Option<fn(&T) -> Bounds3>,
+}
+
+impl<T: Any + Clone> Items for Column<T> {
+    fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    fn insert(&mut self, args: &dyn Any) -> Result<Object, EditorError> {
+        ::insert(&mut self.items, args)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        ::delete(ty, &mut self.items, obj)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        ::update(ty, &mut self.items, obj, args)
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        ::replace(ty, &mut self.items, from, to)
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        ::get(ty, &self.items, obj)
+    }
+
+    fn hit_2d(&self, obj: Object, pos: [f64; 2]) -> bool {
+        match self.hit_2d {
+            None => false,
+            Some(f) => f(&self.items[obj.0], pos),
+        }
+    }
+
+    fn hit_3d(&self, obj: Object, pos: [f64; 3]) -> bool {
+        match self.hit_3d {
+            None => false,
+            Some(f) => f(&self.items[obj.0], pos),
+        }
+    }
+
+    fn hit_ray(&self, obj: Object, ray: &Ray) -> Option<(f64, [f64; 3])> {
+        self.hit_ray.and_then(|f| f(&self.items[obj.0], ray))
+    }
+
+    fn in_view(&self, obj: Object, view: &View) -> bool {
+        match self.in_view {
+            None => true,
+            Some(f) => f(&self.items[obj.0], view),
+        }
+    }
+
+    fn has_bounds_2d(&self) -> bool {
+        self.bounds_2d.is_some()
+    }
+
+    fn has_bounds_3d(&self) -> bool {
+        self.bounds_3d.is_some()
+    }
+
+    fn bounds_2d(&self, obj: Object) -> Option<Bounds2> {
+        match (self.bounds_2d, self.items.get(obj.0)) {
+            (Some(f), Some(item)) => Some(f(item)),
+            _ => None,
+        }
+    }
+
+    fn bounds_3d(&self, obj: Object) -> Option<Bounds3> {
+        match (self.bounds_3d, self.items.get(obj.0)) {
+            (Some(f), Some(item)) => Some(f(item)),
+            _ => None,
+        }
+    }
+
+    fn finite_bounds(&self, args: &dyn Any) -> bool {
+        // Arguments of the wrong type are reported by `insert` and `update`.
+        match args.downcast_ref::<T>() {
+            None => true,
+            Some(val) => self.bounds_2d.map(|f| f(val).is_finite()).unwrap_or(true) &&
+                self.bounds_3d.map(|f| f(val).is_finite()).unwrap_or(true),
+        }
+    }
+
+    fn type_name(&self) -> &'static str {
+        ::std::any::type_name::<T>()
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn as_any_mut(&mut self) -> &mut dyn Any {
+        self
+    }
+}
+
+/// A table of objects.
+struct Table {
+    ty: Type,
+    items: Box<dyn Items>,
+}
+
+/// An in-memory editor storing each type in a `Vec`.
+///
+/// Each registered type has its own table of objects.
+/// Selection, visibility and cursors are kept in an `EditorState`.
+/// Objects are deleted with swap-remove, see `Editor::delete`.
+/// When the selected object is deselected or deleted, or nothing is selected
+/// when selecting multiple objects, the first of the multiple selected objects
+/// becomes the selected object.
+///
+/// Hit testing is done by looping over visible objects,
+/// using the functions set with `set_hit_2d`, `set_hit_3d` and `set_hit_ray`.
+///
+/// Types with bounds set with `set_bounds_2d` and `set_bounds_3d`
+/// are kept in a `Grid` and a `Bvh`, such that hit testing
+/// only tests objects near the position.
+/// Rectangle and lasso hits use the 2D bounds and frustum hits the 3D bounds,
+/// so types without them are never hit.
+///
+/// Without views, every object that is not hidden is visible.
+/// When views are added, the visible objects of each view are computed
+/// by `refresh_views` with the function set with `set_in_view`,
+/// and `visible` and hit testing use the active view.
+#[derive(Default)]
+pub struct VecEditor {
+    tables: Vec<Table>,
+    views: Views,
+    grid: Grid,
+    bvh: Bvh,
+    cloner: Cloner,
+    state: EditorState,
+}
+
+impl VecEditor {
+    /// Creates a new editor without any types.
+    pub fn new() -> VecEditor {
+        VecEditor::default()
+    }
+
+    /// Registers a type stored as `T`.
+    ///
+    /// Registering a type again removes all its objects.
+    pub fn register<T: Any + Clone>(&mut self, ty: Type) {
+        let table = Table {
+            ty,
+            items: Box::new(Column::<T> {
+                items: vec![],
+                hit_2d: None,
+                hit_3d: None,
+                hit_ray: None,
+                in_view: None,
+                bounds_2d: None,
+                bounds_3d: None,
+            }),
+        };
+        match self.tables.iter().position(|table| table.ty == ty) {
+            Some(i) => {
+                self.tables[i] = table;
+                self.state.clear(ty);
+                self.grid.remove_type(ty);
+                self.bvh.remove_type(ty);
+                for view in self.views.iter_mut() {
+                    view.set_visible(ty, vec![]);
+                }
+            }
+            None => self.tables.push(table),
+        }
+        self.cloner.register::<T>(ty);
+    }
+
+    /// Gets the registered types, in the order they were registered.
+    pub fn types(&self) -> Vec<Type> {
+        self.tables.iter().map(|table| table.ty).collect()
+    }
+
+    /// Gets a cloner for the registered types,
+    /// for use with `UndoStack` and `Transaction`.
+    pub fn cloner(&self) -> &Cloner {
+        &self.cloner
+    }
+
+    /// Sets the function used to hit objects at 2D position.
+    pub fn set_hit_2d<T: Any + Clone>(&mut self, ty: Type, f: fn(&T, [f64; 2]) -> bool)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.hit_2d = Some(f);
+        Ok(())
+    }
+
+    /// Sets the function used to hit objects at 3D position.
+    pub fn set_hit_3d<T: Any + Clone>(&mut self, ty: Type, f: fn(&T, [f64; 3]) -> bool)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.hit_3d = Some(f);
+        Ok(())
+    }
+
+    /// Sets the function used to hit objects along a ray,
+    /// returning the distance along the ray and the normal.
+    ///
+    /// See `Ray::hit_sphere` for a helper.
+    pub fn set_hit_ray<T: Any + Clone>(&mut self, ty: Type, f: HitRayFn<T>)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.hit_ray = Some(f);
+        Ok(())
+    }
+
+    /// Sets the function used to get the 2D bounds of objects,
+    /// which enables the spatial index for the type.
+    ///
+    /// Objects are only hit inside their bounds.
+    /// Inserting or updating objects with bounds that are not finite fails,
+    /// and so does this method if existing objects have such bounds.
+    pub fn set_bounds_2d<T: Any + Clone>(&mut self, ty: Type, f: fn(&T) -> Bounds2)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.bounds_2d = Some(f);
+        self.reindex_all(ty)
+    }
+
+    /// Sets the function used to get the 3D bounds of objects,
+    /// which enables the spatial index for the type.
+    ///
+    /// Objects are only hit inside their bounds.
+    /// Inserting or updating objects with bounds that are not finite fails,
+    /// and so does this method if existing objects have such bounds.
+    pub fn set_bounds_3d<T: Any + Clone>(&mut self, ty: Type, f: fn(&T) -> Bounds3)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.bounds_3d = Some(f);
+        self.reindex_all(ty)
+    }
+
+    /// Sets the cell size of the grid used for 2D bounds, which is 1 by default.
+    ///
+    /// Should be about the size of typical objects.
+    pub fn set_cell_size(&mut self, size: f64) {
+        let mut grid = Grid::new(size);
+        for table in &self.tables {
+            for obj in (0..table.items.len()).map(Object) {
+                if let Some(bounds) = table.items.bounds_2d(obj) {
+                    // The bounds were checked when inserted.
+                    let _ = grid.insert(table.ty, obj, bounds);
+                }
+            }
+        }
+        self.grid = grid;
+    }
+
+    /// Gets the grid of 2D bounds.
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    /// Gets the bounding volume hierarchy of 3D bounds.
+    pub fn bvh(&self) -> &Bvh {
+        &self.bvh
+    }
+
+    /// Sets the function used to test whether an object is inside a view,
+    /// for example by checking its bounds against the camera frustum.
+    ///
+    /// Objects of types without such function are inside every view.
+    pub fn set_in_view<T: Any + Clone>(&mut self, ty: Type, f: fn(&T, &View) -> bool)
+    -> Result<(), EditorError> {
+        self.column_mut::<T>(ty)?.in_view = Some(f);
+        Ok(())
+    }
+
+    /// Adds a view, which becomes active if there is no active view.
+    ///
+    /// The view shows nothing until `refresh_views` is called.
+    pub fn add_view(&mut self, viewport: [f64; 4], camera: Camera) -> ViewId {
+        self.views.add(viewport, camera)
+    }
+
+    /// Removes a view, returning `true` if it was found.
+    pub fn remove_view(&mut self, id: ViewId) -> bool {
+        self.views.remove(id)
+    }
+
+    /// Sets the camera of a view.
+    pub fn set_camera(&mut self, id: ViewId, camera: Camera) -> Result<(), EditorError> {
+        self.views.get_mut(id).ok_or_else(|| no_view(id))?.camera = camera;
+        Ok(())
+    }
+
+    /// Sets the viewport of a view in window coordinates, `[x, y, width, height]`.
+    pub fn set_viewport(&mut self, id: ViewId, viewport: [f64; 4]) -> Result<(), EditorError> {
+        self.views.get_mut(id).ok_or_else(|| no_view(id))?.viewport = viewport;
+        Ok(())
+    }
+
+    /// Gets the objects of a type.
+    pub fn items<T: Any + Clone>(&self, ty: Type) -> Result<&[T], EditorError> {
+        let items = &self.table(ty)?.items;
+        match items.as_any().downcast_ref::<Column<T>>() {
+            None => Err(EditorError::downcast::<T>(Some(items.type_name()))),
+            Some(column) => Ok(&column.items),
+        }
+    }
+
+    /// Sets the cursor position in 2D.
+    pub fn set_cursor_2d(&mut self, pos: Option<[f64; 2]>) {
+        self.state.cursor_2d = pos;
+    }
+
+    /// Sets the cursor position in 3D world coordinates.
+    pub fn set_cursor_3d(&mut self, pos: Option<[f64; 3]>) {
+        self.state.cursor_3d = pos;
+    }
+
+    /// Shows or hides an object.
+    pub fn set_visible(&mut self, ty: Type, obj: Object, visible: bool)
+    -> Result<(), EditorError> {
+        let len = self.table(ty)?.items.len();
+        self.state.set_visible(ty, obj, visible, len)
+    }
+
+    /// Shows all objects of a type.
+    pub fn show_all(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.table(ty)?;
+        self.state.show_all(ty);
+        Ok(())
+    }
+
+    /// Updates the spatial index of an object.
+    fn reindex(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        let table = self.table(ty)?;
+        let (bounds_2d, bounds_3d) = (table.items.bounds_2d(obj), table.items.bounds_3d(obj));
+        match bounds_2d {
+            None => { self.grid.remove(ty, obj); }
+            Some(bounds) => self.grid.insert(ty, obj, bounds)?,
+        }
+        match bounds_3d {
+            None => { self.bvh.remove(ty, obj); }
+            Some(bounds) => self.bvh.insert(ty, obj, bounds)?,
+        }
+        Ok(())
+    }
+
+    fn reindex_all(&mut self, ty: Type) -> Result<(), EditorError> {
+        for obj in self.all(ty) { self.reindex(ty, obj)?; }
+        Ok(())
+    }
+
+    /// Gets the objects of a table to hit test, sorted by id.
+    ///
+    /// Uses the candidates from a spatial index if the table has bounds,
+    /// otherwise all visible objects.
+    fn hit_candidates(&self, table: &Table, indexed: bool, candidates: &[(Type, Object)])
+    -> Vec<Object> {
+        if !indexed { return self.visible(table.ty); }
+
+        let view = self.views.active_view();
+        let mut res: Vec<Object> = candidates.iter()
+            .filter(|&&(ty, obj)| ty == table.ty && self.is_visible(ty, obj, view))
+            .map(|&(_, obj)| obj)
+            .collect();
+        res.sort_by_key(|obj| obj.0);
+        res
+    }
+
+    /// Returns `true` if an object is not hidden and visible in a view.
+    fn is_visible(&self, ty: Type, obj: Object, view: Option<&View>) -> bool {
+        self.state.is_visible(ty, obj) && view.map(|view| view.contains(ty, obj)).unwrap_or(true)
+    }
+
+    fn table(&self, ty: Type) -> Result<&Table, EditorError> {
+        self.tables.iter().find(|table| table.ty == ty).ok_or(EditorError::UnknownType(ty))
+    }
+
+    fn table_mut(&mut self, ty: Type) -> Result<&mut Table, EditorError> {
+        self.tables.iter_mut().find(|table| table.ty == ty).ok_or(EditorError::UnknownType(ty))
+    }
+
+    fn column_mut<T: Any + Clone>(&mut self, ty: Type) -> Result<&mut Column<T>, EditorError> {
+        let items = &mut self.table_mut(ty)?.items;
+        let actual = items.type_name();
+        match items.as_any_mut().downcast_mut::<Column<T>>() {
+            None => Err(EditorError::downcast::<T>(Some(actual))),
+            Some(column) => Ok(column),
+        }
+    }
+}
+
+impl Editor for VecEditor {
+    fn cursor_2d(&self) -> Option<[f64; 2]> {
+        self.state.cursor_2d
+    }
+
+    fn cursor_3d(&self) -> Option<[f64; 3]> {
+        self.state.cursor_3d
+    }
+
+    fn hit_2d(&self, pos: [f64; 2]) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        let candidates = self.grid.query_point(pos);
+        for table in &self.tables {
+            let indexed = table.items.has_bounds_2d();
+            for obj in self.hit_candidates(table, indexed, &candidates) {
+                if table.items.hit_2d(obj, pos) {
+                    res.push((table.ty, obj));
+                }
+            }
+        }
+        res
+    }
+
+    fn hit_rect_2d(&self, rect: Bounds2) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        let candidates = self.grid.query_rect(&rect);
+        for table in &self.tables {
+            if !table.items.has_bounds_2d() { continue; }
+            for obj in self.hit_candidates(table, true, &candidates) {
+                res.push((table.ty, obj));
+            }
+        }
+        res
+    }
+
+    fn hit_polygon_2d(&self, points: &[[f64; 2]]) -> Vec<(Type, Object)> {
+        let rect = match Bounds2::polygon(points) {
+            None => return vec![],
+            Some(rect) => rect,
+        };
+        let mut res = vec![];
+        let candidates = self.grid.query_rect(&rect);
+        for table in &self.tables {
+            if !table.items.has_bounds_2d() { continue; }
+            for obj in self.hit_candidates(table, true, &candidates) {
+                let hit = table.items.bounds_2d(obj)
+                    .map(|bounds| bounds.intersects_polygon(points))
+                    .unwrap_or(false);
+                if hit { res.push((table.ty, obj)); }
+            }
+        }
+        res
+    }
+
+    fn hit_3d(&self, pos: [f64; 3]) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        let candidates = self.bvh.query_point(pos);
+        for table in &self.tables {
+            let indexed = table.items.has_bounds_3d();
+            for obj in self.hit_candidates(table, indexed, &candidates) {
+                if table.items.hit_3d(obj, pos) {
+                    res.push((table.ty, obj));
+                }
+            }
+        }
+        res
+    }
+
+    fn hit_frustum(&self, planes: &[Plane; 6]) -> Vec<(Type, Object)> {
+        let mut res = vec![];
+        let candidates = self.bvh.query(|bounds| bounds.intersects_planes(planes));
+        for table in &self.tables {
+            if !table.items.has_bounds_3d() { continue; }
+            for obj in self.hit_candidates(table, true, &candidates) {
+                res.push((table.ty, obj));
+            }
+        }
+        res
+    }
+
+    fn hit_ray(&self, origin: [f64; 3], direction: [f64; 3]) -> Vec<Hit> {
+        let ray = Ray { origin, direction };
+        let mut res = vec![];
+        let candidates = self.bvh.query_ray(origin, direction);
+        for table in &self.tables {
+            let indexed = table.items.has_bounds_3d();
+            for obj in self.hit_candidates(table, indexed, &candidates) {
+                if let Some((distance, normal)) = table.items.hit_ray(obj, &ray) {
+                    res.push(Hit {
+                        ty: table.ty,
+                        obj,
+                        distance,
+                        position: ray.at(distance),
+                        normal,
+                    });
+                }
+            }
+        }
+        sort_hits(&mut res);
+        res
+    }
+
+    fn select(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        let len = self.table(ty)?.items.len();
+        self.state.select(ty, obj, len)
+    }
+
+    fn select_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        let len = self.table(ty)?.items.len();
+        self.state.select_multiple(ty, objs, len)
+    }
+
+    fn deselect_multiple(&mut self, ty: Type, objs: &[Object]) -> Result<(), EditorError> {
+        self.table(ty)?;
+        self.state.deselect_multiple(ty, objs);
+        Ok(())
+    }
+
+    fn select_none(&mut self, ty: Type) -> Result<(), EditorError> {
+        self.table(ty)?;
+        self.state.select_none(ty);
+        Ok(())
+    }
+
+    fn insert(&mut self, ty: Type, args: &dyn Any) -> Result<Object, EditorError> {
+        let table = self.table_mut(ty)?;
+        if !table.items.finite_bounds(args) {
+            return Err(not_finite(ty, Object(table.items.len())));
+        }
+        let obj = table.items.insert(args)?;
+        self.reindex(ty, obj)?;
+        Ok(obj)
+    }
+
+    fn delete(&mut self, ty: Type, obj: Object) -> Result<Option<Object>, EditorError> {
+        let table = self.table_mut(ty)?;
+        let moved = table.items.delete(ty, obj)?;
+        self.state.deleted(ty, obj, moved);
+        for view in self.views.iter_mut() {
+            view.deleted(ty, obj, moved);
+        }
+        self.grid.deleted(ty, obj, moved);
+        self.bvh.deleted(ty, obj, moved);
+        Ok(moved)
+    }
+
+    fn update(&mut self, ty: Type, obj: Object, args: &dyn Any) -> Result<(), EditorError> {
+        let table = self.table_mut(ty)?;
+        if !table.items.finite_bounds(args) { return Err(not_finite(ty, obj)); }
+        table.items.update(ty, obj, args)?;
+        self.reindex(ty, obj)
+    }
+
+    fn replace(&mut self, ty: Type, from: Object, to: Object) -> Result<(), EditorError> {
+        self.table_mut(ty)?.items.replace(ty, from, to)?;
+        self.reindex(ty, from)
+    }
+
+    fn get(&self, ty: Type, obj: Object) -> Result<&dyn Any, EditorError> {
+        self.table(ty)?.items.get(ty, obj)
+    }
+
+    fn visible(&self, ty: Type) -> Vec<Object> {
+        let table = match self.table(ty) {
+            Err(_) => return vec![],
+            Ok(table) => table,
+        };
+        // Hiding objects takes effect immediately, without refreshing views.
+        match self.views.active_view() {
+            None => self.state.visible(ty, table.items.len()),
+            Some(view) => view.visible(ty).iter()
+                .filter(|&&obj| self.state.is_visible(ty, obj))
+                .cloned()
+                .collect(),
+        }
+    }
+
+    fn selected(&self, ty: Type) -> Option<Object> {
+        self.state.selected(ty)
+    }
+
+    fn multiple_selected(&self, ty: Type) -> Vec<Object> {
+        self.state.multiple_selected(ty)
+    }
+
+    fn all(&self, ty: Type) -> Vec<Object> {
+        self.table(ty).map(|table| (0..table.items.len()).map(Object).collect())
+            .unwrap_or_default()
+    }
+
+    fn navigate_to(&mut self, ty: Type, obj: Object) -> Result<(), EditorError> {
+        self.set_visible(ty, obj, true)?;
+        if let Some(id) = self.views.active() {
+            self.views.get_mut(id).ok_or_else(|| no_view(id))?.insert(ty, obj);
+        }
+        Ok(())
+    }
+
+    fn refresh_views(&mut self) {
+        let (tables, state) = (&self.tables, &self.state);
+        for view in self.views.iter_mut() {
+            for table in tables {
+                let objs = state.visible(table.ty, table.items.len()).into_iter()
+                    .filter(|&obj| table.items.in_view(obj, view))
+                    .collect();
+                view.set_visible(table.ty, objs);
+            }
+        }
+    }
+
+    fn views(&self) -> Vec<ViewId> {
+        self.views.ids()
+    }
+
+    fn view(&self, id: ViewId) -> Option<&View> {
+        self.views.get(id)
+    }
+
+    fn active_view(&self) -> Option<ViewId> {
+        self.views.active()
+    }
+
+    fn set_active_view(&mut self, id: ViewId) -> Result<(), EditorError> {
+        self.views.set_active(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Point([f64; 2]);
+
+    fn editor(n: usize) -> VecEditor {
+        let mut editor = VecEditor::new();
+        editor.register::<Point>(POINT);
+        editor.set_hit_2d::<Point>(POINT, |p, pos| {
+            (p.0[0] - pos[0]).abs() < 0.5 && (p.0[1] - pos[1]).abs() < 0.5
+        }).unwrap();
+        for i in 0..n { editor.insert(POINT, &Point([i as f64, 0.0])).unwrap(); }
+        editor
+    }
+
+    #[test]
+    fn selection() {
+        let mut editor = editor(4);
+        editor.select_multiple(POINT, &[Object(2), Object(0), Object(3)]).unwrap();
+        assert_eq!(editor.selected(POINT), Some(Object(2)));
+        editor.deselect_multiple(POINT, &[Object(2)]).unwrap();
+        assert_eq!(editor.selected(POINT), Some(Object(0)));
+        editor.select(POINT, Object(3)).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(0), Object(3)]);
+        editor.select(POINT, Object(1)).unwrap();
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1)]);
+        assert!(editor.select_multiple(POINT, &[Object(0), Object(4)]).is_err());
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1)]);
+    }
+
+    #[test]
+    fn delete_moves_state() {
+        let mut editor = editor(4);
+        editor.select_multiple(POINT, &[Object(1), Object(3)]).unwrap();
+        editor.set_visible(POINT, Object(3), false).unwrap();
+        assert_eq!(editor.delete(POINT, Object(1)).unwrap(), Some(Object(3)));
+        assert_eq!(editor.multiple_selected(POINT), vec![Object(1)]);
+        assert_eq!(editor.selected(POINT), Some(Object(1)));
+        assert_eq!(editor.visible(POINT), vec![Object(0), Object(2)]);
+        assert_eq!(editor.items::<Point>(POINT).unwrap()[1], Point([3.0, 0.0]));
+        assert!(editor.delete(POINT, Object(3)).is_err());
+    }
+
+    #[test]
+    fn hits_with_bounds() {
+        let mut editor = editor(3);
+        assert_eq!(editor.hit_2d([1.1, 0.0]), vec![(POINT, Object(1))]);
+        editor.set_bounds_2d::<Point>(POINT, |p| Bounds2 {
+            min: [p.0[0] - 0.5, p.0[1] - 0.5],
+            max: [p.0[0] + 0.5, p.0[1] + 0.5],
+        }).unwrap();
+        assert_eq!(editor.hit_2d([2.1, 0.0]), vec![(POINT, Object(2))]);
+        editor.update(POINT, Object(2), &Point([5.0, 5.0])).unwrap();
+        assert!(editor.hit_2d([2.1, 0.0]).is_empty());
+        assert_eq!(editor.hit_2d([5.0, 5.0]), vec![(POINT, Object(2))]);
+        editor.set_visible(POINT, Object(2), false).unwrap();
+        assert!(editor.hit_2d([5.0, 5.0]).is_empty());
+    }
+
+    #[test]
+    fn refuse_non_finite_bounds() {
+        let mut editor = editor(2);
+        editor.set_bounds_2d::<Point>(POINT, |p| Bounds2::point(p.0)).unwrap();
+        match editor.insert(POINT, &Point([f64::NAN, 0.0])) {
+            Err(EditorError::ConstraintViolation(_)) => {}
+            _ => panic!("expected a constraint violation"),
+        }
+        assert!(editor.update(POINT, Object(1), &Point([0.0, f64::INFINITY])).is_err());
+        assert_eq!(editor.items::<Point>(POINT).unwrap(), &[Point([0.0, 0.0]), Point([1.0, 0.0])]);
+        assert_eq!(editor.grid().len(), 2);
+    }
+
+    #[test]
+    fn views() {
+        let mut editor = editor(3);
+        editor.set_in_view::<Point>(POINT, |p, view| p.0[0] < view.viewport[2]).unwrap();
+        let id = editor.add_view([0.0, 0.0, 2.0, 1.0], Camera::default());
+        assert_eq!(editor.active_view(), Some(id));
+        assert!(editor.visible(POINT).is_empty());
+        editor.refresh_views();
+        assert_eq!(editor.visible(POINT), vec![Object(0), Object(1)]);
+        editor.navigate_to(POINT, Object(2)).unwrap();
+        assert_eq!(editor.visible(POINT), vec![Object(0), Object(1), Object(2)]);
+
+        // Registering again removes the objects from the views too.
+        editor.register::<Point>(POINT);
+        assert!(editor.view(id).unwrap().visible(POINT).is_empty());
+        editor.insert(POINT, &Point([0.0, 0.0])).unwrap();
+        assert!(editor.visible(POINT).is_empty());
+    }
+}
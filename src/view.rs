@@ -0,0 +1,314 @@
+use ray::{self, Ray};
+use {Bounds2, EditorError, Object, Plane, Type};
+
+/// A 4x4 matrix in column major order, `m[column][row]`.
+pub type Matrix4 = [[f64; 4]; 4];
+
+/// The identity matrix.
+pub const IDENTITY: Matrix4 = [
+    [1.0, 0.0, 0.0, 0.0],
+    [0.0, 1.0, 0.0, 0.0],
+    [0.0, 0.0, 1.0, 0.0],
+    [0.0, 0.0, 0.0, 1.0],
+];
+
+/// The view id.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ViewId(pub usize);
+
+/// The camera of a view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    /// Transforms from world to camera coordinates.
+    pub view: Matrix4,
+    /// Transforms from camera to normalized device coordinates.
+    pub projection: Matrix4,
+}
+
+impl Default for Camera {
+    fn default() -> Camera {
+        Camera {
+            view: IDENTITY,
+            projection: IDENTITY,
+        }
+    }
+}
+
+/// A view into the editor, for example one viewport of a split-screen editor.
+#[derive(Clone, Debug)]
+pub struct View {
+    /// The view id.
+    pub id: ViewId,
+    /// The viewport in window coordinates, `[x, y, width, height]`.
+    pub viewport: [f64; 4],
+    /// The camera.
+    pub camera: Camera,
+    visible: Vec<(Type, Vec<Object>)>,
+}
+
+impl View {
+    /// Creates a new view without visible objects.
+    pub fn new(id: ViewId, viewport: [f64; 4], camera: Camera) -> View {
+        View {
+            id,
+            viewport,
+            camera,
+            visible: vec![],
+        }
+    }
+
+    /// Gets the objects of a type visible in the view, sorted by id.
+    pub fn visible(&self, ty: Type) -> &[Object] {
+        self.visible.iter().find(|&&(t, _)| t == ty).map(|(_, objs)| &objs[..]).unwrap_or(&[])
+    }
+
+    /// Sets the objects of a type visible in the view.
+    ///
+    /// Usually called when refreshing views.
+    pub fn set_visible(&mut self, ty: Type, mut objs: Vec<Object>) {
+        objs.sort_by_key(|obj| obj.0);
+        objs.dedup();
+        match self.visible.iter_mut().find(|&&mut (t, _)| t == ty) {
+            Some(&mut (_, ref mut visible)) => *visible = objs,
+            None => self.visible.push((ty, objs)),
+        }
+    }
+
+    /// Gets the frustum of a rectangle in window coordinates,
+    /// for selecting everything inside it, see `Editor::hit_frustum`.
+    ///
+    /// The planes are left, right, bottom, top, near and far,
+    /// with normals pointing to the inside.
+    /// Returns `None` if the viewport or rectangle is empty.
+    pub fn frustum(&self, rect: Bounds2) -> Option<[Plane; 6]> {
+        let [x, y, w, h] = self.viewport;
+        if w <= 0.0 || h <= 0.0 { return None; }
+
+        let ndc_x = |px: f64| 2.0 * (px - x) / w - 1.0;
+        let ndc_y = |py: f64| 1.0 - 2.0 * (py - y) / h;
+        let (left, right) = (ndc_x(rect.min[0]), ndc_x(rect.max[0]));
+        // Window coordinates have y pointing down.
+        let (bottom, top) = (ndc_y(rect.max[1]), ndc_y(rect.min[1]));
+        if left >= right || bottom >= top { return None; }
+
+        // Extract the planes from the rows of the clip matrix.
+        let m = ray::mul(&self.camera.projection, &self.camera.view);
+        let row = |r: usize| [m[0][r], m[1][r], m[2][r], m[3][r]];
+        let (rx, ry, rz, rw) = (row(0), row(1), row(2), row(3));
+        // The plane of `ka * a + kb * b >= 0`.
+        let plane = |ka: f64, a: [f64; 4], kb: f64, b: [f64; 4]| Plane::from_coefficients(
+            ka * a[0] + kb * b[0],
+            ka * a[1] + kb * b[1],
+            ka * a[2] + kb * b[2],
+            ka * a[3] + kb * b[3],
+        );
+        Some([
+            plane(1.0, rx, -left, rw)?,
+            plane(-1.0, rx, right, rw)?,
+            plane(1.0, ry, -bottom, rw)?,
+            plane(-1.0, ry, top, rw)?,
+            plane(1.0, rz, 1.0, rw)?,
+            plane(-1.0, rz, 1.0, rw)?,
+        ])
+    }
+
+    /// Returns `true` if an object is visible in the view.
+    pub fn contains(&self, ty: Type, obj: Object) -> bool {
+        self.visible(ty).binary_search_by_key(&obj.0, |o| o.0).is_ok()
+    }
+
+    /// Makes an object visible in the view.
+    pub fn insert(&mut self, ty: Type, obj: Object) {
+        let objs = self.objs_mut(ty);
+        if let Err(i) = objs.binary_search_by_key(&obj.0, |o| o.0) { objs.insert(i, obj); }
+    }
+
+    /// Makes an object invisible in the view, returning `true` if it was visible.
+    pub fn remove(&mut self, ty: Type, obj: Object) -> bool {
+        let objs = self.objs_mut(ty);
+        match objs.binary_search_by_key(&obj.0, |o| o.0) {
+            Ok(i) => {
+                objs.remove(i);
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    /// Gets the ray through a position in window coordinates, such as `Editor::cursor_2d`.
+    ///
+    /// The ray starts at the near plane and has a direction of unit length.
+    /// Window coordinates have y pointing down.
+    /// Returns `None` if the viewport is empty or the camera matrices can not be inverted.
+    pub fn ray(&self, pos: [f64; 2]) -> Option<Ray> {
+        let [x, y, w, h] = self.viewport;
+        if w <= 0.0 || h <= 0.0 { return None; }
+
+        let ndc_x = 2.0 * (pos[0] - x) / w - 1.0;
+        let ndc_y = 1.0 - 2.0 * (pos[1] - y) / h;
+        let inv = ray::invert(&ray::mul(&self.camera.projection, &self.camera.view))?;
+        let near = ray::transform(&inv, [ndc_x, ndc_y, -1.0])?;
+        let far = ray::transform(&inv, [ndc_x, ndc_y, 1.0])?;
+        Some(Ray {
+            origin: near,
+            direction: ray::normalize(ray::sub(far, near))?,
+        })
+    }
+
+    /// Updates the visible objects after deleting an object with swap-remove.
+    pub fn deleted(&mut self, ty: Type, obj: Object, moved: Option<Object>) {
+        self.remove(ty, obj);
+        if let Some(last) = moved {
+            if self.remove(ty, last) { self.insert(ty, obj); }
+        }
+    }
+
+    fn objs_mut(&mut self, ty: Type) -> &mut Vec<Object> {
+        let i = match self.visible.iter().position(|&(t, _)| t == ty) {
+            Some(i) => i,
+            None => {
+                self.visible.push((ty, vec![]));
+                self.visible.len() - 1
+            }
+        };
+        &mut self.visible[i].1
+    }
+}
+
+/// Keeps the views of an editor, one of which is active.
+///
+/// A helper for implementing the view methods of `Editor`.
+#[derive(Clone, Debug, Default)]
+pub struct Views {
+    views: Vec<View>,
+    active: Option<ViewId>,
+    next_id: usize,
+}
+
+impl Views {
+    /// Creates a new empty list of views.
+    pub fn new() -> Views {
+        Views::default()
+    }
+
+    /// Adds a view, which becomes active if there is no active view.
+    pub fn add(&mut self, viewport: [f64; 4], camera: Camera) -> ViewId {
+        let id = ViewId(self.next_id);
+        self.next_id += 1;
+        self.views.push(View::new(id, viewport, camera));
+        if self.active.is_none() { self.active = Some(id); }
+        id
+    }
+
+    /// Removes a view, returning `true` if it was found.
+    ///
+    /// If the view was active, the first remaining view becomes active.
+    pub fn remove(&mut self, id: ViewId) -> bool {
+        let n = self.views.len();
+        self.views.retain(|view| view.id != id);
+        if self.active == Some(id) {
+            self.active = self.views.first().map(|view| view.id);
+        }
+        self.views.len() != n
+    }
+
+    /// Gets the view ids.
+    pub fn ids(&self) -> Vec<ViewId> {
+        self.views.iter().map(|view| view.id).collect()
+    }
+
+    /// Gets a view.
+    pub fn get(&self, id: ViewId) -> Option<&View> {
+        self.views.iter().find(|view| view.id == id)
+    }
+
+    /// Gets a mutable view.
+    pub fn get_mut(&mut self, id: ViewId) -> Option<&mut View> {
+        self.views.iter_mut().find(|view| view.id == id)
+    }
+
+    /// Gets all views.
+    pub fn iter(&self) -> ::std::slice::Iter<'_, View> {
+        self.views.iter()
+    }
+
+    /// Gets all views as mutable.
+    pub fn iter_mut(&mut self) -> ::std::slice::IterMut<'_, View> {
+        self.views.iter_mut()
+    }
+
+    /// Gets the active view id.
+    pub fn active(&self) -> Option<ViewId> {
+        self.active
+    }
+
+    /// Gets the active view.
+    pub fn active_view(&self) -> Option<&View> {
+        self.active.and_then(|id| self.get(id))
+    }
+
+    /// Sets the active view.
+    pub fn set_active(&mut self, id: ViewId) -> Result<(), EditorError> {
+        if self.get(id).is_none() { return Err(no_view(id)); }
+        self.active = Some(id);
+        Ok(())
+    }
+}
+
+/// The error returned when a view does not exist.
+pub(crate) fn no_view(id: ViewId) -> EditorError {
+    EditorError::ConstraintViolation(format!("view {} does not exist", id.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const POINT: Type = Type("point");
+    const LINE: Type = Type("line");
+
+    #[test]
+    fn visible() {
+        let mut view = View::new(ViewId(0), [0.0, 0.0, 100.0, 100.0], Camera::default());
+        assert!(view.visible(POINT).is_empty());
+        view.set_visible(POINT, vec![Object(3), Object(1), Object(3)]);
+        assert_eq!(view.visible(POINT), &[Object(1), Object(3)]);
+        view.insert(POINT, Object(2));
+        assert!(view.contains(POINT, Object(2)));
+        assert!(!view.contains(LINE, Object(2)));
+        assert!(view.remove(POINT, Object(1)));
+        assert!(!view.remove(POINT, Object(1)));
+        assert_eq!(view.visible(POINT), &[Object(2), Object(3)]);
+    }
+
+    #[test]
+    fn deleted() {
+        let mut view = View::new(ViewId(0), [0.0, 0.0, 100.0, 100.0], Camera::default());
+        view.set_visible(POINT, vec![Object(0), Object(4)]);
+        view.deleted(POINT, Object(0), Some(Object(4)));
+        assert_eq!(view.visible(POINT), &[Object(0)]);
+        view.deleted(POINT, Object(1), Some(Object(3)));
+        assert_eq!(view.visible(POINT), &[Object(0)]);
+        view.deleted(POINT, Object(0), None);
+        assert!(view.visible(POINT).is_empty());
+    }
+
+    #[test]
+    fn views() {
+        let mut views = Views::new();
+        assert_eq!(views.active(), None);
+        let a = views.add([0.0, 0.0, 50.0, 100.0], Camera::default());
+        let b = views.add([50.0, 0.0, 50.0, 100.0], Camera::default());
+        assert_eq!(views.ids(), vec![a, b]);
+        assert_eq!(views.active(), Some(a));
+        views.set_active(b).unwrap();
+        assert_eq!(views.active_view().unwrap().viewport[0], 50.0);
+        assert!(views.remove(b));
+        assert!(!views.remove(b));
+        assert_eq!(views.active(), Some(a));
+        assert!(views.set_active(b).is_err());
+        assert!(views.get(b).is_none());
+        // Ids are not reused.
+        assert_eq!(views.add([0.0; 4], Camera::default()), ViewId(2));
+    }
+}